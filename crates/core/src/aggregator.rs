@@ -0,0 +1,427 @@
+//! Multi-source price aggregation.
+//!
+//! With more than one [`PriceSource`](crate::PriceSource), every source's
+//! events pass through a [`PriceAggregator`] before they reach the TWAP
+//! calculator. It keeps each source's latest price per asset and, on every
+//! update, emits the median of the sources that are fresh (published within
+//! `max_age_secs` of the newest one) as a [`PriceUpdate`] whose `sources`
+//! names them. A fresh source further than the deviation threshold from that
+//! median raises an [`OracleEvent::DeviationAlarm`] once, and again only
+//! after it has come back within the threshold.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::types::OracleEvent;
+use joyride_oracle_wire::{DeviationAlarm, FixedPrice, PriceUpdate, RoundingMode};
+
+/// Default staleness cutoff: a source whose latest price is this many
+/// seconds older than the newest source's is left out.
+pub const DEFAULT_MAX_AGE_SECS: i64 = 5;
+/// Default relative deviation from the median that raises an alarm (0.5%).
+pub const DEFAULT_DEVIATION_THRESHOLD: f64 = 0.005;
+
+/// How fresh source prices are combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AggregationRule {
+    /// Median of every fresh source.
+    #[default]
+    Median,
+    /// Median of the fresh sources within the deviation threshold of the
+    /// all-source median, emitted only when at least `min_sources` are.
+    Quorum { min_sources: usize },
+}
+
+impl FromStr for AggregationRule {
+    type Err = anyhow::Error;
+
+    /// `median` or `quorum:<min_sources>`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.split_once(':') {
+            None if s == "median" => Ok(Self::Median),
+            Some(("quorum", min_sources)) => {
+                let min_sources = min_sources
+                    .parse()
+                    .with_context(|| format!("invalid quorum size `{min_sources}`"))?;
+                if min_sources == 0 {
+                    bail!("quorum size must be at least 1");
+                }
+                Ok(Self::Quorum { min_sources })
+            }
+            _ => bail!("unknown aggregation rule `{s}` (expected median or quorum:<n>)"),
+        }
+    }
+}
+
+/// Combines prices for the same asset from several named sources.
+#[derive(Debug, Clone)]
+pub struct PriceAggregator {
+    rule: AggregationRule,
+    max_age_secs: i64,
+    deviation_threshold: f64,
+    /// Latest live price per symbol, per source.
+    latest: HashMap<String, BTreeMap<String, PriceUpdate>>,
+    /// `(symbol, source)` pairs currently outside the threshold.
+    alarmed: HashSet<(String, String)>,
+}
+
+impl PriceAggregator {
+    pub fn new(rule: AggregationRule) -> Self {
+        Self {
+            rule,
+            max_age_secs: DEFAULT_MAX_AGE_SECS,
+            deviation_threshold: DEFAULT_DEVIATION_THRESHOLD,
+            latest: HashMap::new(),
+            alarmed: HashSet::new(),
+        }
+    }
+
+    pub fn with_max_age_secs(mut self, max_age_secs: i64) -> Self {
+        self.max_age_secs = max_age_secs;
+        self
+    }
+
+    /// Relative deviation from the median, e.g. `0.005` for 0.5%.
+    pub fn with_deviation_threshold(mut self, threshold: f64) -> Self {
+        self.deviation_threshold = threshold;
+        self
+    }
+
+    pub fn rule(&self) -> AggregationRule {
+        self.rule
+    }
+
+    /// Take one event from `source` and return the events to pass on.
+    ///
+    /// Live prices produce any new deviation alarms followed by the
+    /// aggregated price, or no price when a quorum is not met. Backfilled
+    /// prices are historical, so they pass through tagged with their source
+    /// instead of displacing the live ones. Errors are prefixed with the
+    /// source name; connection status passes through unchanged.
+    pub fn ingest(&mut self, source: &str, event: OracleEvent) -> Vec<OracleEvent> {
+        match event {
+            OracleEvent::Price(update) if !update.backfilled => self.aggregate(source, update),
+            OracleEvent::Price(update) => vec![OracleEvent::Price(PriceUpdate {
+                sources: vec![source.to_string()],
+                ..update
+            })],
+            OracleEvent::Error { message } => vec![OracleEvent::Error {
+                message: format!("{source}: {message}"),
+            }],
+            other => vec![other],
+        }
+    }
+
+    /// Run [`ingest`](Self::ingest) over `(source, event)` pairs from
+    /// `inputs`, sending the results to `output`, until every input closes
+    /// or `output` does.
+    pub async fn run(
+        mut self,
+        mut inputs: mpsc::Receiver<(String, OracleEvent)>,
+        output: mpsc::Sender<OracleEvent>,
+    ) {
+        while let Some((source, event)) = inputs.recv().await {
+            for event in self.ingest(&source, event) {
+                if output.send(event).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn aggregate(&mut self, source: &str, update: PriceUpdate) -> Vec<OracleEvent> {
+        let symbol = update.symbol.clone();
+        let latest = self.latest.entry(symbol.clone()).or_default();
+        if latest
+            .get(source)
+            .is_some_and(|previous| update.publish_time < previous.publish_time)
+        {
+            return Vec::new();
+        }
+        latest.insert(source.to_string(), update);
+
+        let newest = latest
+            .values()
+            .map(|update| update.publish_time)
+            .max()
+            .unwrap_or_default();
+        let fresh: Vec<(String, PriceUpdate)> = latest
+            .iter()
+            .filter(|(_, update)| update.publish_time >= newest - self.max_age_secs)
+            .map(|(name, update)| (name.clone(), update.clone()))
+            .collect();
+        let median = median(fresh.iter().map(|(_, update)| update.price).collect());
+
+        let mut events = Vec::new();
+        let mut agreeing = Vec::new();
+        for (name, update) in &fresh {
+            let deviation = if median == 0.0 {
+                0.0
+            } else {
+                ((update.price - median) / median).abs()
+            };
+            let key = (symbol.clone(), name.clone());
+            if deviation > self.deviation_threshold {
+                if self.alarmed.insert(key) {
+                    warn!(
+                        asset = %symbol,
+                        source = %name,
+                        price = update.price,
+                        median,
+                        deviation,
+                        "Price source deviates from the median"
+                    );
+                    events.push(OracleEvent::DeviationAlarm(DeviationAlarm {
+                        symbol: symbol.clone(),
+                        source: name.clone(),
+                        price: update.price,
+                        median,
+                        deviation,
+                        threshold: self.deviation_threshold,
+                        publish_time: update.publish_time,
+                    }));
+                }
+            } else {
+                if self.alarmed.remove(&key) {
+                    info!(asset = %symbol, source = %name, "Price source back within threshold");
+                }
+                agreeing.push((name.clone(), update.clone()));
+            }
+        }
+
+        let contributors = match self.rule {
+            AggregationRule::Median => fresh,
+            AggregationRule::Quorum { min_sources } if agreeing.len() >= min_sources => agreeing,
+            AggregationRule::Quorum { .. } => return events,
+        };
+        events.push(OracleEvent::Price(combine(symbol, contributors)));
+        events
+    }
+}
+
+/// Tag every event from `events` with `source` and forward it to an
+/// aggregator's input until either side closes.
+pub fn forward_tagged(
+    source: &str,
+    mut events: mpsc::Receiver<OracleEvent>,
+    input: mpsc::Sender<(String, OracleEvent)>,
+) {
+    let source = source.to_string();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            if input.send((source.clone(), event)).await.is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// The median contributor's price, exact when every contributor has a
+/// fixed price. `feed_id` is the (lower) median contributor's; EMA fields
+/// are the median of the contributors that carry one.
+fn combine(symbol: String, mut contributors: Vec<(String, PriceUpdate)>) -> PriceUpdate {
+    let sources = contributors.iter().map(|(name, _)| name.clone()).collect();
+    contributors.sort_by(|(_, a), (_, b)| a.price.total_cmp(&b.price));
+    let updates: Vec<&PriceUpdate> = contributors.iter().map(|(_, update)| update).collect();
+    let lower = &updates[(updates.len() - 1) / 2];
+    let upper = &updates[updates.len() / 2];
+
+    let fixed_price = match (lower.fixed_price, upper.fixed_price) {
+        (Some(lower), Some(upper)) => midpoint(lower, upper),
+        _ => None,
+    };
+    let ema: Vec<(f64, f64)> = updates
+        .iter()
+        .filter_map(|update| Some((update.ema_price?, update.ema_confidence.unwrap_or(0.0))))
+        .collect();
+
+    PriceUpdate {
+        symbol,
+        price: fixed_price.map_or_else(|| (lower.price + upper.price) / 2.0, FixedPrice::to_f64),
+        confidence: median(updates.iter().map(|update| update.confidence).collect()),
+        publish_time: updates
+            .iter()
+            .map(|update| update.publish_time)
+            .max()
+            .unwrap_or_default(),
+        feed_id: lower.feed_id.clone(),
+        fixed_price,
+        ema_price: (!ema.is_empty()).then(|| median(ema.iter().map(|&(price, _)| price).collect())),
+        ema_confidence: (!ema.is_empty())
+            .then(|| median(ema.iter().map(|&(_, conf)| conf).collect())),
+        backfilled: false,
+        sources,
+        // Slots and previous publish times belong to one source's feed.
+        // Signed update data attests one source's price, not the combined
+        // one, so aggregated prices carry none and neither do the TWAP
+        // samples and proofs built from them.
+        slot: None,
+        prev_publish_time: None,
+        update_data: None,
+    }
+}
+
+/// `(a + b) / 2` exactly, one digit finer than the finer of the two when
+/// the sum is odd.
+fn midpoint(a: FixedPrice, b: FixedPrice) -> Option<FixedPrice> {
+    let expo = a.expo.min(b.expo);
+    let a = a.rescale(expo, RoundingMode::HalfEven)?;
+    let b = b.rescale(expo, RoundingMode::HalfEven)?;
+    let sum = i128::from(a.mantissa) + i128::from(b.mantissa);
+    if sum % 2 == 0 {
+        Some(FixedPrice::new(i64::try_from(sum / 2).ok()?, expo))
+    } else {
+        Some(FixedPrice::new(i64::try_from(sum * 5).ok()?, expo - 1))
+    }
+}
+
+/// Median of `values`; the mean of the two middle ones when the count is
+/// even, and 0 when empty.
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A BTC update priced in cents.
+    fn update(price_cents: i64, publish_time: i64) -> PriceUpdate {
+        let fixed = FixedPrice::new(price_cents, -2);
+        PriceUpdate {
+            symbol: "BTC".to_string(),
+            price: fixed.to_f64(),
+            confidence: 0.5,
+            publish_time,
+            feed_id: "0xbtc".to_string(),
+            fixed_price: Some(fixed),
+            ..Default::default()
+        }
+    }
+
+    fn prices(events: &[OracleEvent]) -> Vec<&PriceUpdate> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                OracleEvent::Price(update) => Some(update),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn alarms(events: &[OracleEvent]) -> Vec<&DeviationAlarm> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                OracleEvent::DeviationAlarm(alarm) => Some(alarm),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn median_of_fresh_sources_names_contributors() {
+        let mut aggregator = PriceAggregator::new(AggregationRule::Median);
+
+        let events = aggregator.ingest("hermes", OracleEvent::Price(update(1000000, 100)));
+        assert_eq!(prices(&events)[0].price, 10_000.0);
+        assert_eq!(prices(&events)[0].sources, ["hermes"]);
+
+        aggregator.ingest("lazer", OracleEvent::Price(update(1000100, 101)));
+        let events = aggregator.ingest("ticker", OracleEvent::Price(update(1000201, 101)));
+        let aggregated = prices(&events)[0];
+        assert_eq!(aggregated.fixed_price, Some(FixedPrice::new(1000100, -2)));
+        assert_eq!(aggregated.publish_time, 101);
+        assert_eq!(aggregated.sources, ["hermes", "lazer", "ticker"]);
+
+        // Two sources: the exact midpoint, one digit finer.
+        let mut aggregator = PriceAggregator::new(AggregationRule::Median);
+        aggregator.ingest("hermes", OracleEvent::Price(update(1000000, 100)));
+        let events = aggregator.ingest("lazer", OracleEvent::Price(update(1000001, 100)));
+        assert_eq!(
+            prices(&events)[0].fixed_price,
+            Some(FixedPrice::new(10000005, -3))
+        );
+
+        // Hermes goes quiet; once stale it no longer counts.
+        let events = aggregator.ingest("lazer", OracleEvent::Price(update(1010000, 106)));
+        assert_eq!(prices(&events)[0].sources, ["lazer"]);
+        assert!(alarms(&events).is_empty());
+    }
+
+    #[test]
+    fn deviating_source_alarms_once_and_misses_quorum() {
+        let mut aggregator = PriceAggregator::new(AggregationRule::Quorum { min_sources: 2 })
+            .with_deviation_threshold(0.01);
+        aggregator.ingest("hermes", OracleEvent::Price(update(1000000, 100)));
+        aggregator.ingest("lazer", OracleEvent::Price(update(1000100, 100)));
+
+        let events = aggregator.ingest("ticker", OracleEvent::Price(update(1100000, 100)));
+        let alarm = alarms(&events);
+        assert_eq!(alarm.len(), 1);
+        assert_eq!(alarm[0].source, "ticker");
+        assert_eq!(alarm[0].median, 10_001.0);
+        assert!(alarm[0].deviation > 0.09);
+        // The quorum is the two agreeing sources; the outlier is left out.
+        assert_eq!(prices(&events)[0].sources, ["hermes", "lazer"]);
+
+        // Still out: no second alarm.
+        let events = aggregator.ingest("ticker", OracleEvent::Price(update(1100000, 101)));
+        assert!(alarms(&events).is_empty());
+
+        // Back in range, then out again: alarms afresh.
+        aggregator.ingest("ticker", OracleEvent::Price(update(1000050, 102)));
+        let events = aggregator.ingest("ticker", OracleEvent::Price(update(900000, 103)));
+        assert_eq!(alarms(&events).len(), 1);
+
+        // Only one source within range of the median: no quorum, no price.
+        let mut aggregator = PriceAggregator::new(AggregationRule::Quorum { min_sources: 2 })
+            .with_deviation_threshold(0.01);
+        aggregator.ingest("hermes", OracleEvent::Price(update(1000000, 100)));
+        let events = aggregator.ingest("lazer", OracleEvent::Price(update(1200000, 100)));
+        assert_eq!(alarms(&events).len(), 2);
+        assert!(prices(&events).is_empty());
+    }
+
+    #[test]
+    fn passes_through_status_errors_and_backfill() {
+        let mut aggregator = PriceAggregator::new(AggregationRule::Median);
+        let events = aggregator.ingest(
+            "lazer",
+            OracleEvent::Error {
+                message: "idle".to_string(),
+            },
+        );
+        assert!(
+            matches!(&events[..], [OracleEvent::Error { message }] if message == "lazer: idle")
+        );
+
+        let backfilled = PriceUpdate {
+            backfilled: true,
+            ..update(1000000, 90)
+        };
+        let events = aggregator.ingest("hermes", OracleEvent::Price(backfilled));
+        assert_eq!(prices(&events)[0].sources, ["hermes"]);
+        assert!(aggregator.latest.is_empty());
+
+        assert_eq!(
+            "quorum:2".parse::<AggregationRule>().unwrap(),
+            AggregationRule::Quorum { min_sources: 2 }
+        );
+        assert!("quorum:0".parse::<AggregationRule>().is_err());
+        assert!("mean".parse::<AggregationRule>().is_err());
+    }
+}
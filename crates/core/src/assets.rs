@@ -0,0 +1,672 @@
+//! Asset registry.
+//!
+//! The markets the oracle tracks are configuration, not code: each
+//! [`Asset`] names a symbol, its Pyth feed, the precision it settles at and
+//! its TWAP settings, and an [`AssetRegistry`] holds the set loaded from a
+//! JSON file shaped like
+//!
+//! ```json
+//! {
+//!   "assets": [
+//!     {
+//!       "symbol": "SOL",
+//!       "feed_id": "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d",
+//!       "decimals": 8,
+//!       "tick_size": 0.0001,
+//!       "twap": { "method": "time_weighted" }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Everything keyed on symbol (ingestion, TWAP configuration, settlement
+//! calendars, server bookkeeping) iterates the registry rather than a fixed
+//! list.
+//!
+//! `feed_id` may be left out, in which case [`AssetRegistry::resolve_feeds`]
+//! looks the asset up in the Hermes [`PriceFeedCatalog`] by its Pyth symbol
+//! (`pyth_symbol`, by default `Crypto.{symbol}/USD`). The same call checks
+//! that every configured ID exists there.
+//!
+//! `lazer_id` is the asset's numeric Pyth Lazer feed ID, needed only when
+//! prices come from [`crate::LazerClient`]. Likewise `ticker_product` is
+//! the exchange instrument [`crate::TickerClient`] maps onto the asset, by
+//! default `{symbol}-USD`.
+//!
+//! `health` overrides the [`FeedHealthThresholds`] the asset's feed is
+//! judged fresh, degraded, stale or halted by, and `spike_filter` sets the
+//! [`SpikePolicy`] its prints are quarantined by.
+//!
+//! `settlement` lists the [`ExpirySchedule`]s the asset settles on, e.g.
+//! `[{"kind": "daily", "hour": 8, "minute": 0}, {"kind": "weekly",
+//! "weekday": "Fri", "hour": 8, "minute": 0}]`. Without it the asset
+//! settles on [`DEFAULT_EXPIRY`], daily at 08:00 UTC.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::PriceFeedCatalog;
+use crate::health::{FeedHealthMonitor, FeedHealthThresholds};
+use crate::settlement::{ExpirySchedule, SettlementCalendar, DEFAULT_EXPIRY};
+use crate::spike::{SpikeFilter, SpikePolicy};
+use crate::twap_calculator::{
+    ConfidenceAction, ConfidencePolicy, CoveragePolicy, RoundingRule, TwapCalculator,
+};
+use joyride_oracle_wire::{RoundingMode, TwapMethod};
+
+/// Largest `decimals` an asset may settle at; `10^18` still fits the
+/// mantissa of a [`joyride_oracle_wire::FixedPrice`].
+const MAX_DECIMALS: u32 = 18;
+
+/// One tracked market.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Asset {
+    /// Symbol used on the wire and as the key everywhere else, e.g. `SOL`.
+    pub symbol: String,
+    /// Pyth price feed ID. Normalized to `0x`-prefixed lowercase hex when
+    /// the asset enters a registry; empty until resolved when omitted.
+    #[serde(default)]
+    pub feed_id: String,
+    /// Symbol of the feed in the Hermes catalog, e.g. `Crypto.SOL/USD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pyth_symbol: Option<String>,
+    /// Pyth Lazer price feed ID, e.g. `6` for SOL/USD.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lazer_id: Option<u32>,
+    /// Exchange ticker product, e.g. `SOL-USD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticker_product: Option<String>,
+    /// Decimal places settlement prices are rounded to.
+    pub decimals: u32,
+    /// Smallest price increment the market quotes in, for consumers sizing
+    /// orders against the oracle price.
+    pub tick_size: f64,
+    #[serde(default)]
+    pub twap: AssetTwapSettings,
+    /// Feed health thresholds, when they differ from the service default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<FeedHealthThresholds>,
+    /// Band beyond which prints are quarantined; unfiltered when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spike_filter: Option<SpikePolicy>,
+    /// Expiries the asset settles on; [`DEFAULT_EXPIRY`] when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settlement: Vec<ExpirySchedule>,
+}
+
+/// Per-asset TWAP settings. Unset fields fall back to the calculator's own
+/// defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AssetTwapSettings {
+    #[serde(default)]
+    pub method: Option<TwapMethod>,
+    /// How the exact TWAP is rounded to `decimals`.
+    #[serde(default)]
+    pub rounding_mode: RoundingMode,
+    #[serde(default)]
+    pub coverage_policy: Option<CoveragePolicy>,
+    /// Ceiling on confidence relative to price, and what happens to samples
+    /// above it.
+    #[serde(default)]
+    pub confidence_policy: Option<ConfidencePolicy>,
+}
+
+impl Asset {
+    /// The Hermes catalog symbol this asset's feed is listed under.
+    pub fn pyth_symbol(&self) -> String {
+        self.pyth_symbol
+            .clone()
+            .unwrap_or_else(|| format!("Crypto.{}/USD", self.symbol))
+    }
+
+    /// The exchange ticker product this asset is quoted as.
+    pub fn ticker_product(&self) -> String {
+        self.ticker_product
+            .clone()
+            .unwrap_or_else(|| format!("{}-USD", self.symbol))
+    }
+
+    /// The expiries this asset settles on.
+    pub fn settlement_schedules(&self) -> Vec<ExpirySchedule> {
+        if self.settlement.is_empty() {
+            vec![DEFAULT_EXPIRY]
+        } else {
+            self.settlement.clone()
+        }
+    }
+
+    /// The rule rounding this asset's TWAP to `decimals` places.
+    pub fn rounding(&self) -> RoundingRule {
+        RoundingRule {
+            mode: self.twap.rounding_mode,
+            expo: Some(-(self.decimals as i32)),
+        }
+    }
+}
+
+impl std::fmt::Display for Asset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol)
+    }
+}
+
+/// The set of tracked assets, in configuration order. Symbols, feed IDs,
+/// Lazer IDs and ticker products are unique.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AssetRegistry {
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistryFile {
+    assets: Vec<Asset>,
+}
+
+impl AssetRegistry {
+    /// Validate `assets` and normalize their feed IDs. Empty feed IDs are
+    /// left for [`Self::resolve_feeds`].
+    pub fn new(assets: Vec<Asset>) -> anyhow::Result<Self> {
+        let mut registry = Self {
+            assets: Vec::with_capacity(assets.len()),
+        };
+        for mut asset in assets {
+            if asset.symbol.is_empty() || asset.symbol.trim() != asset.symbol {
+                bail!("invalid asset symbol `{}`", asset.symbol);
+            }
+            if !asset.feed_id.is_empty() {
+                asset.feed_id = normalize_feed_id(&asset.feed_id).with_context(|| {
+                    format!("{}: invalid feed_id `{}`", asset.symbol, asset.feed_id)
+                })?;
+            }
+            if asset.decimals > MAX_DECIMALS {
+                bail!(
+                    "{}: decimals must be at most {MAX_DECIMALS} (got {})",
+                    asset.symbol,
+                    asset.decimals
+                );
+            }
+            if !(asset.tick_size.is_finite() && asset.tick_size > 0.0) {
+                bail!(
+                    "{}: tick_size must be positive (got {})",
+                    asset.symbol,
+                    asset.tick_size
+                );
+            }
+            if let Some(TwapMethod::TrimmedMean { trim_pct }) = asset.twap.method {
+                if trim_pct >= 50 {
+                    bail!(
+                        "{}: trim_pct must be below 50 (got {trim_pct})",
+                        asset.symbol
+                    );
+                }
+            }
+            if let Some(policy) = &asset.twap.coverage_policy {
+                if !(0.0..=1.0).contains(&policy.min_coverage) {
+                    bail!(
+                        "{}: min_coverage must be within 0.0..=1.0 (got {})",
+                        asset.symbol,
+                        policy.min_coverage
+                    );
+                }
+            }
+            if let Some(policy) = &asset.twap.confidence_policy {
+                if policy.max_ratio.is_nan() || policy.max_ratio <= 0.0 {
+                    bail!(
+                        "{}: max_ratio must be positive (got {})",
+                        asset.symbol,
+                        policy.max_ratio
+                    );
+                }
+                if let ConfidenceAction::DownWeight { weight } = policy.action {
+                    if !(weight > 0.0 && weight <= 1.0) {
+                        bail!(
+                            "{}: down-weight must be within (0.0, 1.0] (got {weight})",
+                            asset.symbol
+                        );
+                    }
+                }
+            }
+            if let Some(health) = &asset.health {
+                if let Err(e) = health.validate() {
+                    bail!("{}: {e}", asset.symbol);
+                }
+            }
+            if let Some(policy) = &asset.spike_filter {
+                if let Err(e) = policy.validate() {
+                    bail!("{}: {e}", asset.symbol);
+                }
+            }
+            for schedule in &asset.settlement {
+                if let Err(e) = schedule.validate() {
+                    bail!("{}: {e}", asset.symbol);
+                }
+            }
+            if registry.get(&asset.symbol).is_some() {
+                bail!("duplicate asset symbol `{}`", asset.symbol);
+            }
+            if let Some(other) = registry.by_feed_id(&asset.feed_id) {
+                bail!(
+                    "{} and {} share feed_id {}",
+                    other.symbol,
+                    asset.symbol,
+                    asset.feed_id
+                );
+            }
+            if let Some(other) = asset.lazer_id.and_then(|id| registry.by_lazer_id(id)) {
+                bail!(
+                    "{} and {} share lazer_id {}",
+                    other.symbol,
+                    asset.symbol,
+                    other.lazer_id.unwrap_or_default()
+                );
+            }
+            if let Some(other) = registry.by_ticker_product(&asset.ticker_product()) {
+                bail!(
+                    "{} and {} share ticker_product {}",
+                    other.symbol,
+                    asset.symbol,
+                    asset.ticker_product()
+                );
+            }
+            registry.assets.push(asset);
+        }
+        Ok(registry)
+    }
+
+    /// Parse a registry from `{"assets": [...]}` JSON.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let file: RegistryFile = serde_json::from_str(json).context("parsing asset registry")?;
+        Self::new(file.assets)
+    }
+
+    /// Read and parse the registry file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("loading {}", path.display()))
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.symbol == symbol)
+    }
+
+    /// The asset for `feed_id`, with or without a `0x` prefix and in any
+    /// case.
+    pub fn by_feed_id(&self, feed_id: &str) -> Option<&Asset> {
+        let feed_id = normalize_feed_id(feed_id)?;
+        self.assets.iter().find(|asset| asset.feed_id == feed_id)
+    }
+
+    pub fn by_lazer_id(&self, lazer_id: u32) -> Option<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.lazer_id == Some(lazer_id))
+    }
+
+    pub fn by_ticker_product(&self, product: &str) -> Option<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.ticker_product() == product)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Asset> {
+        self.assets.iter()
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.assets.iter().map(|asset| asset.symbol.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// Whether every asset has a feed ID.
+    pub fn is_resolved(&self) -> bool {
+        self.assets.iter().all(|asset| !asset.feed_id.is_empty())
+    }
+
+    /// Fill in missing feed IDs from `catalog` and check the configured ones
+    /// against it. A configured ID must be listed, and under the asset's
+    /// `pyth_symbol` when one is given. Every problem is reported in the
+    /// error, and the registry is unchanged unless all assets check out.
+    pub fn resolve_feeds(&mut self, catalog: &PriceFeedCatalog) -> anyhow::Result<()> {
+        let mut assets = self.assets.clone();
+        let mut problems = Vec::new();
+        for asset in &mut assets {
+            if asset.feed_id.is_empty() {
+                match catalog.resolve(&asset.pyth_symbol()) {
+                    Ok(feed) => asset.feed_id = feed.id.clone(),
+                    Err(e) => problems.push(format!("{}: {e}", asset.symbol)),
+                }
+                continue;
+            }
+            match catalog.get(&asset.feed_id) {
+                None => problems.push(format!(
+                    "{}: feed {} is not in the Hermes catalog",
+                    asset.symbol, asset.feed_id
+                )),
+                Some(feed) => {
+                    if let (Some(expected), Some(listed)) = (&asset.pyth_symbol, feed.symbol()) {
+                        if expected != listed {
+                            problems.push(format!(
+                                "{}: feed {} is `{listed}`, not `{expected}`",
+                                asset.symbol, asset.feed_id
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        if !problems.is_empty() {
+            bail!("unknown or mismatched feeds: {}", problems.join("; "));
+        }
+        *self = Self::new(assets)?;
+        Ok(())
+    }
+
+    /// Apply every asset's TWAP settings to `calculator`.
+    pub fn configure(&self, mut calculator: TwapCalculator) -> TwapCalculator {
+        for asset in &self.assets {
+            calculator = calculator.with_symbol_rounding(&asset.symbol, asset.rounding());
+            if let Some(method) = asset.twap.method {
+                calculator = calculator.with_symbol_method(&asset.symbol, method);
+            }
+            if let Some(policy) = &asset.twap.coverage_policy {
+                calculator = calculator.with_coverage_policy(&asset.symbol, policy.clone());
+            }
+            if let Some(policy) = asset.twap.confidence_policy {
+                calculator = calculator.with_confidence_policy(&asset.symbol, policy);
+            }
+        }
+        calculator
+    }
+
+    /// Apply every asset's health thresholds to `monitor`.
+    pub fn configure_health(&self, mut monitor: FeedHealthMonitor) -> FeedHealthMonitor {
+        for asset in &self.assets {
+            if let Some(thresholds) = asset.health {
+                monitor = monitor.with_symbol_thresholds(&asset.symbol, thresholds);
+            }
+        }
+        monitor
+    }
+
+    /// Every asset's expiries, as one calendar.
+    pub fn settlement_calendar(&self) -> SettlementCalendar {
+        let mut calendar = SettlementCalendar::new();
+        for asset in &self.assets {
+            for schedule in asset.settlement_schedules() {
+                calendar.add(&asset.symbol, schedule);
+            }
+        }
+        calendar
+    }
+
+    /// Apply every asset's spike policy to `filter`.
+    pub fn configure_spikes(&self, mut filter: SpikeFilter) -> SpikeFilter {
+        for asset in &self.assets {
+            if let Some(policy) = asset.spike_filter {
+                filter = filter.with_symbol_policy(&asset.symbol, policy);
+            }
+        }
+        filter
+    }
+}
+
+impl<'a> IntoIterator for &'a AssetRegistry {
+    type Item = &'a Asset;
+    type IntoIter = std::slice::Iter<'a, Asset>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// `feed_id` as `0x`-prefixed lowercase hex, or `None` unless it is 32
+/// bytes of hex. Hermes returns bare hex; configuration usually carries the
+/// prefix.
+pub fn normalize_feed_id(feed_id: &str) -> Option<String> {
+    let hex = feed_id
+        .strip_prefix("0x")
+        .or_else(|| feed_id.strip_prefix("0X"))
+        .unwrap_or(feed_id);
+    (hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .then(|| format!("0x{}", hex.to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL_FEED: &str = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
+
+    fn asset(symbol: &str, feed_id: &str) -> Asset {
+        Asset {
+            symbol: symbol.to_string(),
+            feed_id: feed_id.to_string(),
+            pyth_symbol: None,
+            lazer_id: None,
+            ticker_product: None,
+            decimals: 2,
+            tick_size: 0.01,
+            twap: AssetTwapSettings::default(),
+            health: None,
+            spike_filter: None,
+            settlement: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parses_and_normalizes_feed_ids() {
+        let registry = AssetRegistry::from_json(
+            r#"{"assets": [{
+                "symbol": "SOL",
+                "feed_id": "EF0D8B6FDA2CEBA41DA15D4095D1DA392A0D2F8ED0C6C7BC0F4CFAC8C280B56D",
+                "decimals": 4,
+                "tick_size": 0.001,
+                "twap": {"method": "median", "rounding_mode": "half_up"}
+            }]}"#,
+        )
+        .unwrap();
+
+        let sol = registry.get("SOL").unwrap();
+        assert_eq!(sol.feed_id, SOL_FEED);
+        assert_eq!(sol.twap.method, Some(TwapMethod::Median));
+        assert_eq!(
+            sol.rounding(),
+            RoundingRule {
+                mode: RoundingMode::HalfUp,
+                expo: Some(-4)
+            }
+        );
+        assert_eq!(registry.by_feed_id(&SOL_FEED[2..]), Some(sol));
+        assert_eq!(registry.by_feed_id("0x1234"), None);
+        assert_eq!(registry.symbols().collect::<Vec<_>>(), ["SOL"]);
+    }
+
+    #[test]
+    fn rejects_invalid_registries() {
+        assert!(AssetRegistry::new(vec![asset("SOL", "0xef0d")]).is_err());
+        assert!(
+            AssetRegistry::new(vec![asset("SOL", SOL_FEED), asset("SOL", &"1".repeat(64))])
+                .is_err()
+        );
+        assert!(
+            AssetRegistry::new(vec![asset("SOL", SOL_FEED), asset("JITOSOL", SOL_FEED)]).is_err()
+        );
+        assert!(AssetRegistry::new(vec![Asset {
+            tick_size: 0.0,
+            ..asset("SOL", SOL_FEED)
+        }])
+        .is_err());
+        assert!(AssetRegistry::new(vec![
+            Asset {
+                lazer_id: Some(6),
+                ..asset("SOL", SOL_FEED)
+            },
+            Asset {
+                lazer_id: Some(6),
+                ..asset("BTC", "")
+            },
+        ])
+        .is_err());
+        assert!(AssetRegistry::new(vec![
+            asset("SOL", SOL_FEED),
+            Asset {
+                ticker_product: Some("SOL-USD".to_string()),
+                ..asset("WSOL", "")
+            },
+        ])
+        .is_err());
+        assert!(AssetRegistry::new(vec![Asset {
+            health: Some(FeedHealthThresholds {
+                stale_after_secs: 1,
+                ..FeedHealthThresholds::default()
+            }),
+            ..asset("SOL", SOL_FEED)
+        }])
+        .is_err());
+        assert!(AssetRegistry::new(vec![Asset {
+            spike_filter: Some(SpikePolicy::max_move_per_sec(0.0)),
+            ..asset("SOL", SOL_FEED)
+        }])
+        .is_err());
+        assert!(AssetRegistry::new(vec![Asset {
+            settlement: vec![ExpirySchedule::Daily {
+                hour: 24,
+                minute: 0
+            }],
+            ..asset("SOL", SOL_FEED)
+        }])
+        .is_err());
+        assert!(AssetRegistry::from_json(r#"{"assets": [{"symbol": "SOL"}]}"#).is_err());
+    }
+
+    #[test]
+    fn resolves_and_validates_feeds_against_catalog() {
+        let catalog =
+            PriceFeedCatalog::from_json(include_str!("../fixtures/hermes_price_feeds.json"))
+                .unwrap();
+        let mut registry = AssetRegistry::from_json(
+            r#"{"assets": [
+                {"symbol": "SOL", "decimals": 4, "tick_size": 0.001},
+                {"symbol": "BTC", "pyth_symbol": "Crypto.BTC/USD", "decimals": 2, "tick_size": 0.01,
+                 "feed_id": "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43"}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(!registry.is_resolved());
+        registry.resolve_feeds(&catalog).unwrap();
+        assert!(registry.is_resolved());
+        assert_eq!(registry.get("SOL").unwrap().feed_id, SOL_FEED);
+
+        // A typo, a feed listed under another symbol, and an unknown symbol.
+        let mut registry = AssetRegistry::new(vec![
+            asset("SOL", &SOL_FEED.replace('e', "f")),
+            Asset {
+                pyth_symbol: Some("Crypto.ETH/USD".to_string()),
+                ..asset("ETH", SOL_FEED)
+            },
+            asset("DOGE", ""),
+        ])
+        .unwrap();
+        let error = registry.resolve_feeds(&catalog).unwrap_err().to_string();
+        assert!(error.contains("SOL: feed 0xff0d"), "{error}");
+        assert!(
+            error.contains("is `Crypto.SOL/USD`, not `Crypto.ETH/USD`"),
+            "{error}"
+        );
+        assert!(error.contains("DOGE: no Hermes feed"), "{error}");
+        assert!(!registry.is_resolved());
+    }
+
+    #[test]
+    fn configure_applies_per_asset_settings() {
+        let registry = AssetRegistry::new(vec![Asset {
+            twap: AssetTwapSettings {
+                method: Some(TwapMethod::TimeWeighted),
+                rounding_mode: RoundingMode::Floor,
+                coverage_policy: Some(CoveragePolicy {
+                    min_coverage: 0.5,
+                    fallbacks: Vec::new(),
+                }),
+                confidence_policy: Some(ConfidencePolicy {
+                    max_ratio: 0.01,
+                    action: ConfidenceAction::Flag,
+                }),
+            },
+            health: Some(FeedHealthThresholds {
+                stale_after_secs: 5,
+                degraded_lag_secs: 2,
+                ..FeedHealthThresholds::default()
+            }),
+            spike_filter: Some(SpikePolicy::max_move_per_sec(0.02)),
+            ..asset("SOL", SOL_FEED)
+        }])
+        .unwrap();
+
+        let calculator = registry.configure(TwapCalculator::new());
+        assert_eq!(calculator.method_for("SOL"), TwapMethod::TimeWeighted);
+        assert_eq!(calculator.method_for("BTC"), TwapMethod::Mean);
+        assert_eq!(calculator.rounding_for("SOL").expo, Some(-2));
+        assert!(calculator.coverage_policy("SOL").is_some());
+        assert!(calculator.confidence_policy("SOL").is_some());
+        assert!(calculator.confidence_policy("BTC").is_none());
+
+        let monitor = registry.configure_health(FeedHealthMonitor::default());
+        assert_eq!(monitor.thresholds_for("SOL").stale_after_secs, 5);
+        assert_eq!(
+            monitor.thresholds_for("BTC"),
+            FeedHealthThresholds::default()
+        );
+
+        let filter = registry.configure_spikes(SpikeFilter::new());
+        assert!(filter.policy("SOL").is_some());
+        assert!(filter.policy("BTC").is_none());
+    }
+
+    #[test]
+    fn settlement_calendar_defaults_to_daily_and_adds_weekly() {
+        let registry = AssetRegistry::from_json(&format!(
+            r#"{{"assets": [
+                {{"symbol": "SOL", "feed_id": "{SOL_FEED}", "decimals": 4, "tick_size": 0.001,
+                 "settlement": [{{"kind": "daily", "hour": 8, "minute": 0}},
+                                {{"kind": "weekly", "weekday": "Fri", "hour": 16, "minute": 0}}]}},
+                {{"symbol": "BTC", "decimals": 2, "tick_size": 0.01}}
+            ]}}"#
+        ))
+        .unwrap();
+        assert_eq!(
+            registry.get("SOL").unwrap().settlement_schedules()[1],
+            ExpirySchedule::weekly(chrono::Weekday::Fri, 16, 0)
+        );
+        assert_eq!(
+            registry.get("BTC").unwrap().settlement_schedules(),
+            [DEFAULT_EXPIRY]
+        );
+
+        // 2026-04-23 is a Thursday; both settle at 08:00 the next morning,
+        // and SOL again at 16:00.
+        let calendar = registry.settlement_calendar();
+        let thursday_noon = 1_776_945_600;
+        let (friday_8am, symbols) = calendar.next_expiry(thursday_noon).unwrap();
+        assert_eq!(friday_8am, thursday_noon + 20 * 3600);
+        assert_eq!(symbols, ["BTC", "SOL"]);
+        assert_eq!(
+            calendar.next_expiry(friday_8am),
+            Some((friday_8am + 8 * 3600, vec!["SOL".to_string()]))
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Hermes price-feed catalog.
+//!
+//! Hermes lists every feed it serves at `GET /v2/price_feeds`, each with its
+//! ID and descriptive attributes such as `symbol` (`Crypto.SOL/USD`),
+//! `base` and `quote_currency`. [`PriceFeedCatalog`] wraps that listing so
+//! configured feed IDs can be checked against it and assets configured by
+//! symbol alone can be resolved to an ID, instead of a mistyped ID silently
+//! matching no updates.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::assets::normalize_feed_id;
+
+/// One entry of the Hermes catalog.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogFeed {
+    /// Feed ID, normalized to `0x`-prefixed lowercase hex once in a
+    /// [`PriceFeedCatalog`].
+    pub id: String,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl CatalogFeed {
+    /// The Pyth symbol, e.g. `Crypto.SOL/USD`.
+    pub fn symbol(&self) -> Option<&str> {
+        self.attributes.get("symbol").map(String::as_str)
+    }
+}
+
+/// The feeds Hermes serves.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceFeedCatalog {
+    feeds: Vec<CatalogFeed>,
+}
+
+impl PriceFeedCatalog {
+    /// Wrap catalog entries, normalizing their IDs. Entries whose ID is not
+    /// 32 bytes of hex are dropped.
+    pub fn new(feeds: Vec<CatalogFeed>) -> Self {
+        let feeds = feeds
+            .into_iter()
+            .filter_map(|mut feed| {
+                feed.id = normalize_feed_id(&feed.id)?;
+                Some(feed)
+            })
+            .collect();
+        Self { feeds }
+    }
+
+    /// Parse the JSON array `GET /v2/price_feeds` returns.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let feeds: Vec<CatalogFeed> =
+            serde_json::from_str(json).context("parsing Hermes price-feed catalog")?;
+        Ok(Self::new(feeds))
+    }
+
+    /// Download the full catalog from the Hermes instance at `hermes_url`.
+    pub async fn fetch(hermes_url: &str) -> anyhow::Result<Self> {
+        let url = format!("{hermes_url}/v2/price_feeds");
+        let feeds: Vec<CatalogFeed> = reqwest::get(&url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("fetching {url}"))?
+            .json()
+            .await
+            .with_context(|| format!("parsing {url}"))?;
+        Ok(Self::new(feeds))
+    }
+
+    /// Download the catalog from the first of `hermes_urls` that serves it.
+    pub async fn fetch_any(hermes_urls: &[String]) -> anyhow::Result<Self> {
+        let mut last_error = anyhow::anyhow!("no Hermes endpoints configured");
+        for hermes_url in hermes_urls {
+            match Self::fetch(hermes_url).await {
+                Ok(catalog) => return Ok(catalog),
+                Err(e) => {
+                    tracing::warn!("Hermes catalog unavailable: {e:#}");
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// The feed with `id`, with or without a `0x` prefix and in any case.
+    pub fn get(&self, id: &str) -> Option<&CatalogFeed> {
+        let id = normalize_feed_id(id)?;
+        self.feeds.iter().find(|feed| feed.id == id)
+    }
+
+    /// The single feed whose Pyth symbol is `symbol`. Errors when none or
+    /// several match.
+    pub fn resolve(&self, symbol: &str) -> anyhow::Result<&CatalogFeed> {
+        let mut matches = self
+            .feeds
+            .iter()
+            .filter(|feed| feed.symbol() == Some(symbol));
+        match (matches.next(), matches.next()) {
+            (Some(feed), None) => Ok(feed),
+            (None, _) => bail!("no Hermes feed has symbol `{symbol}`"),
+            (Some(first), Some(second)) => bail!(
+                "Hermes symbol `{symbol}` is ambiguous ({}, {}, ...)",
+                first.id,
+                second.id
+            ),
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, CatalogFeed> {
+        self.feeds.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.feeds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.feeds.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const FIXTURE: &str = include_str!("../fixtures/hermes_price_feeds.json");
+
+    #[test]
+    fn parses_fixture_and_resolves_symbols() {
+        let catalog = PriceFeedCatalog::from_json(FIXTURE).unwrap();
+        assert_eq!(catalog.len(), 5);
+
+        let sol = catalog.resolve("Crypto.SOL/USD").unwrap();
+        assert_eq!(
+            sol.id,
+            "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d"
+        );
+        assert_eq!(catalog.get(&sol.id[2..].to_uppercase()), Some(sol));
+        assert!(catalog.resolve("Crypto.DOGE/USD").is_err());
+        assert!(catalog
+            .resolve("Crypto.WBTC/USD")
+            .unwrap_err()
+            .to_string()
+            .contains("ambiguous"));
+    }
+
+    #[tokio::test]
+    async fn fetches_catalog_from_hermes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 1024];
+            let n = stream.read(&mut request).await.unwrap();
+            assert!(request[..n].starts_with(b"GET /v2/price_feeds "));
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{FIXTURE}",
+                FIXTURE.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let catalog = PriceFeedCatalog::fetch(&format!("http://{addr}"))
+            .await
+            .unwrap();
+        assert_eq!(catalog, PriceFeedCatalog::from_json(FIXTURE).unwrap());
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,451 @@
+//! Per-asset feed health.
+//!
+//! A [`FeedHealthMonitor`] sits between the price sources and the rest of
+//! the pipeline. It passes every event through, tracks each asset's newest
+//! publish time and how long it has gone without advancing, and classifies
+//! the asset as [`FeedHealth::Fresh`], `Degraded`, `Stale` or `Halted`
+//! against its [`FeedHealthThresholds`]. Every change of state is emitted
+//! as an [`OracleEvent::FeedHealth`]. A one-second tick re-checks assets
+//! between updates, so a feed that goes silent turns stale on time rather
+//! than at its next update.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, MissedTickBehavior};
+
+use crate::source::unix_now_secs;
+use crate::types::OracleEvent;
+use joyride_oracle_wire::{FeedHealth, FeedHealthChange, PriceUpdate};
+
+/// Default receive lag, in seconds, above which a feed is degraded.
+pub const DEFAULT_DEGRADED_LAG_SECS: i64 = 10;
+/// Default number of receive seconds in a row without a new publish time
+/// after which a feed is degraded.
+pub const DEFAULT_DEGRADED_UNCHANGED_STREAK: u32 = 5;
+/// Default age, in seconds, of the newest price above which it is stale.
+pub const DEFAULT_STALE_AFTER_SECS: i64 = 30;
+/// Default age, in seconds, of the newest price above which the feed is
+/// considered halted.
+pub const DEFAULT_HALTED_AFTER_SECS: i64 = 300;
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where one asset's feed crosses from one [`FeedHealth`] state into the
+/// next. Ages are measured from the newest publish time to now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedHealthThresholds {
+    /// Age above which a feed is degraded.
+    pub degraded_lag_secs: i64,
+    /// Receive seconds in a row without a new publish time after which a
+    /// feed is degraded.
+    pub degraded_unchanged_streak: u32,
+    /// Age above which the price is stale.
+    pub stale_after_secs: i64,
+    /// Age above which the feed is halted.
+    pub halted_after_secs: i64,
+}
+
+impl Default for FeedHealthThresholds {
+    fn default() -> Self {
+        Self {
+            degraded_lag_secs: DEFAULT_DEGRADED_LAG_SECS,
+            degraded_unchanged_streak: DEFAULT_DEGRADED_UNCHANGED_STREAK,
+            stale_after_secs: DEFAULT_STALE_AFTER_SECS,
+            halted_after_secs: DEFAULT_HALTED_AFTER_SECS,
+        }
+    }
+}
+
+impl FeedHealthThresholds {
+    /// Check the ages are positive and increase from degraded to halted.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            0 < self.degraded_lag_secs
+                && self.degraded_lag_secs < self.stale_after_secs
+                && self.stale_after_secs < self.halted_after_secs,
+            "health thresholds must satisfy 0 < degraded_lag_secs ({}) < stale_after_secs ({}) < halted_after_secs ({})",
+            self.degraded_lag_secs,
+            self.stale_after_secs,
+            self.halted_after_secs
+        );
+        ensure!(
+            self.degraded_unchanged_streak > 0,
+            "degraded_unchanged_streak must be positive"
+        );
+        Ok(())
+    }
+
+    /// The state of a feed whose newest price is `age_secs` old (`None`
+    /// before any price) and whose publish time has not advanced for
+    /// `unchanged_streak` receive seconds.
+    pub fn classify(&self, age_secs: Option<i64>, unchanged_streak: u32) -> FeedHealth {
+        match age_secs {
+            None => FeedHealth::Stale,
+            Some(age) if age > self.halted_after_secs => FeedHealth::Halted,
+            Some(age) if age > self.stale_after_secs => FeedHealth::Stale,
+            Some(age)
+                if age > self.degraded_lag_secs
+                    || unchanged_streak >= self.degraded_unchanged_streak =>
+            {
+                FeedHealth::Degraded
+            }
+            Some(_) => FeedHealth::Fresh,
+        }
+    }
+}
+
+/// Newest publish time of one feed and how long it has repeated.
+#[derive(Debug, Default)]
+pub(crate) struct FreshnessTracker {
+    prev_publish_time: Option<i64>,
+    unchanged_streak: u32,
+    /// Receive second the streak last grew in, so several updates in one
+    /// second count once.
+    streak_receive_time: Option<i64>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct FreshnessObservation {
+    pub(crate) publish_advanced: bool,
+    pub(crate) unchanged_streak: u32,
+    pub(crate) receive_lag_ms: i64,
+    pub(crate) publish_gap_secs: Option<i64>,
+    /// The feed's own previous publish time is later than the last one we
+    /// saw, so at least one update never reached us.
+    pub(crate) missed_update: bool,
+}
+
+impl FreshnessTracker {
+    /// `prev_publish_time` is the publish time the feed reports for its
+    /// previous update, when the source carries one (Hermes does).
+    pub(crate) fn observe(
+        &mut self,
+        publish_time: i64,
+        prev_publish_time: Option<i64>,
+        receive_time: i64,
+    ) -> FreshnessObservation {
+        let publish_gap_secs = self
+            .prev_publish_time
+            .map(|previous_publish_time| publish_time.saturating_sub(previous_publish_time));
+        let publish_advanced = self
+            .prev_publish_time
+            .map(|prev| prev != publish_time)
+            .unwrap_or(true);
+        let missed_update = publish_advanced
+            && matches!(
+                (self.prev_publish_time, prev_publish_time),
+                (Some(seen), Some(reported)) if reported > seen
+            );
+
+        if publish_advanced {
+            self.unchanged_streak = 0;
+            self.streak_receive_time = Some(receive_time);
+        } else if self.streak_receive_time != Some(receive_time) {
+            self.unchanged_streak = self.unchanged_streak.saturating_add(1);
+            self.streak_receive_time = Some(receive_time);
+        }
+
+        self.prev_publish_time = Some(publish_time);
+
+        FreshnessObservation {
+            publish_advanced,
+            unchanged_streak: self.unchanged_streak,
+            receive_lag_ms: receive_time
+                .saturating_sub(publish_time)
+                .saturating_mul(1000),
+            publish_gap_secs,
+            missed_update,
+        }
+    }
+
+    pub(crate) fn publish_time(&self) -> Option<i64> {
+        self.prev_publish_time
+    }
+}
+
+#[derive(Debug, Default)]
+struct AssetHealth {
+    state: FeedHealth,
+    tracker: FreshnessTracker,
+}
+
+/// Tracks every asset's [`FeedHealth`] and reports transitions.
+#[derive(Debug, Default)]
+pub struct FeedHealthMonitor {
+    thresholds: FeedHealthThresholds,
+    /// Per-symbol overrides of `thresholds`.
+    symbol_thresholds: HashMap<String, FeedHealthThresholds>,
+    assets: BTreeMap<String, AssetHealth>,
+}
+
+impl FeedHealthMonitor {
+    pub fn new(thresholds: FeedHealthThresholds) -> Self {
+        Self {
+            thresholds,
+            ..Self::default()
+        }
+    }
+
+    /// Classify `symbol` by `thresholds` instead of the monitor default.
+    pub fn with_symbol_thresholds(
+        mut self,
+        symbol: impl Into<String>,
+        thresholds: FeedHealthThresholds,
+    ) -> Self {
+        self.symbol_thresholds.insert(symbol.into(), thresholds);
+        self
+    }
+
+    /// The thresholds `symbol` is classified by.
+    pub fn thresholds_for(&self, symbol: &str) -> FeedHealthThresholds {
+        self.symbol_thresholds
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.thresholds)
+    }
+
+    /// `symbol`'s current state; `Stale` until its first price.
+    pub fn state(&self, symbol: &str) -> FeedHealth {
+        self.assets
+            .get(symbol)
+            .map_or(FeedHealth::Stale, |asset| asset.state)
+    }
+
+    /// Account for `update`, received at `now`. Backfilled and regressed
+    /// prices say nothing about the live feed and are ignored.
+    pub fn observe(&mut self, update: &PriceUpdate, now: i64) -> Option<FeedHealthChange> {
+        let thresholds = self.thresholds_for(&update.symbol);
+        let asset = self.assets.entry(update.symbol.clone()).or_default();
+        if update.backfilled
+            || asset
+                .tracker
+                .publish_time()
+                .is_some_and(|newest| update.publish_time < newest)
+        {
+            return None;
+        }
+        let observation = asset
+            .tracker
+            .observe(update.publish_time, update.prev_publish_time, now);
+        let age = (now - update.publish_time).max(0);
+        let state = thresholds.classify(Some(age), observation.unchanged_streak);
+        transition(&update.symbol, asset, state, now)
+    }
+
+    /// Re-classify every asset as of `now`, for feeds that have gone quiet.
+    pub fn check(&mut self, now: i64) -> Vec<FeedHealthChange> {
+        let mut changes = Vec::new();
+        for (symbol, asset) in &mut self.assets {
+            let thresholds = self
+                .symbol_thresholds
+                .get(symbol)
+                .copied()
+                .unwrap_or(self.thresholds);
+            let age = asset
+                .tracker
+                .publish_time()
+                .map(|newest| (now - newest).max(0));
+            let state = thresholds.classify(age, asset.tracker.unchanged_streak);
+            changes.extend(transition(symbol, asset, state, now));
+        }
+        changes
+    }
+
+    /// Pass `event` through, followed by the health change a price causes.
+    pub fn ingest(&mut self, event: OracleEvent, now: i64) -> Vec<OracleEvent> {
+        let change = match &event {
+            OracleEvent::Price(update) => self.observe(update, now),
+            _ => None,
+        };
+        std::iter::once(event)
+            .chain(change.map(OracleEvent::FeedHealth))
+            .collect()
+    }
+
+    /// Forward `inputs` to `output`, adding health transitions, until
+    /// either side closes.
+    pub async fn run(
+        mut self,
+        mut inputs: mpsc::Receiver<OracleEvent>,
+        output: mpsc::Sender<OracleEvent>,
+    ) {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let events = tokio::select! {
+                event = inputs.recv() => match event {
+                    Some(event) => self.ingest(event, unix_now_secs()),
+                    None => return,
+                },
+                _ = interval.tick() => self
+                    .check(unix_now_secs())
+                    .into_iter()
+                    .map(OracleEvent::FeedHealth)
+                    .collect(),
+            };
+            for event in events {
+                if output.send(event).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Move `asset` to `state`, reporting the change if there is one.
+fn transition(
+    symbol: &str,
+    asset: &mut AssetHealth,
+    state: FeedHealth,
+    now: i64,
+) -> Option<FeedHealthChange> {
+    if state == asset.state {
+        return None;
+    }
+    let publish_time = asset.tracker.publish_time();
+    let change = FeedHealthChange {
+        symbol: symbol.to_string(),
+        state,
+        previous: std::mem::replace(&mut asset.state, state),
+        publish_time,
+        age_secs: publish_time.map(|newest| (now - newest).max(0)),
+    };
+    Some(change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(symbol: &str, publish_time: i64) -> PriceUpdate {
+        PriceUpdate {
+            symbol: symbol.to_string(),
+            price: 100.0,
+            confidence: 0.01,
+            publish_time,
+            feed_id: "0x123".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn freshness_tracker_marks_publish_time_as_unchanged() {
+        let mut tracker = FreshnessTracker::default();
+
+        let first = tracker.observe(100, None, 101);
+        let second = tracker.observe(100, None, 102);
+        // A second update in the same receive second does not grow the streak.
+        let third = tracker.observe(100, None, 102);
+
+        assert!(first.publish_advanced);
+        assert_eq!(first.unchanged_streak, 0);
+        assert_eq!(first.publish_gap_secs, None);
+
+        assert!(!second.publish_advanced);
+        assert_eq!(second.unchanged_streak, 1);
+        assert_eq!(second.publish_gap_secs, Some(0));
+        assert_eq!(third.unchanged_streak, 1);
+    }
+
+    #[test]
+    fn freshness_tracker_resets_streak_when_publish_time_advances() {
+        let mut tracker = FreshnessTracker::default();
+
+        tracker.observe(100, None, 101);
+        tracker.observe(100, None, 102);
+        let third = tracker.observe(101, None, 103);
+
+        assert!(third.publish_advanced);
+        assert_eq!(third.unchanged_streak, 0);
+        assert_eq!(third.publish_gap_secs, Some(1));
+    }
+
+    #[test]
+    fn freshness_tracker_detects_missed_updates_from_prev_publish_time() {
+        let mut tracker = FreshnessTracker::default();
+
+        let first = tracker.observe(100, Some(99), 100);
+        let next = tracker.observe(101, Some(100), 101);
+        // The feed published at 102 in between, which never arrived.
+        let skipped = tracker.observe(103, Some(102), 103);
+        // A repeat of 103 is not a new gap.
+        let repeat = tracker.observe(103, Some(102), 103);
+        // Without the feed's own previous time a wide gap is not a miss.
+        let unknown = tracker.observe(110, None, 110);
+
+        assert!(!first.missed_update);
+        assert!(!next.missed_update);
+        assert!(skipped.missed_update);
+        assert_eq!(skipped.publish_gap_secs, Some(2));
+        assert!(!repeat.missed_update);
+        assert!(!unknown.missed_update);
+    }
+
+    #[test]
+    fn walks_fresh_stale_halted_and_recovers() {
+        let mut monitor = FeedHealthMonitor::default();
+        assert_eq!(monitor.state("SOL"), FeedHealth::Stale);
+
+        let change = monitor.observe(&update("SOL", 1000), 1000).unwrap();
+        assert_eq!(
+            (change.previous, change.state),
+            (FeedHealth::Stale, FeedHealth::Fresh)
+        );
+        assert!(monitor.check(1010).is_empty());
+
+        let changes = monitor.check(1011);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].state, FeedHealth::Degraded);
+        assert_eq!(changes[0].age_secs, Some(11));
+
+        assert_eq!(monitor.check(1031)[0].state, FeedHealth::Stale);
+        assert_eq!(monitor.check(1301)[0].state, FeedHealth::Halted);
+
+        // Backfill and regressions don't count; a live price does.
+        let mut backfilled = update("SOL", 1300);
+        backfilled.backfilled = true;
+        assert!(monitor.observe(&backfilled, 1301).is_none());
+        assert!(monitor.observe(&update("SOL", 999), 1301).is_none());
+        let change = monitor.observe(&update("SOL", 1301), 1301).unwrap();
+        assert_eq!(
+            (change.previous, change.state),
+            (FeedHealth::Halted, FeedHealth::Fresh)
+        );
+    }
+
+    #[test]
+    fn repeated_publish_times_degrade_the_feed() {
+        let mut monitor = FeedHealthMonitor::default().with_symbol_thresholds(
+            "SOL",
+            FeedHealthThresholds {
+                degraded_unchanged_streak: 2,
+                ..FeedHealthThresholds::default()
+            },
+        );
+        monitor.observe(&update("SOL", 1000), 1000);
+        assert!(monitor.observe(&update("SOL", 1000), 1001).is_none());
+        let change = monitor.observe(&update("SOL", 1000), 1002).unwrap();
+        assert_eq!(change.state, FeedHealth::Degraded);
+
+        let events = monitor.ingest(OracleEvent::Price(update("SOL", 1003)), 1003);
+        assert!(matches!(events[0], OracleEvent::Price(_)));
+        match &events[1] {
+            OracleEvent::FeedHealth(change) => assert_eq!(change.state, FeedHealth::Fresh),
+            other => panic!("expected a health change, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn validates_thresholds() {
+        assert!(FeedHealthThresholds::default().validate().is_ok());
+        assert!(FeedHealthThresholds {
+            stale_after_secs: 500,
+            ..FeedHealthThresholds::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
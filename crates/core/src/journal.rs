@@ -0,0 +1,501 @@
+//! Durable on-disk journal of TWAP inputs.
+//!
+//! Every price update the [`TwapCalculator`] accepts can be appended to a
+//! segmented, append-only journal (one JSON line per update). On restart,
+//! [`SampleJournal::replay`] feeds the retained segments back through
+//! [`TwapCalculator::record`], so a deploy shortly before expiry does not
+//! wipe the settlement window. Replaying the *inputs* rather than the
+//! stored samples keeps carry-forward and retention behavior identical to
+//! the live path.
+//!
+//! Signed update data covers every feed of a Hermes response, so it is not
+//! repeated on each price line: a segment holds each update once, on its
+//! own line ahead of the first price that refers to it, and price lines
+//! carry its `update_id`.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::twap_calculator::TwapCalculator;
+use joyride_oracle_wire::{PriceUpdate, UpdateData};
+
+/// Default segment length: one file per 5 minutes of publish time.
+pub const DEFAULT_SEGMENT_SECS: i64 = 5 * 60;
+
+const SEGMENT_PREFIX: &str = "samples-";
+const SEGMENT_SUFFIX: &str = ".jsonl";
+
+/// When the journal forces appended data to stable storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// `fsync` after every append. Survives power loss, costs one sync per
+    /// accepted sample.
+    #[default]
+    Always,
+
+    /// `fsync` after every `n` appends and whenever a segment is closed.
+    EveryRecords(u32),
+
+    /// Never `fsync` explicitly; survives process crashes but not host
+    /// failures.
+    Never,
+}
+
+impl FromStr for FsyncPolicy {
+    type Err = anyhow::Error;
+
+    /// `always`, `never` or `every:<n>`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.split_once(':') {
+            None if s == "always" => Ok(Self::Always),
+            None if s == "never" => Ok(Self::Never),
+            Some(("every", n)) => {
+                let n = n
+                    .parse()
+                    .with_context(|| format!("invalid fsync record count `{n}`"))?;
+                if n == 0 {
+                    bail!("fsync record count must be at least 1");
+                }
+                Ok(Self::EveryRecords(n))
+            }
+            _ => bail!("unknown fsync policy `{s}` (expected always, never or every:<n>)"),
+        }
+    }
+}
+
+/// Segmented append-only journal of accepted [`PriceUpdate`]s.
+pub struct SampleJournal {
+    dir: PathBuf,
+    segment_secs: i64,
+    retention_secs: i64,
+    fsync: FsyncPolicy,
+    current: Option<OpenSegment>,
+    unsynced: u32,
+}
+
+struct OpenSegment {
+    start: i64,
+    file: File,
+    /// Ids of the signed updates already written to this segment.
+    update_ids: HashSet<String>,
+}
+
+/// One journal line: an accepted price, or signed update data that later
+/// price lines in the segment refer to.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JournalLine {
+    Price {
+        #[serde(flatten)]
+        update: PriceUpdate,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        update_id: Option<String>,
+    },
+    UpdateData(UpdateData),
+}
+
+impl SampleJournal {
+    /// Open (creating if needed) a journal in `dir`. Segments whose data is
+    /// older than `retention_secs` behind the newest append are deleted;
+    /// pass [`TwapCalculator::retention_secs`] so the journal keeps exactly
+    /// what the calculator can use.
+    pub fn open(dir: impl Into<PathBuf>, retention_secs: i64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            segment_secs: DEFAULT_SEGMENT_SECS,
+            retention_secs,
+            fsync: FsyncPolicy::default(),
+            current: None,
+            unsynced: 0,
+        })
+    }
+
+    /// Override [`DEFAULT_SEGMENT_SECS`].
+    pub fn with_segment_secs(mut self, segment_secs: i64) -> Self {
+        assert!(
+            segment_secs > 0,
+            "segment_secs must be positive (got {segment_secs})"
+        );
+        self.segment_secs = segment_secs;
+        self
+    }
+
+    /// Select the fsync policy. Defaults to [`FsyncPolicy::Always`].
+    pub fn with_fsync(mut self, fsync: FsyncPolicy) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append one accepted update to the segment covering its publish time,
+    /// or to the open segment if that is newer. Segments only move forward,
+    /// so assets interleaving across a boundary or a late print do not
+    /// reopen older files; each symbol's updates stay in publish-time order
+    /// either way.
+    pub fn append(&mut self, update: &PriceUpdate) -> io::Result<()> {
+        let segment_start = update.publish_time.div_euclid(self.segment_secs) * self.segment_secs;
+        let rolled = self
+            .current
+            .as_ref()
+            .is_none_or(|segment| segment.start < segment_start);
+        if rolled {
+            self.roll_to(segment_start)?;
+            self.prune(update.publish_time)?;
+        }
+
+        let segment = self.current.as_mut().expect("segment opened by roll_to");
+        let mut lines = Vec::new();
+        let new_update_data = update
+            .update_data
+            .as_ref()
+            .filter(|data| !segment.update_ids.contains(&data.id));
+        if let Some(data) = new_update_data {
+            serde_json::to_writer(&mut lines, data.as_ref())?;
+            lines.push(b'\n');
+        }
+        let price = JournalLine::Price {
+            update: PriceUpdate {
+                update_data: None,
+                ..update.clone()
+            },
+            update_id: update.update_data.as_ref().map(|data| data.id.clone()),
+        };
+        serde_json::to_writer(&mut lines, &price)?;
+        lines.push(b'\n');
+        segment.file.write_all(&lines)?;
+        if let Some(data) = new_update_data {
+            segment.update_ids.insert(data.id.clone());
+        }
+
+        self.unsynced = self.unsynced.saturating_add(1);
+        let sync_now = match self.fsync {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::EveryRecords(n) => self.unsynced >= n.max(1),
+            FsyncPolicy::Never => false,
+        };
+        if sync_now {
+            segment.file.sync_data()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    /// Replay every retained segment, oldest first, into `calculator`.
+    /// Returns the number of updates the calculator accepted. A torn final
+    /// line (crash mid-append) is skipped with a warning.
+    pub fn replay(&self, calculator: &mut TwapCalculator) -> io::Result<usize> {
+        let mut accepted = 0;
+        let (segments, skipped) = self.visit(|update| {
+            if calculator.record(&update) {
+                accepted += 1;
+            }
+        })?;
+
+        info!(segments, accepted, skipped, "Replayed TWAP sample journal");
+        Ok(accepted)
+    }
+
+    /// Every readable update in the retained segments, oldest first.
+    pub fn updates(&self) -> io::Result<Vec<PriceUpdate>> {
+        let mut updates = Vec::new();
+        self.visit(|update| updates.push(update))?;
+        Ok(updates)
+    }
+
+    /// Feed each readable update to `on_update`, oldest first, with its
+    /// signed update data reattached. Returns the number of segments read
+    /// and of lines skipped.
+    fn visit(&self, mut on_update: impl FnMut(PriceUpdate)) -> io::Result<(usize, usize)> {
+        let segments = self.segments()?;
+        let mut skipped = 0;
+        let mut update_data: HashMap<String, Arc<UpdateData>> = HashMap::new();
+
+        for (_, path) in &segments {
+            let reader = BufReader::new(File::open(path)?);
+            for (line_no, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<JournalLine>(&line) {
+                    Ok(JournalLine::Price {
+                        mut update,
+                        update_id,
+                    }) => {
+                        if let Some(id) = update_id {
+                            update.update_data = update_data.get(&id).cloned();
+                        }
+                        on_update(update);
+                    }
+                    Ok(JournalLine::UpdateData(data)) => {
+                        update_data.insert(data.id.clone(), Arc::new(data));
+                    }
+                    Err(e) => {
+                        skipped += 1;
+                        warn!(
+                            segment = %path.display(),
+                            line = line_no + 1,
+                            error = %e,
+                            "Skipping unreadable journal line"
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok((segments.len(), skipped))
+    }
+
+    fn roll_to(&mut self, segment_start: i64) -> io::Result<()> {
+        if let Some(previous) = self.current.take() {
+            if self.fsync != FsyncPolicy::Never {
+                previous.file.sync_data()?;
+            }
+            self.unsynced = 0;
+        }
+        let path = self.segment_path(segment_start);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        debug!(segment = %path.display(), "Opened journal segment");
+        self.current = Some(OpenSegment {
+            start: segment_start,
+            file,
+            update_ids: HashSet::new(),
+        });
+        Ok(())
+    }
+
+    /// Delete segments that end before `newest - retention_secs`.
+    fn prune(&self, newest: i64) -> io::Result<()> {
+        let cutoff = newest.saturating_sub(self.retention_secs);
+        for (start, path) in self.segments()? {
+            if start.saturating_add(self.segment_secs) <= cutoff {
+                fs::remove_file(&path)?;
+                debug!(segment = %path.display(), "Pruned journal segment");
+            }
+        }
+        Ok(())
+    }
+
+    /// Segment files in the journal directory, sorted by start time.
+    fn segments(&self) -> io::Result<Vec<(i64, PathBuf)>> {
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let start = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(SEGMENT_PREFIX))
+                .and_then(|name| name.strip_suffix(SEGMENT_SUFFIX))
+                .and_then(|start| start.parse::<i64>().ok());
+            if let Some(start) = start {
+                segments.push((start, path));
+            }
+        }
+        segments.sort_by_key(|(start, _)| *start);
+        Ok(segments)
+    }
+
+    fn segment_path(&self, segment_start: i64) -> PathBuf {
+        self.dir
+            .join(format!("{SEGMENT_PREFIX}{segment_start}{SEGMENT_SUFFIX}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_update(symbol: &str, price: f64, timestamp: i64) -> PriceUpdate {
+        PriceUpdate {
+            symbol: symbol.to_string(),
+            price,
+            confidence: 0.01,
+            publish_time: timestamp,
+            feed_id: "0x123".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("joyride-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn replay_rebuilds_calculator_across_segments() {
+        let dir = scratch_dir("replay");
+        let mut journal = SampleJournal::open(&dir, 1_000)
+            .unwrap()
+            .with_segment_secs(10)
+            .with_fsync(FsyncPolicy::EveryRecords(4));
+        let mut live = TwapCalculator::with_window(30);
+        for i in 0..25 {
+            for symbol in ["SOL", "BTC"] {
+                let update = make_update(symbol, 100.0 + i as f64, 1000 + i);
+                if live.record(&update) {
+                    journal.append(&update).unwrap();
+                }
+            }
+        }
+        drop(journal);
+
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+        let mut restored = TwapCalculator::with_window(30);
+        let journal = SampleJournal::open(&dir, 1_000).unwrap();
+        assert_eq!(journal.replay(&mut restored).unwrap(), 50);
+        let expected = live.calculate("SOL", 1024).unwrap();
+        let replayed = restored.calculate("SOL", 1024).unwrap();
+        assert_eq!(replayed.sample_count, expected.sample_count);
+        assert!((replayed.twap - expected.twap).abs() < f64::EPSILON);
+        assert_eq!(restored.sample_count("BTC"), 25);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn append_prunes_segments_beyond_retention() {
+        let dir = scratch_dir("prune");
+        let mut journal = SampleJournal::open(&dir, 20).unwrap().with_segment_secs(10);
+        for ts in [1000, 1010, 1020, 1030, 1045] {
+            journal.append(&make_update("SOL", 100.0, ts)).unwrap();
+        }
+
+        let starts: Vec<i64> = journal
+            .segments()
+            .unwrap()
+            .into_iter()
+            .map(|(s, _)| s)
+            .collect();
+        assert_eq!(starts, vec![1020, 1030, 1040]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn signed_update_is_written_once_per_segment() {
+        let dir = scratch_dir("update-data");
+        let mut journal = SampleJournal::open(&dir, 1_000).unwrap();
+        let data = Arc::new(UpdateData {
+            id: "a1".to_string(),
+            data: vec!["504e4155".to_string()],
+        });
+        for symbol in ["SOL", "BTC", "ETH"] {
+            let update = PriceUpdate {
+                update_data: Some(Arc::clone(&data)),
+                ..make_update(symbol, 100.0, 1000)
+            };
+            journal.append(&update).unwrap();
+        }
+        drop(journal);
+
+        let path = dir.join(format!("{SEGMENT_PREFIX}900{SEGMENT_SUFFIX}"));
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 4);
+        assert_eq!(contents.matches("504e4155").count(), 1);
+
+        let journal = SampleJournal::open(&dir, 1_000).unwrap();
+        let updates = journal.updates().unwrap();
+        assert_eq!(updates.len(), 3);
+        let first = updates[0].update_data.as_ref().unwrap();
+        assert_eq!(**first, *data);
+        assert!(updates
+            .iter()
+            .all(|update| Arc::ptr_eq(update.update_data.as_ref().unwrap(), first)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn late_prints_stay_in_the_open_segment() {
+        let dir = scratch_dir("forward");
+        let mut journal = SampleJournal::open(&dir, 1_000)
+            .unwrap()
+            .with_segment_secs(10);
+        let data = Arc::new(UpdateData {
+            id: "a1".to_string(),
+            data: vec!["504e4155".to_string()],
+        });
+        // BTC crosses into the next segment before SOL catches up.
+        for (symbol, timestamp) in [("SOL", 1008), ("BTC", 1010), ("SOL", 1009), ("SOL", 1010)] {
+            let update = PriceUpdate {
+                update_data: Some(Arc::clone(&data)),
+                ..make_update(symbol, 100.0, timestamp)
+            };
+            journal.append(&update).unwrap();
+        }
+
+        let starts: Vec<i64> = journal
+            .segments()
+            .unwrap()
+            .into_iter()
+            .map(|(s, _)| s)
+            .collect();
+        assert_eq!(starts, vec![1000, 1010]);
+        let newest =
+            fs::read_to_string(dir.join(format!("{SEGMENT_PREFIX}1010{SEGMENT_SUFFIX}"))).unwrap();
+        assert_eq!(newest.lines().count(), 4);
+        assert_eq!(newest.matches("504e4155").count(), 1);
+
+        let sol: Vec<i64> = journal
+            .updates()
+            .unwrap()
+            .into_iter()
+            .filter(|update| update.symbol == "SOL")
+            .map(|update| update.publish_time)
+            .collect();
+        assert_eq!(sol, [1008, 1009, 1010]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_fsync_policies() {
+        assert_eq!(
+            "always".parse::<FsyncPolicy>().unwrap(),
+            FsyncPolicy::Always
+        );
+        assert_eq!("never".parse::<FsyncPolicy>().unwrap(), FsyncPolicy::Never);
+        assert_eq!(
+            "every:64".parse::<FsyncPolicy>().unwrap(),
+            FsyncPolicy::EveryRecords(64)
+        );
+        assert!("every:0".parse::<FsyncPolicy>().is_err());
+        assert!("sometimes".parse::<FsyncPolicy>().is_err());
+    }
+
+    #[test]
+    fn replay_skips_torn_trailing_line() {
+        let dir = scratch_dir("torn");
+        let mut journal = SampleJournal::open(&dir, 1_000).unwrap();
+        journal.append(&make_update("SOL", 100.0, 1000)).unwrap();
+        journal.append(&make_update("SOL", 101.0, 1001)).unwrap();
+        drop(journal);
+
+        let path = dir.join(format!("{SEGMENT_PREFIX}900{SEGMENT_SUFFIX}"));
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"symbol":"SOL","price":1"#).unwrap();
+
+        let mut calc = TwapCalculator::new();
+        let journal = SampleJournal::open(&dir, 1_000).unwrap();
+        assert_eq!(journal.replay(&mut calc).unwrap(), 2);
+        assert_eq!(calc.sample_count("SOL"), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
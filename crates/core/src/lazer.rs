@@ -0,0 +1,395 @@
+//! Pyth Lazer (Pyth Pro) WebSocket client.
+//!
+//! Lazer streams sub-second prices over an authenticated WebSocket. The
+//! client subscribes every asset's `lazer_id` on one channel (e.g.
+//! `fixed_rate@200ms` or `real_time`) with JSON delivery, and forwards each
+//! feed in a `streamUpdated` message as a [`PriceUpdate`]. Updates carry
+//! Lazer's microsecond timestamp truncated to the second, so several per
+//! second share a `publish_time`; the TWAP calculator samples the first.
+
+use anyhow::{anyhow, bail, Context};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::{HeaderValue, AUTHORIZATION};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, info, warn};
+
+use crate::assets::AssetRegistry;
+use crate::source::{reconnect_after, Backoff};
+use crate::types::OracleEvent;
+use joyride_oracle_wire::{FixedPrice, PriceUpdate};
+
+/// Default Lazer stream endpoint.
+pub const LAZER_URL: &str = "wss://pyth-lazer.dourolabs.app/v1/stream";
+/// Channel subscribed to unless [`LazerClient::with_channel`] says otherwise.
+pub const DEFAULT_LAZER_CHANNEL: &str = "fixed_rate@200ms";
+const LAZER_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+const SUBSCRIPTION_ID: u64 = 1;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SubscribeRequest<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    subscription_id: u64,
+    price_feed_ids: Vec<u32>,
+    properties: &'static [&'static str],
+    formats: &'static [&'static str],
+    delivery_format: &'static str,
+    json_binary_encoding: &'static str,
+    parsed: bool,
+    channel: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum LazerMessage {
+    Subscribed {},
+    SubscriptionError {
+        error: String,
+    },
+    Error {
+        error: String,
+    },
+    StreamUpdated {
+        #[serde(default)]
+        parsed: Option<ParsedPayload>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ParsedPayload {
+    timestamp_us: String,
+    price_feeds: Vec<LazerFeed>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LazerFeed {
+    price_feed_id: u32,
+    #[serde(default)]
+    price: Option<String>,
+    #[serde(default)]
+    confidence: Option<String>,
+    #[serde(default)]
+    exponent: Option<i32>,
+}
+
+/// Client for the Pyth Lazer WebSocket API.
+pub struct LazerClient {
+    event_tx: mpsc::Sender<OracleEvent>,
+    assets: AssetRegistry,
+    url: String,
+    token: String,
+    channel: String,
+    /// Whether the current session has been subscribed, so `Disconnected`
+    /// only follows a `Connected`.
+    connected: bool,
+}
+
+impl LazerClient {
+    /// A client for the public Lazer endpoint authenticating with `token`.
+    /// Every asset needs a `lazer_id`.
+    pub fn new(
+        event_tx: mpsc::Sender<OracleEvent>,
+        assets: AssetRegistry,
+        token: &str,
+    ) -> anyhow::Result<Self> {
+        let missing: Vec<&str> = assets
+            .iter()
+            .filter(|asset| asset.lazer_id.is_none())
+            .map(|asset| asset.symbol.as_str())
+            .collect();
+        if !missing.is_empty() {
+            bail!("assets without a lazer_id: {}", missing.join(", "));
+        }
+        Ok(Self {
+            event_tx,
+            assets,
+            url: LAZER_URL.to_string(),
+            token: token.to_string(),
+            channel: DEFAULT_LAZER_CHANNEL.to_string(),
+            connected: false,
+        })
+    }
+
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.channel = channel.to_string();
+        self
+    }
+
+    pub fn assets(&self) -> &AssetRegistry {
+        &self.assets
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    pub async fn run(&mut self) -> anyhow::Result<()> {
+        let mut backoff = Backoff::new();
+
+        loop {
+            let result = self.connect_and_stream().await;
+            let connected = std::mem::take(&mut self.connected);
+            reconnect_after(
+                "Pyth Lazer",
+                &self.event_tx,
+                &mut backoff,
+                result,
+                connected,
+            )
+            .await;
+        }
+    }
+
+    fn subscribe_request(&self) -> SubscribeRequest<'_> {
+        SubscribeRequest {
+            kind: "subscribe",
+            subscription_id: SUBSCRIPTION_ID,
+            price_feed_ids: self.assets.iter().filter_map(|a| a.lazer_id).collect(),
+            properties: &["price", "confidence", "exponent"],
+            formats: &[],
+            delivery_format: "json",
+            json_binary_encoding: "base64",
+            parsed: true,
+            channel: &self.channel,
+        }
+    }
+
+    async fn connect_and_stream(&mut self) -> anyhow::Result<()> {
+        info!("Connecting to Pyth Lazer: {}", self.url);
+
+        let mut request = self.url.as_str().into_client_request()?;
+        request.headers_mut().insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", self.token))
+                .context("invalid Lazer token")?,
+        );
+        let (mut socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .context("connecting to Pyth Lazer")?;
+        let subscribe = serde_json::to_string(&self.subscribe_request())?;
+        socket.send(Message::Text(subscribe)).await?;
+
+        loop {
+            let message = match tokio::time::timeout(LAZER_IDLE_TIMEOUT, socket.next()).await {
+                Ok(Some(message)) => message?,
+                Ok(None) => {
+                    info!("Pyth Lazer stream ended");
+                    return Ok(());
+                }
+                Err(_) => {
+                    return Err(anyhow!(
+                        "Pyth Lazer idle for {}s",
+                        LAZER_IDLE_TIMEOUT.as_secs()
+                    ));
+                }
+            };
+
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(frame) => {
+                    info!(?frame, "Pyth Lazer closed the connection");
+                    return Ok(());
+                }
+                // Pings are answered by tungstenite.
+                _ => continue,
+            };
+            match serde_json::from_str::<LazerMessage>(&text) {
+                Ok(LazerMessage::Subscribed {}) => {
+                    info!(channel = %self.channel, "Subscribed to Pyth Lazer");
+                    self.connected = true;
+                    let _ = self.event_tx.send(OracleEvent::Connected).await;
+                }
+                Ok(LazerMessage::SubscriptionError { error } | LazerMessage::Error { error }) => {
+                    bail!("Pyth Lazer error: {error}");
+                }
+                Ok(LazerMessage::StreamUpdated {
+                    parsed: Some(payload),
+                }) => {
+                    for update in self.parse_updates(payload) {
+                        if let Err(e) = self.event_tx.send(OracleEvent::Price(update)).await {
+                            error!("Failed to send price update: {}", e);
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Failed to parse Lazer message: {}", e);
+                }
+            }
+        }
+    }
+
+    fn parse_updates(&self, payload: ParsedPayload) -> Vec<PriceUpdate> {
+        let Ok(timestamp_us) = payload.timestamp_us.parse::<i64>() else {
+            warn!(timestamp_us = %payload.timestamp_us, "Invalid Lazer timestamp");
+            return Vec::new();
+        };
+        payload
+            .price_feeds
+            .into_iter()
+            .filter_map(|feed| {
+                let asset = self.assets.by_lazer_id(feed.price_feed_id)?;
+                // Feeds without a current price carry no `price`.
+                let expo = feed.exponent?;
+                let price = FixedPrice::new(feed.price?.parse().ok()?, expo);
+                let confidence = feed
+                    .confidence
+                    .and_then(|conf| conf.parse().ok())
+                    .map_or(0.0, |conf| FixedPrice::new(conf, expo).to_f64());
+                Some(PriceUpdate {
+                    symbol: asset.symbol.clone(),
+                    price: price.to_f64(),
+                    confidence,
+                    publish_time: timestamp_us.div_euclid(1_000_000),
+                    feed_id: format!("lazer:{}", feed.price_feed_id),
+                    fixed_price: Some(price),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::PriceSource;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+    fn registry() -> AssetRegistry {
+        AssetRegistry::from_json(
+            r#"{"assets": [
+                {"symbol": "BTC", "lazer_id": 1, "decimals": 8, "tick_size": 0.01},
+                {"symbol": "SOL", "lazer_id": 6, "decimals": 8, "tick_size": 0.0001}
+            ]}"#,
+        )
+        .unwrap()
+    }
+
+    const STREAM_UPDATED: &str = r#"{
+        "type": "streamUpdated",
+        "subscriptionId": 1,
+        "parsed": {
+            "timestampUs": "1730986152400000",
+            "priceFeeds": [
+                {"priceFeedId": 1, "price": "7512345000000", "confidence": "2500000", "exponent": -8},
+                {"priceFeedId": 6, "exponent": -8},
+                {"priceFeedId": 99, "price": "1", "exponent": 0}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn parses_stream_updates() {
+        let (tx, _rx) = mpsc::channel(1);
+        let client = LazerClient::new(tx, registry(), "token").unwrap();
+        let Ok(LazerMessage::StreamUpdated {
+            parsed: Some(payload),
+        }) = serde_json::from_str(STREAM_UPDATED)
+        else {
+            panic!("not a stream update");
+        };
+
+        let updates = client.parse_updates(payload);
+        assert_eq!(updates.len(), 1);
+        let btc = &updates[0];
+        assert_eq!(btc.symbol, "BTC");
+        assert_eq!(btc.publish_time, 1_730_986_152);
+        assert_eq!(btc.feed_id, "lazer:1");
+        assert_eq!(
+            btc.fixed_price,
+            Some(FixedPrice::new(7_512_345_000_000, -8))
+        );
+        assert!((btc.price - 75_123.45).abs() < 1e-6);
+        assert!((btc.confidence - 0.025).abs() < 1e-12);
+
+        let (tx, _rx) = mpsc::channel(1);
+        let unmapped = AssetRegistry::from_json(
+            r#"{"assets": [{"symbol": "ETH", "decimals": 8, "tick_size": 0.01}]}"#,
+        )
+        .unwrap();
+        assert!(LazerClient::new(tx, unmapped, "token").is_err());
+    }
+
+    #[tokio::test]
+    async fn streams_from_mock_lazer_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/v1/stream", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            // The callback signature is tungstenite's.
+            #[allow(clippy::result_large_err)]
+            let check_auth = |request: &Request, response: Response| {
+                assert_eq!(request.headers()[AUTHORIZATION], "Bearer secret");
+                Ok(response)
+            };
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, check_auth)
+                .await
+                .unwrap();
+            let Some(Ok(Message::Text(subscribe))) = socket.next().await else {
+                panic!("expected a subscribe request");
+            };
+            let subscribe: serde_json::Value = serde_json::from_str(&subscribe).unwrap();
+            assert_eq!(subscribe["type"], "subscribe");
+            assert_eq!(subscribe["priceFeedIds"], serde_json::json!([1, 6]));
+            assert_eq!(subscribe["channel"], "real_time");
+            socket
+                .send(Message::Text(
+                    r#"{"type":"subscribed","subscriptionId":1}"#.to_string(),
+                ))
+                .await
+                .unwrap();
+            socket
+                .send(Message::Text(STREAM_UPDATED.to_string()))
+                .await
+                .unwrap();
+            socket.close(None).await.unwrap();
+        });
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut source: Box<dyn PriceSource> = Box::new(
+            LazerClient::new(tx, registry(), "secret")
+                .unwrap()
+                .with_url(&url)
+                .with_channel("real_time"),
+        );
+        assert_eq!(source.name(), "lazer");
+        tokio::spawn(async move { source.run().await });
+
+        let mut events = Vec::new();
+        while events.len() < 3 {
+            events.push(
+                tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                    .await
+                    .expect("no event within 5s")
+                    .unwrap(),
+            );
+        }
+        assert!(matches!(events[0], OracleEvent::Connected));
+        match &events[1] {
+            OracleEvent::Price(update) => assert_eq!(update.symbol, "BTC"),
+            other => panic!("expected a price, got {other:?}"),
+        }
+        assert!(matches!(events[2], OracleEvent::Disconnected));
+        server.await.unwrap();
+    }
+}
@@ -1,11 +1,23 @@
 //! Joyride Oracle core library.
 //!
-//! Contains the in-process API for embedders: Pyth ingestion, TWAP
-//! calculation, and domain event types. WebSocket transport lives in the
-//! top-level `joyride-oracle` crate; wire-format serde types live in
-//! `joyride-oracle-wire`.
+//! Contains the in-process API for embedders: the asset registry, price
+//! sources (Pyth Hermes and Lazer, exchange tickers), TWAP calculation,
+//! settlement scheduling and proofs, and domain event types. WebSocket
+//! transport lives in the top-level `joyride-oracle` crate; wire-format
+//! serde types live in `joyride-oracle-wire`.
 
+pub mod aggregator;
+pub mod assets;
+pub mod catalog;
+pub mod health;
+pub mod journal;
+pub mod lazer;
+pub mod proof;
 pub mod pyth;
+pub mod settlement;
+pub mod source;
+pub mod spike;
+pub mod ticker;
 pub mod twap_calculator;
 pub mod types;
 
@@ -13,7 +25,38 @@ pub mod types;
 // variants — callers receiving events need them. BroadcastFrame and
 // WirePayload are transport-layer concerns; consumers that want those
 // should depend on `joyride-oracle-wire` directly.
-pub use joyride_oracle_wire::{PriceUpdate, TwapPreview};
-pub use pyth::{PythClient, HERMES_URL};
-pub use twap_calculator::{TwapCalculator, TwapResult, TwapSample, DEFAULT_TWAP_WINDOW_SECS};
-pub use types::{Asset, OracleEvent};
+pub use aggregator::{
+    forward_tagged, AggregationRule, PriceAggregator, DEFAULT_DEVIATION_THRESHOLD,
+    DEFAULT_MAX_AGE_SECS,
+};
+pub use assets::{Asset, AssetRegistry, AssetTwapSettings};
+pub use catalog::{CatalogFeed, PriceFeedCatalog};
+pub use health::{
+    FeedHealthMonitor, FeedHealthThresholds, DEFAULT_DEGRADED_LAG_SECS,
+    DEFAULT_DEGRADED_UNCHANGED_STREAK, DEFAULT_HALTED_AFTER_SECS, DEFAULT_STALE_AFTER_SECS,
+};
+pub use journal::{FsyncPolicy, SampleJournal};
+pub use joyride_oracle_wire::{
+    DeviationAlarm, FeedHealth, FeedHealthChange, FixedPrice, GapInterval, PriceQuarantine,
+    PriceUpdate, QuarantineStatus, RoundingMode, SampleRejected, SampleRejection, SettlementRung,
+    TwapMethod, TwapPreview, TwapResult,
+};
+pub use lazer::{LazerClient, DEFAULT_LAZER_CHANNEL, LAZER_URL};
+pub use proof::{
+    merkle_root, recompute, result_mismatches, ProofParams, ProofStore, SettlementProof,
+    PROOF_VERSION,
+};
+pub use pyth::{EndpointHealth, EndpointHealthHandle, PythClient, StreamMode, HERMES_URL};
+pub use settlement::{
+    ExpirySchedule, SettlementCalendar, SettlementScheduler, DEFAULT_EXPIRY,
+    DEFAULT_SETTLEMENT_GRACE,
+};
+pub use source::{PriceSource, PriceSourceKind};
+pub use spike::{SpikeFilter, SpikePolicy, DEFAULT_SPIKE_CONFIRMATIONS, DEFAULT_SPIKE_HISTORY};
+pub use ticker::{TickerClient, COINBASE_URL};
+pub use twap_calculator::{
+    ConfidenceAction, ConfidenceCounts, ConfidencePolicy, CoveragePolicy, GapPolicy,
+    RejectionCounts, RoundingRule, SettlementFallback, TwapCalculator, TwapSample, TwapWindow,
+    DEFAULT_MAX_FUTURE_SKEW_SECS, DEFAULT_RETENTION_WINDOWS, DEFAULT_TWAP_WINDOW_SECS,
+};
+pub use types::OracleEvent;
@@ -0,0 +1,695 @@
+//! Settlement proof bundles.
+//!
+//! A [`SettlementProof`] packages what a counterparty needs to check a
+//! settlement independently: the samples that went into it, the calculator
+//! parameters, the [`TwapResult`] itself, and a SHA-256 Merkle root that
+//! commits to the samples. [`ProofStore`] keeps one JSON file per
+//! settlement, which the server exposes at `GET /proofs/{symbol}/{window_end}`.
+//!
+//! # Merkle construction
+//!
+//! Each sample is a leaf hashed as `SHA-256(0x00 || timestamp || price ||
+//! confidence || filled [|| mantissa || expo])`, with the timestamp as a
+//! big-endian `i64`, the prices as big-endian IEEE-754 bits and `filled` as
+//! one byte. A sample with a fixed-point price appends its mantissa and
+//! exponent as big-endian `i64` and `i32`. A sample with signed update data
+//! then appends the update's id, the 64 hex characters of
+//! [`update_data_id`]; the bundle carries each update once, beside the
+//! samples, and [`SettlementProof::verify_root`] checks that it hashes to
+//! its id. Interior nodes are `SHA-256(0x01 || left || right)`. An odd node
+//! at the end of a level is promoted unchanged rather than paired with
+//! itself. The root of an empty sample set is `SHA-256("")`.
+//!
+//! # Recomputing
+//!
+//! [`recompute`] rebuilds a [`TwapCalculator`] from recorded price updates
+//! and a set of [`ProofParams`], and [`result_mismatches`] compares the
+//! outcome with a claimed [`TwapResult`]. [`SettlementProof::recompute`]
+//! runs the same replay over a bundle's own samples.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::twap_calculator::{
+    ConfidenceAction, ConfidencePolicy, CoveragePolicy, GapPolicy, RoundingRule, TwapCalculator,
+    TwapSample, TwapWindow,
+};
+use joyride_oracle_wire::{PriceUpdate, SettlementRung, TwapMethod, TwapResult, UpdateData};
+
+/// Format version written into every bundle. Verifiers refuse bundles of
+/// another version rather than reporting a root mismatch.
+pub const PROOF_VERSION: u32 = 2;
+
+/// Relative tolerance for recomputed prices. Prefix sums make the last bits
+/// depend on how much history preceded the window.
+const PRICE_TOLERANCE: f64 = 1e-9;
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Calculator parameters in force when a settlement was calculated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofParams {
+    /// Length of the settlement window in seconds, before any extension.
+    pub window_secs: i64,
+    pub sample_interval_secs: i64,
+    pub method: TwapMethod,
+    pub gap_policy: GapPolicy,
+    /// Minimum coverage and fallback chain, if the asset has one.
+    #[serde(default)]
+    pub coverage_policy: Option<CoveragePolicy>,
+    /// How the exact fixed-point average is rounded.
+    #[serde(default)]
+    pub rounding: RoundingRule,
+    /// Confidence ceiling, if the asset has one. Replaying the bundled
+    /// samples under it re-derives their flags and weights.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence_policy: Option<ConfidencePolicy>,
+}
+
+/// Everything that went into one [`TwapResult`], with a commitment to the
+/// samples.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettlementProof {
+    pub version: u32,
+    pub result: TwapResult,
+    pub params: ProofParams,
+    /// Retained samples from the last observed sample at or before
+    /// `result.window_start` through `result.window_end`. The leading sample
+    /// is the price in effect at the window start, which the time-weighted
+    /// method and the last-good-price rung both depend on.
+    pub samples: Vec<TwapSample>,
+    /// Hex-encoded Merkle root over `samples`; see the module docs.
+    pub merkle_root: String,
+    /// Signed update data the samples refer to by
+    /// [`TwapSample::update_id`], each update once, in order of first use.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub update_data: Vec<UpdateData>,
+}
+
+impl SettlementProof {
+    /// Bundle `result` with the samples and parameters `calculator` holds
+    /// for it. Call while the calculator still retains the window, i.e. at
+    /// settlement time.
+    pub fn build(calculator: &TwapCalculator, result: &TwapResult) -> Self {
+        let retained = calculator
+            .snapshot_samples(&result.symbol)
+            .unwrap_or_default();
+        let start = retained
+            .iter()
+            .rev()
+            .find(|sample| sample.timestamp <= result.window_start && !sample.filled)
+            .map_or(result.window_start, |sample| sample.timestamp);
+        let samples: Vec<TwapSample> = retained
+            .into_iter()
+            .filter(|sample| sample.timestamp >= start && sample.timestamp <= result.window_end)
+            .collect();
+
+        let mut update_data: Vec<UpdateData> = Vec::new();
+        for sample in &samples {
+            let Some(id) = &sample.update_id else {
+                continue;
+            };
+            if update_data.iter().any(|data| &data.id == id) {
+                continue;
+            }
+            match calculator.update_data(sample.timestamp, id) {
+                Some(data) => update_data.push(UpdateData::clone(data)),
+                None => debug!(
+                    symbol = %result.symbol,
+                    timestamp = sample.timestamp,
+                    "Signed update data no longer retained for proof sample"
+                ),
+            }
+        }
+
+        let window_secs = calculator
+            .windows()
+            .iter()
+            .find(|window| window.name == result.window)
+            .map_or(result.window_end - result.window_start, |window| {
+                window.secs
+            });
+
+        Self {
+            version: PROOF_VERSION,
+            result: result.clone(),
+            params: ProofParams {
+                window_secs,
+                sample_interval_secs: calculator.sample_interval_secs(),
+                method: result.method,
+                gap_policy: calculator.gap_policy(),
+                coverage_policy: calculator.coverage_policy(&result.symbol).cloned(),
+                rounding: calculator.rounding_for(&result.symbol),
+                confidence_policy: calculator.confidence_policy(&result.symbol).copied(),
+            },
+            merkle_root: merkle_root(&samples),
+            samples,
+            update_data,
+        }
+    }
+
+    /// Whether `merkle_root` matches the bundled samples and every bundled
+    /// update hashes to its id, which is what the leaves commit to.
+    pub fn verify_root(&self) -> bool {
+        merkle_root(&self.samples) == self.merkle_root
+            && self
+                .update_data
+                .iter()
+                .all(|data| update_data_id(&data.data) == data.id)
+    }
+
+    /// Recalculate the settlement from the bundled observed samples and
+    /// parameters. Carried-forward samples are regenerated by the gap
+    /// policy rather than trusted. The EMA rung's price is not derivable
+    /// from samples, so a bundle settled on it is recomputed with its own
+    /// claimed EMA price; only the decision to fall back is checked.
+    pub fn recompute(&self) -> anyhow::Result<Option<TwapResult>> {
+        let updates: Vec<PriceUpdate> = self
+            .samples
+            .iter()
+            .filter(|sample| !sample.filled)
+            .map(|sample| PriceUpdate {
+                symbol: self.result.symbol.clone(),
+                price: sample.price,
+                confidence: sample.confidence,
+                publish_time: sample.timestamp,
+                fixed_price: sample.fixed_price,
+                ..Default::default()
+            })
+            .collect();
+        let ema = (self.result.rung == SettlementRung::EmaPrice)
+            .then_some((self.result.window_end, self.result.twap));
+        recompute(
+            &self.params,
+            &self.result.symbol,
+            &self.result.window,
+            self.result.window_end,
+            updates,
+            ema,
+        )
+    }
+}
+
+/// Replay `updates` for `symbol` into a calculator configured by `params`
+/// and calculate the window named `window` ending at `window_end`. Updates
+/// for other symbols are ignored and the rest are replayed in publish-time
+/// order, with retention wide enough to keep all of them. `ema`, if given,
+/// is recorded as the Pyth EMA price `(publish_time, price)`.
+pub fn recompute(
+    params: &ProofParams,
+    symbol: &str,
+    window: &str,
+    window_end: i64,
+    updates: impl IntoIterator<Item = PriceUpdate>,
+    ema: Option<(i64, f64)>,
+) -> anyhow::Result<Option<TwapResult>> {
+    anyhow::ensure!(
+        params.window_secs > 0,
+        "window_secs must be positive (got {})",
+        params.window_secs
+    );
+    if let TwapMethod::TrimmedMean { trim_pct } = params.method {
+        anyhow::ensure!(trim_pct < 50, "trim_pct must be below 50 (got {trim_pct})");
+    }
+    if let Some(policy) = &params.coverage_policy {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&policy.min_coverage),
+            "min_coverage must be within 0.0..=1.0 (got {})",
+            policy.min_coverage
+        );
+    }
+    if let Some(policy) = &params.confidence_policy {
+        anyhow::ensure!(
+            policy.max_ratio > 0.0,
+            "max_ratio must be positive (got {})",
+            policy.max_ratio
+        );
+        if let ConfidenceAction::DownWeight { weight } = policy.action {
+            anyhow::ensure!(
+                weight > 0.0 && weight <= 1.0,
+                "down-weight must be within (0.0, 1.0] (got {weight})"
+            );
+        }
+    }
+
+    let mut updates: Vec<PriceUpdate> = updates
+        .into_iter()
+        .filter(|update| update.symbol == symbol)
+        .collect();
+    updates.sort_by_key(|update| update.publish_time);
+    let span = match (updates.first(), updates.last()) {
+        (Some(first), Some(last)) => last.publish_time.max(window_end) - first.publish_time,
+        _ => 0,
+    };
+    let retention_windows = u32::try_from(span / params.window_secs + 2).unwrap_or(u32::MAX);
+
+    let mut calculator = TwapCalculator::with_windows(
+        vec![TwapWindow::new(window, params.window_secs)],
+        retention_windows,
+    )
+    .with_method(params.method)
+    .with_gap_policy(params.gap_policy)
+    .with_symbol_rounding(symbol, params.rounding);
+    if let Some(policy) = &params.coverage_policy {
+        calculator = calculator.with_coverage_policy(symbol, policy.clone());
+    }
+    if let Some(policy) = params.confidence_policy {
+        calculator = calculator.with_confidence_policy(symbol, policy);
+    }
+    anyhow::ensure!(
+        calculator.sample_interval_secs() == params.sample_interval_secs,
+        "unsupported sample interval {}s (calculator samples every {}s)",
+        params.sample_interval_secs,
+        calculator.sample_interval_secs()
+    );
+
+    for update in &updates {
+        calculator.record(update);
+    }
+    if let Some((publish_time, ema_price)) = ema {
+        calculator.record_ema(symbol, ema_price, publish_time);
+    }
+    Ok(calculator.calculate(symbol, window_end))
+}
+
+/// Human-readable differences between a `claimed` result and a
+/// `recomputed` one. Empty when they agree; prices and coverage are
+/// compared with a small tolerance.
+pub fn result_mismatches(claimed: &TwapResult, recomputed: &TwapResult) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let mut check = |field: &str, claimed: String, recomputed: String, equal: bool| {
+        if !equal {
+            mismatches.push(format!(
+                "{field}: claimed {claimed}, recomputed {recomputed}"
+            ));
+        }
+    };
+
+    check(
+        "symbol",
+        claimed.symbol.clone(),
+        recomputed.symbol.clone(),
+        claimed.symbol == recomputed.symbol,
+    );
+    check(
+        "window",
+        claimed.window.clone(),
+        recomputed.window.clone(),
+        claimed.window == recomputed.window,
+    );
+    check(
+        "window_start",
+        claimed.window_start.to_string(),
+        recomputed.window_start.to_string(),
+        claimed.window_start == recomputed.window_start,
+    );
+    check(
+        "window_end",
+        claimed.window_end.to_string(),
+        recomputed.window_end.to_string(),
+        claimed.window_end == recomputed.window_end,
+    );
+    check(
+        "twap",
+        claimed.twap.to_string(),
+        recomputed.twap.to_string(),
+        (claimed.twap - recomputed.twap).abs()
+            <= PRICE_TOLERANCE * claimed.twap.abs().max(recomputed.twap.abs()),
+    );
+    check(
+        "fixed_twap",
+        format!("{:?}", claimed.fixed_twap),
+        format!("{:?}", recomputed.fixed_twap),
+        claimed.fixed_twap == recomputed.fixed_twap,
+    );
+    check(
+        "sample_count",
+        claimed.sample_count.to_string(),
+        recomputed.sample_count.to_string(),
+        claimed.sample_count == recomputed.sample_count,
+    );
+    check(
+        "coverage",
+        claimed.coverage.to_string(),
+        recomputed.coverage.to_string(),
+        (claimed.coverage - recomputed.coverage).abs() <= PRICE_TOLERANCE,
+    );
+    check(
+        "method",
+        format!("{:?}", claimed.method),
+        format!("{:?}", recomputed.method),
+        claimed.method == recomputed.method,
+    );
+    check(
+        "flagged_samples",
+        claimed.flagged_samples.to_string(),
+        recomputed.flagged_samples.to_string(),
+        claimed.flagged_samples == recomputed.flagged_samples,
+    );
+    check(
+        "rung",
+        format!("{:?}", claimed.rung),
+        format!("{:?}", recomputed.rung),
+        claimed.rung == recomputed.rung,
+    );
+    check(
+        "gaps",
+        format!("{} intervals", claimed.gaps.len()),
+        format!("{} intervals", recomputed.gaps.len()),
+        claimed.gaps == recomputed.gaps,
+    );
+    mismatches
+}
+
+/// Hex-encoded SHA-256 Merkle root over `samples`, in order.
+pub fn merkle_root(samples: &[TwapSample]) -> String {
+    let mut level: Vec<[u8; 32]> = samples.iter().map(leaf_hash).collect();
+    if level.is_empty() {
+        return to_hex(&Sha256::digest([]).into());
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut hasher = Sha256::new();
+                    hasher.update([NODE_PREFIX]);
+                    hasher.update(left);
+                    hasher.update(right);
+                    hasher.finalize().into()
+                }
+                [odd] => *odd,
+                _ => unreachable!("chunks(2) yields one or two nodes"),
+            })
+            .collect();
+    }
+    to_hex(&level[0])
+}
+
+/// Id of signed update data: the hex-encoded SHA-256 of its hex strings,
+/// each prefixed with its length as a big-endian `u32`.
+pub fn update_data_id(data: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for data in data {
+        hasher.update((data.len() as u32).to_be_bytes());
+        hasher.update(data.as_bytes());
+    }
+    to_hex(&hasher.finalize().into())
+}
+
+fn leaf_hash(sample: &TwapSample) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(sample.timestamp.to_be_bytes());
+    hasher.update(sample.price.to_bits().to_be_bytes());
+    hasher.update(sample.confidence.to_bits().to_be_bytes());
+    hasher.update([u8::from(sample.filled)]);
+    if let Some(fixed) = sample.fixed_price {
+        hasher.update(fixed.mantissa.to_be_bytes());
+        hasher.update(fixed.expo.to_be_bytes());
+    }
+    if let Some(id) = &sample.update_id {
+        hasher.update(id.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn to_hex(bytes: &[u8; 32]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Directory of proof bundles, one `{symbol}/{window_end}.json` per
+/// settlement.
+#[derive(Debug, Clone)]
+pub struct ProofStore {
+    dir: PathBuf,
+}
+
+impl ProofStore {
+    /// Open (creating if needed) a proof store in `dir`.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write `proof`, replacing any bundle for the same settlement. The file
+    /// appears atomically so a concurrent reader never sees half of it.
+    pub fn save(&self, proof: &SettlementProof) -> io::Result<PathBuf> {
+        let path = self
+            .path(&proof.result.symbol, proof.result.window_end)
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("unsafe symbol for proof path: {}", proof.result.symbol),
+                )
+            })?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(proof)?)?;
+        fs::rename(&tmp, &path)?;
+        debug!("Wrote settlement proof {}", path.display());
+        Ok(path)
+    }
+
+    /// The stored bundle for `symbol` at `window_end`, as written.
+    /// `Ok(None)` if there is none or `symbol` is not a plain name.
+    pub fn load_json(&self, symbol: &str, window_end: i64) -> io::Result<Option<String>> {
+        let Some(path) = self.path(symbol, window_end) else {
+            return Ok(None);
+        };
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(Some(json)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like [`load_json`](Self::load_json), parsed.
+    pub fn load(&self, symbol: &str, window_end: i64) -> io::Result<Option<SettlementProof>> {
+        self.load_json(symbol, window_end)?
+            .map(|json| serde_json::from_str(&json).map_err(io::Error::from))
+            .transpose()
+    }
+
+    /// `None` unless `symbol` is a plain name, so requests cannot address
+    /// files outside the store.
+    fn path(&self, symbol: &str, window_end: i64) -> Option<PathBuf> {
+        let plain = !symbol.is_empty()
+            && symbol
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        plain.then(|| self.dir.join(symbol).join(format!("{window_end}.json")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::twap_calculator::SettlementFallback;
+    use joyride_oracle_wire::{FixedPrice, RoundingMode};
+
+    fn make_update(price: f64, timestamp: i64) -> PriceUpdate {
+        PriceUpdate {
+            symbol: "SOL".to_string(),
+            price,
+            confidence: 0.05,
+            publish_time: timestamp,
+            feed_id: "0x123".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("joyride-proof-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn build_bundles_window_and_leading_sample() {
+        let mut calc = TwapCalculator::with_window(10).with_method(TwapMethod::TimeWeighted);
+        for (price, timestamp) in [(90.0, 985), (100.0, 995), (110.0, 1003), (120.0, 1012)] {
+            calc.record(&make_update(price, timestamp));
+        }
+        let result = calc.calculate("SOL", 1010).unwrap();
+        let proof = SettlementProof::build(&calc, &result);
+
+        let timestamps: Vec<i64> = proof.samples.iter().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, vec![995, 1003]);
+        assert_eq!(proof.params.window_secs, 10);
+        assert_eq!(proof.params.method, TwapMethod::TimeWeighted);
+        assert_eq!(proof.result, result);
+        assert!(proof.verify_root());
+    }
+
+    #[test]
+    fn build_attaches_each_update_once() {
+        let mut calc = TwapCalculator::with_window(10);
+        let data: Vec<String> = vec!["504e4155".to_string()];
+        let shared = std::sync::Arc::new(UpdateData {
+            id: update_data_id(&data),
+            data,
+        });
+        for timestamp in [1001, 1002] {
+            for symbol in ["SOL", "BTC"] {
+                calc.record(&PriceUpdate {
+                    symbol: symbol.to_string(),
+                    update_data: Some(shared.clone()),
+                    ..make_update(100.0, timestamp)
+                });
+            }
+        }
+        calc.record(&make_update(101.0, 1003));
+        let result = calc.calculate("SOL", 1010).unwrap();
+        let proof = SettlementProof::build(&calc, &result);
+
+        assert_eq!(proof.samples.len(), 3);
+        assert_eq!(proof.update_data, [UpdateData::clone(&shared)]);
+        assert_eq!(proof.samples[0].update_id, Some(shared.id.clone()));
+        assert_eq!(proof.samples[2].update_id, None);
+        assert!(proof.verify_root());
+
+        let mut tampered = proof.clone();
+        tampered.update_data[0].data = vec!["504e4156".to_string()];
+        assert!(!tampered.verify_root());
+    }
+
+    #[test]
+    fn merkle_root_commits_to_every_field() {
+        let samples: Vec<TwapSample> = (0..5)
+            .map(|i| TwapSample {
+                price: 100.0 + i as f64,
+                timestamp: 1000 + i,
+                confidence: 0.1,
+                fixed_price: None,
+                filled: false,
+                wide: false,
+                weight: 1.0,
+                update_id: None,
+            })
+            .collect();
+        let root = merkle_root(&samples);
+        assert_eq!(root.len(), 64);
+        assert_eq!(root, merkle_root(&samples));
+
+        let mut tampered = samples.clone();
+        tampered[4].filled = true;
+        assert_ne!(merkle_root(&tampered), root);
+        let mut tampered = samples.clone();
+        tampered.swap(0, 1);
+        assert_ne!(merkle_root(&tampered), root);
+        let mut tampered = samples.clone();
+        tampered[2].fixed_price = Some(FixedPrice::new(10_200, -2));
+        assert_ne!(merkle_root(&tampered), root);
+        let mut tampered = samples.clone();
+        tampered[3].update_id = Some(update_data_id(&["504e4155".to_string()]));
+        assert_ne!(merkle_root(&tampered), root);
+
+        // Promoting the odd node means a duplicated tail changes the root.
+        let mut padded = samples.clone();
+        padded.push(samples[4].clone());
+        assert_ne!(merkle_root(&padded), root);
+
+        assert_eq!(
+            merkle_root(&[]),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn recompute_reproduces_bundle_and_flags_tampering() {
+        let policy = CoveragePolicy {
+            min_coverage: 0.9,
+            fallbacks: vec![SettlementFallback::ExtendWindow { max_extra_secs: 10 }],
+        };
+        let mut calc = TwapCalculator::with_window(10)
+            .with_method(TwapMethod::TimeWeighted)
+            .with_gap_policy(GapPolicy::CarryForward { max_gap_secs: 2 })
+            .with_coverage_policy("SOL", policy);
+        for timestamp in (990..1000).chain([1001, 1003, 1009, 1010]) {
+            calc.record(&make_update(100.0 + (timestamp % 7) as f64, timestamp));
+        }
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.rung, SettlementRung::ExtendedWindow);
+        let proof = SettlementProof::build(&calc, &result);
+
+        let recomputed = proof.recompute().unwrap().unwrap();
+        assert!(result_mismatches(&proof.result, &recomputed).is_empty());
+
+        let mut tampered = proof.clone();
+        tampered.result.twap += 0.01;
+        let recomputed = tampered.recompute().unwrap().unwrap();
+        let mismatches = result_mismatches(&tampered.result, &recomputed);
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].starts_with("twap:"));
+    }
+
+    #[test]
+    fn recompute_matches_fixed_point_settlement_exactly() {
+        let rounding = RoundingRule {
+            mode: RoundingMode::Floor,
+            expo: Some(-2),
+        };
+        let mut calc = TwapCalculator::with_window(10).with_symbol_rounding("SOL", rounding);
+        for i in 0..10 {
+            let fixed = FixedPrice::new(10_000_037 + i * 101, -5);
+            calc.record(&PriceUpdate {
+                fixed_price: Some(fixed),
+                ..make_update(fixed.to_f64(), 1000 + i)
+            });
+        }
+        let result = calc.calculate("SOL", 1009).unwrap();
+        assert_eq!(result.fixed_twap, Some(FixedPrice::new(10_000, -2)));
+        let proof = SettlementProof::build(&calc, &result);
+        assert_eq!(proof.params.rounding, rounding);
+
+        let recomputed = proof.recompute().unwrap().unwrap();
+        assert!(result_mismatches(&proof.result, &recomputed).is_empty());
+
+        let mut tampered = proof.clone();
+        tampered.result.fixed_twap = Some(FixedPrice::new(10_001, -2));
+        let recomputed = tampered.recompute().unwrap().unwrap();
+        let mismatches = result_mismatches(&tampered.result, &recomputed);
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].starts_with("fixed_twap:"));
+    }
+
+    #[test]
+    fn store_round_trips_and_rejects_unsafe_symbols() {
+        let dir = scratch_dir("store");
+        let store = ProofStore::open(&dir).unwrap();
+        let mut calc = TwapCalculator::with_window(10);
+        for i in 0..10 {
+            calc.record(&make_update(100.0 + i as f64, 1000 + i));
+        }
+        let proof = SettlementProof::build(&calc, &calc.calculate("SOL", 1009).unwrap());
+
+        let path = store.save(&proof).unwrap();
+        assert_eq!(path, dir.join("SOL").join("1009.json"));
+        assert_eq!(store.load("SOL", 1009).unwrap(), Some(proof));
+        assert_eq!(store.load("SOL", 1010).unwrap(), None);
+        assert_eq!(store.load_json("../SOL", 1009).unwrap(), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
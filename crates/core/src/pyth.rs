@@ -1,7 +1,11 @@
 //! Pyth Hermes client for streaming price updates.
 
-use std::collections::HashMap;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
 
 use eventsource_client::{Client, SSE};
 use futures_util::StreamExt;
@@ -10,29 +14,61 @@ use tokio::sync::mpsc;
 use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
-use crate::types::{Asset, OracleEvent};
-use joyride_oracle_wire::PriceUpdate;
+use crate::assets::{normalize_feed_id, AssetRegistry};
+use crate::catalog::PriceFeedCatalog;
+use crate::health::{
+    FreshnessTracker, DEFAULT_DEGRADED_LAG_SECS, DEFAULT_DEGRADED_UNCHANGED_STREAK,
+};
+use crate::proof::update_data_id;
+use crate::source::{unix_now_secs, Backoff};
+use crate::types::OracleEvent;
+use joyride_oracle_wire::{FixedPrice, PriceUpdate, UpdateData};
 
 /// Default Hermes API endpoint.
 pub const HERMES_URL: &str = "https://hermes.pyth.network";
 const FRESHNESS_LOG_INTERVAL: Duration = Duration::from_secs(5);
 const SSE_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
-const INITIAL_RECONNECT_BACKOFF_SECS: u64 = 5;
-const MAX_RECONNECT_BACKOFF_SECS: u64 = 60;
-const MAX_RECEIVE_LAG_MS: i64 = 10_000;
-const MAX_UNCHANGED_STREAK: u32 = 5;
+/// Longest outage backfilled after a reconnect; older seconds stay missing.
+const MAX_BACKFILL_SECS: i64 = 300;
+/// Longest live prices are held back for a backfill; seconds not fetched by
+/// then stay missing.
+const MAX_BACKFILL_DURATION: Duration = Duration::from_secs(10);
+/// Backfill requests in flight at once.
+const BACKFILL_CONCURRENCY: usize = 8;
+const STREAM_EVENT_BUFFER: usize = 256;
+/// Query parameters asking Hermes for parsed prices alongside the signed
+/// update data, hex-encoded.
+const UPDATE_QUERY: &str = "encoding=hex&parsed=true";
 
 #[derive(Debug, Deserialize)]
 struct HermesPriceResponse {
+    #[serde(default)]
+    binary: Option<BinaryUpdate>,
     parsed: Vec<ParsedPrice>,
 }
 
+/// Signed update data Hermes sends with the parsed prices. One update
+/// covers every feed in the request.
+#[derive(Debug, Deserialize)]
+struct BinaryUpdate {
+    data: Vec<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ParsedPrice {
     id: String,
     price: PriceData,
-    #[allow(dead_code)]
     ema_price: PriceData,
+    #[serde(default)]
+    metadata: Option<PriceMetadata>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PriceMetadata {
+    #[serde(default)]
+    slot: Option<u64>,
+    #[serde(default)]
+    prev_publish_time: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,300 +79,759 @@ struct PriceData {
     publish_time: i64,
 }
 
+impl PriceData {
+    /// Price and confidence scaled by the exponent, or `None` if either
+    /// field does not parse.
+    fn to_f64_pair(&self) -> Option<(f64, f64)> {
+        let price: i64 = self.price.parse().ok()?;
+        let conf: i64 = self.conf.parse().ok()?;
+        Some((
+            FixedPrice::new(price, self.expo).to_f64(),
+            FixedPrice::new(conf, self.expo).to_f64(),
+        ))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct StreamUpdate {
+    #[serde(default)]
+    binary: Option<BinaryUpdate>,
     parsed: Vec<ParsedPrice>,
 }
 
 #[derive(Debug, Default)]
 struct AssetFreshnessState {
-    prev_publish_time: Option<i64>,
-    unchanged_streak: u32,
+    tracker: FreshnessTracker,
     last_log_instant: Option<Instant>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-struct FreshnessObservation {
-    publish_advanced: bool,
-    unchanged_streak: u32,
-    receive_lag_ms: i64,
-    publish_gap_secs: Option<i64>,
+impl AssetFreshnessState {
+    fn should_emit_sample(&self, now: Instant) -> bool {
+        self.last_log_instant
+            .map(|last| now.duration_since(last) >= FRESHNESS_LOG_INTERVAL)
+            .unwrap_or(true)
+    }
+
+    fn mark_logged(&mut self, now: Instant) {
+        self.last_log_instant = Some(now);
+    }
+}
+
+/// How [`PythClient`] uses its Hermes endpoints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StreamMode {
+    /// Stream from one endpoint at a time and move to the next one after an
+    /// error, idle timeout or closed stream.
+    #[default]
+    Failover,
+    /// Hold streams to two endpoints at once and forward each feed's publish
+    /// time only once, so either endpoint can drop without a gap.
+    Redundant,
 }
 
-impl AssetFreshnessState {
-    fn observe(&mut self, publish_time: i64, receive_time: i64) -> FreshnessObservation {
-        let publish_gap_secs = self
-            .prev_publish_time
-            .map(|previous_publish_time| publish_time.saturating_sub(previous_publish_time));
-        let publish_advanced = self
-            .prev_publish_time
-            .map(|prev| prev != publish_time)
-            .unwrap_or(true);
-
-        if publish_advanced {
-            self.unchanged_streak = 0;
-        } else {
-            self.unchanged_streak = self.unchanged_streak.saturating_add(1);
+impl StreamMode {
+    /// Streams held open at once.
+    fn streams(self) -> usize {
+        match self {
+            Self::Failover => 1,
+            Self::Redundant => 2,
         }
+    }
+}
 
-        self.prev_publish_time = Some(publish_time);
+impl FromStr for StreamMode {
+    type Err = anyhow::Error;
 
-        FreshnessObservation {
-            publish_advanced,
-            unchanged_streak: self.unchanged_streak,
-            receive_lag_ms: receive_time
-                .saturating_sub(publish_time)
-                .saturating_mul(1000),
-            publish_gap_secs,
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "failover" => Ok(Self::Failover),
+            "redundant" => Ok(Self::Redundant),
+            other => bail!("unknown Hermes stream mode `{other}` (expected failover or redundant)"),
         }
     }
+}
 
-    fn should_emit_sample(&self, now: Instant) -> bool {
-        self.last_log_instant
-            .map(|last| now.duration_since(last) >= FRESHNESS_LOG_INTERVAL)
-            .unwrap_or(true)
+/// Health of one Hermes endpoint as seen by [`PythClient`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// Whether an SSE stream to this endpoint is open.
+    pub connected: bool,
+    /// Price updates received from this endpoint, duplicates included.
+    pub updates: u64,
+    /// Updates this endpoint delivered first, which were forwarded.
+    pub forwarded: u64,
+    /// Streams to this endpoint that ended in an error or idle timeout.
+    pub failures: u64,
+    /// Unix time the last update from this endpoint arrived.
+    pub last_update: Option<i64>,
+    pub last_error: Option<String>,
+    /// Updates per asset this endpoint never delivered, detected from the
+    /// `prev_publish_time` Hermes attaches to each update.
+    pub missed_updates: BTreeMap<String, u64>,
+}
+
+/// Shared view of a [`PythClient`]'s endpoint health, readable while the
+/// client runs.
+#[derive(Debug, Clone)]
+pub struct EndpointHealthHandle(Arc<Mutex<Vec<EndpointHealth>>>);
+
+impl EndpointHealthHandle {
+    fn new(urls: &[String]) -> Self {
+        Self(Arc::new(Mutex::new(
+            urls.iter()
+                .map(|url| EndpointHealth {
+                    url: url.clone(),
+                    ..EndpointHealth::default()
+                })
+                .collect(),
+        )))
     }
 
-    fn mark_logged(&mut self, now: Instant) {
-        self.last_log_instant = Some(now);
+    /// Health of every endpoint, in order of preference.
+    pub fn snapshot(&self) -> Vec<EndpointHealth> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
+
+    fn update(&self, index: usize, f: impl FnOnce(&mut EndpointHealth)) {
+        if let Some(health) = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(index)
+        {
+            f(health);
+        }
+    }
+}
+
+/// What an endpoint's stream task reports to [`PythClient::run`].
+#[derive(Debug)]
+enum StreamEvent {
+    Connected(usize),
+    Update(usize, PriceUpdate),
+    /// The stream skipped at least one update of this asset.
+    Missed(usize, String),
+    /// The stream closed (`Ok`) or failed; the task has exited.
+    Ended(usize, Result<(), String>),
+    /// Updates fetched for the seconds before `until`, in publish-time
+    /// order; the backfill task has exited.
+    Backfilled {
+        until: i64,
+        updates: Vec<PriceUpdate>,
+    },
 }
 
 /// Client for Pyth Hermes API.
 pub struct PythClient {
     event_tx: mpsc::Sender<OracleEvent>,
-    assets: Vec<Asset>,
-    hermes_url: String,
+    assets: AssetRegistry,
+    /// Hermes base URLs in order of preference.
+    endpoints: Vec<String>,
+    mode: StreamMode,
+    health: EndpointHealthHandle,
+    /// Publish time of the newest update sent per symbol, live or
+    /// backfilled, so a reconnect knows which seconds it missed.
+    last_published: HashMap<String, i64>,
+    /// Whether a reconnect fetches the seconds the stream missed.
+    backfill: bool,
 }
 
 impl PythClient {
-    pub fn new(event_tx: mpsc::Sender<OracleEvent>, assets: Vec<Asset>) -> Self {
-        Self {
-            event_tx,
-            assets,
-            hermes_url: HERMES_URL.to_string(),
-        }
+    pub fn new(event_tx: mpsc::Sender<OracleEvent>, assets: AssetRegistry) -> Self {
+        Self::with_url(event_tx, assets, HERMES_URL)
     }
 
-    pub fn with_url(event_tx: mpsc::Sender<OracleEvent>, assets: Vec<Asset>, url: &str) -> Self {
+    pub fn with_url(event_tx: mpsc::Sender<OracleEvent>, assets: AssetRegistry, url: &str) -> Self {
+        let endpoints = vec![url.to_string()];
         Self {
             event_tx,
             assets,
-            hermes_url: url.to_string(),
+            health: EndpointHealthHandle::new(&endpoints),
+            endpoints,
+            mode: StreamMode::default(),
+            last_published: HashMap::new(),
+            backfill: true,
         }
     }
 
+    /// Use these Hermes endpoints, in order of preference, instead of the
+    /// one given at construction.
+    pub fn with_endpoints(mut self, urls: Vec<String>) -> Self {
+        self.health = EndpointHealthHandle::new(&urls);
+        self.endpoints = urls;
+        self
+    }
+
+    pub fn with_stream_mode(mut self, mode: StreamMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Whether to backfill the seconds missed while disconnected. On by
+    /// default. Turn it off when other sources feed the same TWAP through
+    /// a `PriceAggregator`: they keep sampling through the outage, so the
+    /// backfilled seconds would only arrive behind their newest samples.
+    pub fn with_backfill(mut self, backfill: bool) -> Self {
+        self.backfill = backfill;
+        self
+    }
+
+    pub fn assets(&self) -> &AssetRegistry {
+        &self.assets
+    }
+
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    /// Per-endpoint health, updated as [`run`](Self::run) streams.
+    pub fn endpoint_health(&self) -> EndpointHealthHandle {
+        self.health.clone()
+    }
+
+    /// Fetch the Hermes price-feed catalog from the first endpoint that
+    /// serves it.
+    pub async fn fetch_catalog(&self) -> anyhow::Result<PriceFeedCatalog> {
+        PriceFeedCatalog::fetch_any(&self.endpoints).await
+    }
+
+    /// Resolve missing feed IDs and validate configured ones against the
+    /// current Hermes catalog; see [`AssetRegistry::resolve_feeds`]. Takes
+    /// effect on the next (re)connect.
+    pub async fn refresh_feeds(&mut self) -> anyhow::Result<()> {
+        let catalog = self.fetch_catalog().await?;
+        self.assets.resolve_feeds(&catalog)
+    }
+
     pub async fn run(&mut self) -> anyhow::Result<()> {
-        let mut backoff_secs = INITIAL_RECONNECT_BACKOFF_SECS;
-
-        loop {
-            let reconnect_reason: String;
-            match self.connect_and_stream().await {
-                Ok(()) => {
-                    reconnect_reason = "stream_closed".to_string();
-                    info!("Pyth connection closed gracefully");
-                    backoff_secs = INITIAL_RECONNECT_BACKOFF_SECS;
-                }
-                Err(e) => {
-                    reconnect_reason = e.to_string();
-                    error!("Pyth connection error: {}", e);
-                    let _ = self
-                        .event_tx
-                        .send(OracleEvent::Error {
-                            message: e.to_string(),
-                        })
-                        .await;
-                }
-            }
+        anyhow::ensure!(
+            self.assets.is_resolved(),
+            "every asset needs a feed ID; call refresh_feeds first"
+        );
+        anyhow::ensure!(!self.endpoints.is_empty(), "no Hermes endpoints configured");
 
-            let _ = self.event_tx.send(OracleEvent::Disconnected).await;
-            info!(
-                backoff_secs,
-                reconnect_reason = %reconnect_reason,
-                "Reconnecting to Pyth after backoff"
-            );
-            tokio::time::sleep(tokio::time::Duration::from_secs(backoff_secs)).await;
-            backoff_secs = next_backoff_secs(backoff_secs);
+        let (stream_tx, mut stream_rx) = mpsc::channel(STREAM_EVENT_BUFFER);
+        let mut active = Vec::new();
+        for index in 0..self.mode.streams().min(self.endpoints.len()) {
+            self.spawn_stream(index, Duration::ZERO, &stream_tx);
+            active.push(index);
         }
-    }
+        let mut open = vec![false; self.endpoints.len()];
+        let mut failures_in_a_row = 0;
+        let mut backoff = Backoff::new();
+        // Live updates held while a backfill is in flight, so the missed
+        // seconds still reach consumers first.
+        let mut held_live: Option<Vec<(usize, PriceUpdate)>> = None;
 
-    pub async fn fetch_latest(&self) -> anyhow::Result<Vec<PriceUpdate>> {
-        let feed_ids: Vec<&str> = self.assets.iter().map(|a| a.feed_id()).collect();
-        let query = feed_ids
-            .iter()
-            .map(|id| format!("ids[]={}", id))
-            .collect::<Vec<_>>()
-            .join("&");
-
-        let url = format!("{}/v2/updates/price/latest?{}", self.hermes_url, query);
-        debug!("Fetching latest prices from: {}", url);
-
-        let response = reqwest::get(&url).await?;
-        let data: HermesPriceResponse = response.json().await?;
-
-        Ok(data
-            .parsed
-            .into_iter()
-            .filter_map(|p| self.parse_price_update(p))
-            .collect())
-    }
-
-    async fn connect_and_stream(&mut self) -> anyhow::Result<()> {
-        let feed_ids: Vec<&str> = self.assets.iter().map(|a| a.feed_id()).collect();
-        let query = feed_ids
-            .iter()
-            .map(|id| format!("ids[]={}", id))
-            .collect::<Vec<_>>()
-            .join("&");
-
-        let url = format!("{}/v2/updates/price/stream?{}", self.hermes_url, query);
-        info!("Connecting to Pyth Hermes SSE stream: {}", url);
-
-        let client = eventsource_client::ClientBuilder::for_url(&url)?.build();
-        let mut stream = client.stream();
-        let mut freshness_state: HashMap<String, AssetFreshnessState> = HashMap::new();
-
-        let _ = self.event_tx.send(OracleEvent::Connected).await;
-        info!("Connected to Pyth Hermes");
-
-        loop {
-            let event = match tokio::time::timeout(SSE_IDLE_TIMEOUT, stream.next()).await {
-                Ok(Some(event)) => event,
-                Ok(None) => {
-                    info!("Pyth Hermes SSE stream ended");
-                    return Ok(());
+        while let Some(event) = stream_rx.recv().await {
+            match event {
+                StreamEvent::Connected(index) => {
+                    info!(endpoint = %self.endpoints[index], "Connected to Pyth Hermes");
+                    self.health.update(index, |health| health.connected = true);
+                    let was_down = !open.contains(&true);
+                    open[index] = true;
+                    if was_down && held_live.is_none() {
+                        if self.spawn_backfill(unix_now_secs(), &stream_tx) {
+                            held_live = Some(Vec::new());
+                        } else {
+                            let _ = self.event_tx.send(OracleEvent::Connected).await;
+                        }
+                    }
                 }
-                Err(_) => {
-                    warn!(
-                        idle_timeout_secs = SSE_IDLE_TIMEOUT.as_secs(),
-                        "No SSE events received from Hermes; forcing reconnect"
-                    );
-                    return Err(anyhow::anyhow!(
-                        "Pyth Hermes SSE idle for {}s",
-                        SSE_IDLE_TIMEOUT.as_secs()
-                    ));
+                StreamEvent::Update(index, update) => {
+                    failures_in_a_row = 0;
+                    backoff.reset();
+                    let now = unix_now_secs();
+                    self.health.update(index, |health| {
+                        health.updates += 1;
+                        health.last_update = Some(now);
+                    });
+                    match held_live.as_mut() {
+                        Some(held) => held.push((index, update)),
+                        None => self.forward_live(index, update).await,
+                    }
                 }
-            };
-
-            match event {
-                Ok(SSE::Event(ev)) if ev.event_type == "message" => {
-                    match serde_json::from_str::<StreamUpdate>(&ev.data) {
-                        Ok(update) => {
-                            for parsed in update.parsed {
-                                if let Some(price_update) = self.parse_price_update(parsed) {
-                                    let receive_time = unix_now_secs();
-                                    let now = Instant::now();
-                                    let state = freshness_state
-                                        .entry(price_update.symbol.clone())
-                                        .or_default();
-                                    let observation =
-                                        state.observe(price_update.publish_time, receive_time);
-                                    let abnormal = observation.receive_lag_ms > MAX_RECEIVE_LAG_MS
-                                        || observation.unchanged_streak >= MAX_UNCHANGED_STREAK;
-
-                                    if abnormal {
-                                        warn!(
-                                            asset = %price_update.symbol,
-                                            publish_time = price_update.publish_time,
-                                            publish_gap_secs = observation.publish_gap_secs,
-                                            receive_time,
-                                            receive_lag_ms = observation.receive_lag_ms,
-                                            publish_advanced = observation.publish_advanced,
-                                            unchanged_streak = observation.unchanged_streak,
-                                            "hermes_freshness_abnormal"
-                                        );
-                                    } else if state.should_emit_sample(now) {
-                                        info!(
-                                            asset = %price_update.symbol,
-                                            publish_time = price_update.publish_time,
-                                            publish_gap_secs = observation.publish_gap_secs,
-                                            receive_time,
-                                            receive_lag_ms = observation.receive_lag_ms,
-                                            publish_advanced = observation.publish_advanced,
-                                            unchanged_streak = observation.unchanged_streak,
-                                            "hermes_freshness_sample"
-                                        );
-                                        state.mark_logged(now);
-                                    }
-
-                                    if let Err(e) =
-                                        self.event_tx.send(OracleEvent::Price(price_update)).await
-                                    {
-                                        error!("Failed to send price update: {}", e);
-                                    }
-                                }
-                            }
+                StreamEvent::Backfilled { until, updates } => {
+                    let backfilled = self.send_backfill(until, updates).await;
+                    if backfilled > 0 {
+                        info!(
+                            backfilled,
+                            "Backfilled price updates missed while disconnected"
+                        );
+                    }
+                    let _ = self.event_tx.send(OracleEvent::Connected).await;
+                    for (index, update) in held_live.take().unwrap_or_default() {
+                        self.forward_live(index, update).await;
+                    }
+                }
+                StreamEvent::Missed(index, symbol) => {
+                    self.health.update(index, |health| {
+                        *health.missed_updates.entry(symbol).or_default() += 1;
+                    });
+                }
+                StreamEvent::Ended(index, result) => {
+                    let endpoint = &self.endpoints[index];
+                    let reconnect_reason = match &result {
+                        Ok(()) => {
+                            info!(endpoint = %endpoint, "Pyth connection closed gracefully");
+                            backoff.reset();
+                            "stream_closed".to_string()
                         }
                         Err(e) => {
-                            warn!("Failed to parse SSE update: {}", e);
+                            error!(endpoint = %endpoint, "Pyth connection error: {}", e);
+                            let _ = self
+                                .event_tx
+                                .send(OracleEvent::Error { message: e.clone() })
+                                .await;
+                            e.clone()
+                        }
+                    };
+                    self.health.update(index, |health| {
+                        health.connected = false;
+                        if let Err(e) = result {
+                            health.failures += 1;
+                            health.last_error = Some(e);
                         }
+                    });
+                    active.retain(|&i| i != index);
+                    if std::mem::take(&mut open[index]) && !open.contains(&true) {
+                        let _ = self.event_tx.send(OracleEvent::Disconnected).await;
                     }
+
+                    // Move straight to an untried endpoint; back off once
+                    // every endpoint has failed since the last update.
+                    failures_in_a_row += 1;
+                    let next = next_endpoint(index, self.endpoints.len(), &active);
+                    let delay = if next != index && failures_in_a_row < self.endpoints.len() {
+                        Duration::ZERO
+                    } else {
+                        backoff.next_delay()
+                    };
+                    info!(
+                        backoff_secs = delay.as_secs(),
+                        endpoint = %self.endpoints[next],
+                        reconnect_reason = %reconnect_reason,
+                        "Reconnecting to Pyth after backoff"
+                    );
+                    self.spawn_stream(next, delay, &stream_tx);
+                    active.push(next);
                 }
-                Ok(SSE::Connected(_)) => {
-                    debug!("Hermes SSE connected");
-                }
-                Ok(SSE::Comment(_)) => {}
-                Err(e) => {
-                    return Err(anyhow::anyhow!("SSE stream error: {}", e));
-                }
-                _ => {}
             }
         }
+        Ok(())
+    }
+
+    /// Whether `update` should be forwarded, recording it as its symbol's
+    /// newest if so. Updates older than the newest sent are dropped. In
+    /// redundant mode so is a repeated publish time, since both streams
+    /// deliver every one; a single stream's repeats of an unchanged feed
+    /// still pass in failover mode, as Hermes sends them.
+    fn accept(&mut self, update: &PriceUpdate) -> bool {
+        let new = match self.last_published.get(&update.symbol) {
+            None => true,
+            Some(&last) => {
+                update.publish_time > last
+                    || (update.publish_time == last && self.mode == StreamMode::Failover)
+            }
+        };
+        if new {
+            self.last_published
+                .insert(update.symbol.clone(), update.publish_time);
+        }
+        new
+    }
+
+    /// Send `update` from endpoint `index` on if [`accept`](Self::accept)
+    /// lets it through.
+    async fn forward_live(&mut self, index: usize, update: PriceUpdate) {
+        let forward = self.accept(&update);
+        self.health
+            .update(index, |health| health.forwarded += u64::from(forward));
+        if forward {
+            if let Err(e) = self.event_tx.send(OracleEvent::Price(update)).await {
+                error!("Failed to send price update: {}", e);
+            }
+        }
+    }
+
+    /// Stream endpoint `index` on its own task after `delay`, reporting to
+    /// `tx` until the stream ends.
+    fn spawn_stream(&self, index: usize, delay: Duration, tx: &mpsc::Sender<StreamEvent>) {
+        let url = format!(
+            "{}/v2/updates/price/stream?{}&{UPDATE_QUERY}",
+            self.endpoints[index],
+            self.feed_query()
+        );
+        let assets = self.assets.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let result = stream_endpoint(index, &url, &assets, &tx).await;
+            let _ = tx
+                .send(StreamEvent::Ended(index, result.map_err(|e| e.to_string())))
+                .await;
+        });
     }
 
-    fn parse_price_update(&self, parsed: ParsedPrice) -> Option<PriceUpdate> {
-        // Hermes returns ids as bare lowercase hex; our Asset constants
-        // carry a 0x prefix. Normalize before matching.
-        let feed_id = if parsed.id.starts_with("0x") {
-            parsed.id.to_lowercase()
-        } else {
-            format!("0x{}", parsed.id.to_lowercase())
+    /// `ids[]=` query string selecting every configured feed.
+    fn feed_query(&self) -> String {
+        feed_query(&self.assets)
+    }
+
+    /// Fetch the seconds each symbol missed before `until` on a task of its
+    /// own, which reports them to `tx` as [`StreamEvent::Backfilled`].
+    /// Reaches back at most `MAX_BACKFILL_SECS`. Returns `false`, spawning
+    /// nothing, when there is no gap to fill or backfill is off.
+    fn spawn_backfill(&self, until: i64, tx: &mpsc::Sender<StreamEvent>) -> bool {
+        if !self.backfill {
+            return false;
+        }
+        let Some(&oldest) = self.last_published.values().min() else {
+            return false;
         };
+        let from = (oldest + 1).max(until - MAX_BACKFILL_SECS);
+        if from >= until {
+            return false;
+        }
+        let endpoints = self.endpoints.clone();
+        let assets = self.assets.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let updates = fetch_backfill(&endpoints, &assets, from, until).await;
+            let _ = tx.send(StreamEvent::Backfilled { until, updates }).await;
+        });
+        true
+    }
+
+    /// Send the backfilled `updates` published after each symbol's last
+    /// sent one and before `until`, marked as backfilled, so
+    /// `TwapCalculator::record` accepts them ahead of the live stream.
+    /// Returns how many were sent.
+    async fn send_backfill(&mut self, until: i64, updates: Vec<PriceUpdate>) -> usize {
+        let mut sent = 0;
+        for update in updates {
+            let Some(&last) = self.last_published.get(&update.symbol) else {
+                continue;
+            };
+            if update.publish_time <= last || update.publish_time >= until {
+                continue;
+            }
+            self.last_published
+                .insert(update.symbol.clone(), update.publish_time);
+            let update = PriceUpdate {
+                backfilled: true,
+                ..update
+            };
+            if let Err(e) = self.event_tx.send(OracleEvent::Price(update)).await {
+                error!("Failed to send backfilled price update: {}", e);
+                return sent;
+            }
+            sent += 1;
+        }
+        sent
+    }
+
+    pub async fn fetch_latest(&self) -> anyhow::Result<Vec<PriceUpdate>> {
+        fetch_updates(
+            &self.endpoints,
+            &self.assets,
+            &format!(
+                "/v2/updates/price/latest?{}&{UPDATE_QUERY}",
+                self.feed_query()
+            ),
+        )
+        .await
+    }
+}
+
+/// `ids[]=` query string selecting every feed in `assets`.
+fn feed_query(assets: &AssetRegistry) -> String {
+    assets
+        .iter()
+        .map(|asset| format!("ids[]={}", asset.feed_id))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Parsed updates from `path`, asked of each endpoint in order until one
+/// answers.
+async fn fetch_updates(
+    endpoints: &[String],
+    assets: &AssetRegistry,
+    path: &str,
+) -> anyhow::Result<Vec<PriceUpdate>> {
+    let mut last_error = anyhow!("no Hermes endpoints configured");
+    for endpoint in endpoints {
+        let url = format!("{endpoint}{path}");
+        debug!("Fetching prices from: {}", url);
+        let response = async {
+            reqwest::get(&url)
+                .await?
+                .error_for_status()?
+                .json::<HermesPriceResponse>()
+                .await
+        };
+        match response.await {
+            Ok(data) => return Ok(parse_price_updates(assets, data.parsed, data.binary)),
+            Err(e) => {
+                warn!(endpoint = %endpoint, "Hermes request failed: {}", e);
+                last_error = e.into();
+            }
+        }
+    }
+    Err(last_error)
+}
 
-        let asset = Asset::from_feed_id(&feed_id)?;
-        let expo = parsed.price.expo;
-        let raw_price: i64 = parsed.price.price.parse().ok()?;
-        let raw_conf: u64 = parsed.price.conf.parse().ok()?;
-        let factor = 10f64.powi(expo);
-
-        Some(PriceUpdate {
-            symbol: asset.symbol().to_string(),
-            price: raw_price as f64 * factor,
-            confidence: raw_conf as f64 * factor,
-            publish_time: parsed.price.publish_time,
-            feed_id,
+/// Updates for each second in `from..until` from Hermes's timestamped
+/// endpoint, which answers with each feed's first update at or after that
+/// second. Up to `BACKFILL_CONCURRENCY` seconds are fetched at once and the
+/// answers kept in order, so the result is in publish-time order per
+/// symbol, repeats included. Stops at the first failed request or after
+/// `MAX_BACKFILL_DURATION`, keeping what arrived before.
+async fn fetch_backfill(
+    endpoints: &[String],
+    assets: &AssetRegistry,
+    from: i64,
+    until: i64,
+) -> Vec<PriceUpdate> {
+    let query = feed_query(assets);
+    let deadline = Instant::now() + MAX_BACKFILL_DURATION;
+    let mut responses = futures_util::stream::iter(from..until)
+        .map(|at| {
+            let path = format!("/v2/updates/price/{at}?{query}&{UPDATE_QUERY}");
+            async move { (at, fetch_updates(endpoints, assets, &path).await) }
         })
+        .buffered(BACKFILL_CONCURRENCY);
+
+    let mut updates = Vec::new();
+    loop {
+        match tokio::time::timeout_at(deadline, responses.next()).await {
+            Ok(Some((_, Ok(batch)))) => updates.extend(batch),
+            Ok(Some((at, Err(e)))) => {
+                warn!(
+                    at,
+                    until, "Hermes backfill failed; leaving the rest of the gap: {}", e
+                );
+                break;
+            }
+            Ok(None) => break,
+            Err(_) => {
+                warn!(
+                    until,
+                    timeout_secs = MAX_BACKFILL_DURATION.as_secs(),
+                    "Hermes backfill timed out; leaving the rest of the gap"
+                );
+                break;
+            }
+        }
     }
+    updates
 }
 
-fn unix_now_secs() -> i64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs() as i64
+/// Stream one Hermes SSE endpoint into `tx` until it errors, idles out or
+/// closes, logging feed freshness along the way.
+async fn stream_endpoint(
+    index: usize,
+    url: &str,
+    assets: &AssetRegistry,
+    tx: &mpsc::Sender<StreamEvent>,
+) -> anyhow::Result<()> {
+    info!("Connecting to Pyth Hermes SSE stream: {}", url);
+
+    let client = eventsource_client::ClientBuilder::for_url(url)?.build();
+    let mut stream = client.stream();
+    let mut freshness_state: HashMap<String, AssetFreshnessState> = HashMap::new();
+
+    loop {
+        let event = match tokio::time::timeout(SSE_IDLE_TIMEOUT, stream.next()).await {
+            Ok(Some(event)) => event,
+            Ok(None) => {
+                info!("Pyth Hermes SSE stream ended");
+                return Ok(());
+            }
+            Err(_) => {
+                warn!(
+                    endpoint_index = index,
+                    idle_timeout_secs = SSE_IDLE_TIMEOUT.as_secs(),
+                    "No SSE events received from Hermes; forcing reconnect"
+                );
+                return Err(anyhow!(
+                    "Pyth Hermes SSE idle for {}s",
+                    SSE_IDLE_TIMEOUT.as_secs()
+                ));
+            }
+        };
+
+        match event {
+            Ok(SSE::Event(ev)) if ev.event_type == "message" => {
+                match serde_json::from_str::<StreamUpdate>(&ev.data) {
+                    Ok(update) => {
+                        for price_update in
+                            parse_price_updates(assets, update.parsed, update.binary)
+                        {
+                            let receive_time = unix_now_secs();
+                            let now = Instant::now();
+                            let state = freshness_state
+                                .entry(price_update.symbol.clone())
+                                .or_default();
+                            let observation = state.tracker.observe(
+                                price_update.publish_time,
+                                price_update.prev_publish_time,
+                                receive_time,
+                            );
+                            let abnormal = observation.missed_update
+                                || observation.receive_lag_ms > DEFAULT_DEGRADED_LAG_SECS * 1000
+                                || observation.unchanged_streak
+                                    >= DEFAULT_DEGRADED_UNCHANGED_STREAK;
+
+                            if abnormal {
+                                warn!(
+                                    endpoint_index = index,
+                                    asset = %price_update.symbol,
+                                    publish_time = price_update.publish_time,
+                                    publish_gap_secs = observation.publish_gap_secs,
+                                    prev_publish_time = price_update.prev_publish_time,
+                                    missed_update = observation.missed_update,
+                                    receive_time,
+                                    receive_lag_ms = observation.receive_lag_ms,
+                                    publish_advanced = observation.publish_advanced,
+                                    unchanged_streak = observation.unchanged_streak,
+                                    "hermes_freshness_abnormal"
+                                );
+                            } else if state.should_emit_sample(now) {
+                                info!(
+                                    endpoint_index = index,
+                                    asset = %price_update.symbol,
+                                    publish_time = price_update.publish_time,
+                                    publish_gap_secs = observation.publish_gap_secs,
+                                    prev_publish_time = price_update.prev_publish_time,
+                                    missed_update = observation.missed_update,
+                                    receive_time,
+                                    receive_lag_ms = observation.receive_lag_ms,
+                                    publish_advanced = observation.publish_advanced,
+                                    unchanged_streak = observation.unchanged_streak,
+                                    "hermes_freshness_sample"
+                                );
+                                state.mark_logged(now);
+                            }
+
+                            if observation.missed_update
+                                && tx
+                                    .send(StreamEvent::Missed(index, price_update.symbol.clone()))
+                                    .await
+                                    .is_err()
+                            {
+                                return Ok(());
+                            }
+                            if tx
+                                .send(StreamEvent::Update(index, price_update))
+                                .await
+                                .is_err()
+                            {
+                                return Ok(());
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to parse SSE update: {}", e);
+                    }
+                }
+            }
+            Ok(SSE::Connected(_)) => {
+                debug!("Hermes SSE connected");
+                if tx.send(StreamEvent::Connected(index)).await.is_err() {
+                    return Ok(());
+                }
+            }
+            Ok(SSE::Comment(_)) => {}
+            Err(e) => {
+                return Err(anyhow!("SSE stream error: {}", e));
+            }
+            _ => {}
+        }
+    }
 }
 
-fn next_backoff_secs(current: u64) -> u64 {
-    (current.saturating_mul(2)).min(MAX_RECONNECT_BACKOFF_SECS)
+/// The endpoint after `ended` in preference order, wrapping around, that
+/// has no stream in `active`.
+fn next_endpoint(ended: usize, count: usize, active: &[usize]) -> usize {
+    (1..=count)
+        .map(|step| (ended + step) % count)
+        .find(|index| !active.contains(index))
+        .unwrap_or(ended)
+}
+
+/// Price updates for the configured feeds in one Hermes response, all
+/// sharing the response's signed update data.
+fn parse_price_updates(
+    assets: &AssetRegistry,
+    parsed: Vec<ParsedPrice>,
+    binary: Option<BinaryUpdate>,
+) -> Vec<PriceUpdate> {
+    let update_data = binary
+        .filter(|binary| !binary.data.is_empty())
+        .map(|binary| {
+            Arc::new(UpdateData {
+                id: update_data_id(&binary.data),
+                data: binary.data,
+            })
+        });
+    parsed
+        .into_iter()
+        .filter_map(|parsed| parse_price_update(assets, parsed, update_data.as_ref()))
+        .collect()
+}
+
+fn parse_price_update(
+    assets: &AssetRegistry,
+    parsed: ParsedPrice,
+    update_data: Option<&Arc<UpdateData>>,
+) -> Option<PriceUpdate> {
+    // Hermes returns ids as bare lowercase hex; registry feed ids carry
+    // a 0x prefix. Normalize before matching.
+    let feed_id = normalize_feed_id(&parsed.id)?;
+    let asset = assets.by_feed_id(&feed_id)?;
+    let expo = parsed.price.expo;
+    let raw_price: i64 = parsed.price.price.parse().ok()?;
+    let raw_conf: u64 = parsed.price.conf.parse().ok()?;
+    let fixed = FixedPrice::new(raw_price, expo);
+    let confidence = FixedPrice::new(i64::try_from(raw_conf).ok()?, expo);
+    // A malformed EMA only costs the EMA, not the spot price.
+    let ema = parsed.ema_price.to_f64_pair();
+    let metadata = parsed.metadata.unwrap_or_default();
+
+    Some(PriceUpdate {
+        symbol: asset.symbol.clone(),
+        price: fixed.to_f64(),
+        confidence: confidence.to_f64(),
+        publish_time: parsed.price.publish_time,
+        feed_id,
+        fixed_price: Some(fixed),
+        ema_price: ema.map(|(price, _)| price),
+        ema_confidence: ema.map(|(_, confidence)| confidence),
+        backfilled: false,
+        sources: Vec::new(),
+        slot: metadata.slot,
+        prev_publish_time: metadata.prev_publish_time,
+        update_data: update_data.cloned(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_asset_feed_ids() {
-        assert!(Asset::Sol.feed_id().starts_with("0x"));
-        assert!(Asset::Btc.feed_id().starts_with("0x"));
-        assert!(Asset::Eth.feed_id().starts_with("0x"));
-    }
+    const SOL_FEED: &str = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
 
-    #[test]
-    fn test_asset_from_feed_id() {
-        assert_eq!(Asset::from_feed_id(Asset::Sol.feed_id()), Some(Asset::Sol));
-        assert_eq!(Asset::from_feed_id(Asset::Btc.feed_id()), Some(Asset::Btc));
-        assert_eq!(Asset::from_feed_id(Asset::Eth.feed_id()), Some(Asset::Eth));
-        assert_eq!(Asset::from_feed_id("unknown"), None);
+    fn sol_registry() -> AssetRegistry {
+        AssetRegistry::from_json(&format!(
+            r#"{{"assets": [{{"symbol": "SOL", "feed_id": "{SOL_FEED}", "decimals": 8, "tick_size": 0.0001}}]}}"#
+        ))
+        .unwrap()
     }
 
     #[test]
@@ -344,13 +839,11 @@ mod tests {
         // Regression: the split dropped the feed-id normalization in
         // parse_price_update, causing every Hermes price update to be
         // silently dropped because Hermes returns bare-hex ids while
-        // Asset::feed_id constants carry the 0x prefix.
-        let (tx, _rx) = mpsc::channel(1);
-        let client = PythClient::new(tx, vec![Asset::Sol]);
-        let update = client
-            .parse_price_update(ParsedPrice {
-                id: "ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d"
-                    .to_string(),
+        // configured feed ids carry the 0x prefix.
+        let update = parse_price_update(
+            &sol_registry(),
+            ParsedPrice {
+                id: SOL_FEED.trim_start_matches("0x").to_string(),
                 price: PriceData {
                     price: "12345".to_string(),
                     conf: "67".to_string(),
@@ -363,57 +856,393 @@ mod tests {
                     expo: 0,
                     publish_time: 42,
                 },
-            })
-            .expect("bare-hex id from Hermes must resolve to an asset");
+                metadata: None,
+            },
+            None,
+        )
+        .expect("bare-hex id from Hermes must resolve to an asset");
 
         assert_eq!(update.symbol, "SOL");
         assert!(update.feed_id.starts_with("0x"));
     }
 
-    #[test]
-    fn freshness_state_marks_publish_time_as_unchanged() {
-        let mut state = AssetFreshnessState::default();
+    /// A Hermes response with one SOL update, priced `100 + publish_time`
+    /// cents and following one published a second earlier, with signed
+    /// update data [`sol_update_data`].
+    fn sol_update_json(publish_time: i64) -> String {
+        format!(
+            r#"{{"binary":{{"encoding":"hex","data":["{}"]}},"parsed":[{{"id":"{}","price":{{"price":"{}","conf":"5","expo":-2,"publish_time":{publish_time}}},"ema_price":{{"price":"10000","conf":"5","expo":-2,"publish_time":{publish_time}}},"metadata":{{"slot":{},"proof_available_time":{publish_time},"prev_publish_time":{}}}}}]}}"#,
+            sol_update_data(publish_time),
+            &SOL_FEED[2..],
+            10_000 + publish_time,
+            publish_time * 3,
+            publish_time - 1
+        )
+    }
 
-        let first = state.observe(100, 101);
-        let second = state.observe(100, 102);
+    /// Serve one Hermes SSE stream sending a SOL update for each of
+    /// `publish_times`, then hold the connection open. Returns the base URL.
+    async fn mock_sse(publish_times: &[i64]) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-        assert!(first.publish_advanced);
-        assert_eq!(first.unchanged_streak, 0);
-        assert_eq!(first.publish_gap_secs, None);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let mut body = String::new();
+        for &publish_time in publish_times {
+            body.push_str(&format!("data: {}\n\n", sol_update_json(publish_time)));
+        }
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let _ = stream.read(&mut request).await.unwrap();
+            let response =
+                format!("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n{body}");
+            stream.write_all(response.as_bytes()).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+        url
+    }
 
-        assert!(!second.publish_advanced);
-        assert_eq!(second.unchanged_streak, 1);
-        assert_eq!(second.publish_gap_secs, Some(0));
+    /// Stand-in for the signed update published at `publish_time`: the
+    /// accumulator magic followed by the time.
+    fn sol_update_data(publish_time: i64) -> String {
+        format!("504e4155{publish_time:016x}")
     }
 
-    #[test]
-    fn freshness_state_resets_streak_when_publish_time_advances() {
-        let mut state = AssetFreshnessState::default();
+    /// A base URL nothing listens on.
+    async fn dead_endpoint() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    async fn next_event(rx: &mut mpsc::Receiver<OracleEvent>) -> OracleEvent {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("no event within 5s")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn failover_moves_past_a_dead_endpoint() {
+        let endpoints = vec![dead_endpoint().await, mock_sse(&[100]).await];
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut client = PythClient::new(tx, sol_registry()).with_endpoints(endpoints);
+        let health = client.endpoint_health();
+        tokio::spawn(async move { client.run().await });
+
+        assert!(matches!(
+            next_event(&mut rx).await,
+            OracleEvent::Error { .. }
+        ));
+        assert!(matches!(next_event(&mut rx).await, OracleEvent::Connected));
+        match next_event(&mut rx).await {
+            OracleEvent::Price(update) => assert_eq!(update.publish_time, 100),
+            other => panic!("expected a price, got {other:?}"),
+        }
 
-        state.observe(100, 101);
-        state.observe(100, 102);
-        let third = state.observe(101, 103);
+        let health = health.snapshot();
+        assert_eq!(health[0].failures, 1);
+        assert!(health[0].last_error.is_some());
+        assert!(!health[0].connected);
+        assert!(health[1].connected);
+        assert_eq!((health[1].updates, health[1].forwarded), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn redundant_streams_forward_each_publish_time_once() {
+        let endpoints = vec![
+            mock_sse(&[100, 101]).await,
+            mock_sse(&[100, 101, 102]).await,
+        ];
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut client = PythClient::new(tx, sol_registry())
+            .with_endpoints(endpoints)
+            .with_stream_mode(StreamMode::Redundant);
+        let health = client.endpoint_health();
+        tokio::spawn(async move { client.run().await });
 
-        assert!(third.publish_advanced);
-        assert_eq!(third.unchanged_streak, 0);
-        assert_eq!(third.publish_gap_secs, Some(1));
+        let mut connected = 0;
+        let mut publish_times = Vec::new();
+        while publish_times.len() < 3 {
+            match next_event(&mut rx).await {
+                OracleEvent::Connected => connected += 1,
+                OracleEvent::Price(update) => publish_times.push(update.publish_time),
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert_eq!(connected, 1);
+        assert_eq!(publish_times, [100, 101, 102]);
+
+        // Let the slower stream's copies arrive; none are forwarded.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while health.snapshot().iter().map(|h| h.updates).sum::<u64>() < 5 {
+            assert!(Instant::now() < deadline, "duplicates never arrived");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let health = health.snapshot();
+        assert!(health.iter().all(|h| h.connected && h.failures == 0));
+        assert_eq!(health.iter().map(|h| h.forwarded).sum::<u64>(), 3);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn counts_updates_the_stream_skipped() {
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut client = PythClient::new(tx, sol_registry())
+            .with_endpoints(vec![mock_sse(&[100, 101, 103]).await]);
+        let health = client.endpoint_health();
+        tokio::spawn(async move { client.run().await });
+
+        let mut publish_times = Vec::new();
+        while publish_times.len() < 3 {
+            match next_event(&mut rx).await {
+                OracleEvent::Connected => {}
+                OracleEvent::Price(update) => {
+                    assert_eq!(update.prev_publish_time, Some(update.publish_time - 1));
+                    let update_data = update.update_data.expect("signed update data");
+                    assert_eq!(update_data.data, [sol_update_data(update.publish_time)]);
+                    assert_eq!(update_data.id, update_data_id(&update_data.data));
+                    publish_times.push(update.publish_time);
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert_eq!(publish_times, [100, 101, 103]);
+        // Hermes reported 102 as the previous publish time of 103.
+        let health = health.snapshot();
+        assert_eq!(
+            health[0].missed_updates,
+            BTreeMap::from([("SOL".to_string(), 1)])
+        );
     }
 
     #[test]
-    fn reconnect_backoff_caps_at_max() {
-        assert_eq!(next_backoff_secs(5), 10);
-        assert_eq!(next_backoff_secs(10), 20);
-        assert_eq!(next_backoff_secs(40), 60);
-        assert_eq!(next_backoff_secs(60), 60);
+    fn accept_dedupes_only_redundant_repeats() {
+        let update = |publish_time| {
+            let response: HermesPriceResponse =
+                serde_json::from_str(&sol_update_json(publish_time)).unwrap();
+            parse_price_updates(&sol_registry(), response.parsed, response.binary).remove(0)
+        };
+        let (tx, _rx) = mpsc::channel(1);
+        let mut failover = PythClient::new(tx.clone(), sol_registry());
+        assert!(failover.accept(&update(10)));
+        assert!(failover.accept(&update(10)));
+        assert!(!failover.accept(&update(9)));
+
+        let mut redundant =
+            PythClient::new(tx, sol_registry()).with_stream_mode(StreamMode::Redundant);
+        assert!(redundant.accept(&update(10)));
+        assert!(!redundant.accept(&update(10)));
+        assert!(!redundant.accept(&update(9)));
+        assert!(redundant.accept(&update(11)));
+        assert_eq!(redundant.last_published["SOL"], 11);
+    }
+
+    #[test]
+    fn next_endpoint_skips_open_streams() {
+        assert_eq!(next_endpoint(0, 3, &[]), 1);
+        assert_eq!(next_endpoint(2, 3, &[]), 0);
+        assert_eq!(next_endpoint(0, 3, &[1]), 2);
+        assert_eq!(next_endpoint(1, 2, &[0]), 1);
+        assert_eq!(next_endpoint(0, 1, &[]), 0);
+        assert_eq!(
+            "redundant".parse::<StreamMode>().unwrap(),
+            StreamMode::Redundant
+        );
+        assert!("primary".parse::<StreamMode>().is_err());
+    }
+
+    /// Serve `GET /v2/updates/price/{t}` like Hermes for SOL, with no
+    /// update published at 1002. Returns the base URL and the requested
+    /// timestamps.
+    async fn mock_hermes() -> (String, std::sync::Arc<std::sync::Mutex<Vec<i64>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requested = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requested.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let n = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..n]).into_owned();
+                let at: i64 = request
+                    .strip_prefix("GET /v2/updates/price/")
+                    .and_then(|rest| rest.split('?').next())
+                    .and_then(|at| at.parse().ok())
+                    .unwrap();
+                log.lock().unwrap().push(at);
+                let publish_time = if at == 1002 { 1003 } else { at };
+                let body = sol_update_json(publish_time);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requested)
+    }
+
+    #[tokio::test]
+    async fn backfill_replays_missed_seconds_in_order() {
+        let (url, requested) = mock_hermes().await;
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut client = PythClient::with_url(tx, sol_registry(), &url);
+        let (stream_tx, _stream_rx) = mpsc::channel(1);
+        assert!(!client.spawn_backfill(1006, &stream_tx));
+
+        client.last_published.insert("SOL".to_string(), 1000);
+        let updates = fetch_backfill(&client.endpoints, &client.assets, 1001, 1006).await;
+        assert_eq!(client.send_backfill(1006, updates).await, 4);
+        let mut requested = requested.lock().unwrap().clone();
+        requested.sort_unstable();
+        assert_eq!(requested, [1001, 1002, 1003, 1004, 1005]);
+
+        let mut publish_times = Vec::new();
+        while let Ok(OracleEvent::Price(update)) = rx.try_recv() {
+            assert!(update.backfilled);
+            assert_eq!(update.ema_price, Some(100.0));
+            publish_times.push(update.publish_time);
+        }
+        assert_eq!(publish_times, [1001, 1003, 1004, 1005]);
+        assert_eq!(client.last_published["SOL"], 1005);
+
+        // Nothing new since the last backfill.
+        assert!(!client.spawn_backfill(1006, &stream_tx));
+    }
+
+    /// Serve a Hermes SSE stream of SOL updates at `live`, and the
+    /// timestamped endpoint after `delay` per request.
+    async fn mock_hermes_with_stream(live: &[i64], delay: Duration) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let mut stream_body = String::new();
+        for &publish_time in live {
+            stream_body.push_str(&format!("data: {}\n\n", sol_update_json(publish_time)));
+        }
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let stream_body = stream_body.clone();
+                tokio::spawn(async move {
+                    let mut request = vec![0; 4096];
+                    let n = stream.read(&mut request).await.unwrap();
+                    let request = String::from_utf8_lossy(&request[..n]).into_owned();
+                    if request.starts_with("GET /v2/updates/price/stream") {
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n{stream_body}"
+                        );
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                        std::future::pending::<()>().await;
+                    }
+                    let at: i64 = request
+                        .strip_prefix("GET /v2/updates/price/")
+                        .and_then(|rest| rest.split('?').next())
+                        .and_then(|at| at.parse().ok())
+                        .unwrap();
+                    tokio::time::sleep(delay).await;
+                    let body = sol_update_json(at);
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn live_stream_keeps_flowing_during_backfill() {
+        let now = unix_now_secs();
+        let url = mock_hermes_with_stream(&[now + 5, now + 6], Duration::from_millis(500)).await;
+        let (tx, mut rx) = mpsc::channel(64);
+        let mut client = PythClient::with_url(tx, sol_registry(), &url);
+        client.last_published.insert("SOL".to_string(), now - 4);
+        let health = client.endpoint_health();
+        tokio::spawn(async move { client.run().await });
+
+        // The live updates are read while the missed seconds are fetched...
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while health.snapshot()[0].updates < 2 {
+            assert!(Instant::now() < deadline, "live updates never arrived");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(rx.try_recv().is_err());
+
+        // ...and still reach consumers after the backfill, in order.
+        let mut backfilled = Vec::new();
+        let mut live = Vec::new();
+        while live.len() < 2 {
+            match next_event(&mut rx).await {
+                OracleEvent::Price(update) if update.backfilled => {
+                    assert!(live.is_empty());
+                    backfilled.push(update.publish_time);
+                }
+                OracleEvent::Price(update) => live.push(update.publish_time),
+                OracleEvent::Connected => assert!(live.is_empty()),
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert_eq!(backfilled[..3], [now - 3, now - 2, now - 1]);
+        assert_eq!(live, [now + 5, now + 6]);
+        assert_eq!(health.snapshot()[0].forwarded, 2);
+    }
+
+    #[tokio::test]
+    async fn reconnect_behind_an_aggregator_leaves_the_window_alone() {
+        use crate::aggregator::{AggregationRule, PriceAggregator};
+        use crate::twap_calculator::TwapCalculator;
+
+        let now = unix_now_secs();
+        let url = mock_hermes_with_stream(&[now + 1], Duration::ZERO).await;
+        let (tx, mut rx) = mpsc::channel(64);
+        let mut client = PythClient::with_url(tx, sol_registry(), &url).with_backfill(false);
+        // Hermes last published before the outage; Lazer kept going.
+        client.last_published.insert("SOL".to_string(), now - 5);
+        let mut aggregator = PriceAggregator::new(AggregationRule::Median);
+        let mut calc = TwapCalculator::new();
+        for publish_time in now - 4..=now {
+            let lazer = PriceUpdate {
+                symbol: "SOL".to_string(),
+                price: 100.0,
+                publish_time,
+                ..Default::default()
+            };
+            for event in aggregator.ingest("lazer", OracleEvent::Price(lazer)) {
+                if let OracleEvent::Price(update) = event {
+                    assert_eq!(calc.admit(&update, publish_time), Ok(true));
+                }
+            }
+        }
+        tokio::spawn(async move { client.run().await });
+
+        assert!(matches!(next_event(&mut rx).await, OracleEvent::Connected));
+        let OracleEvent::Price(hermes) = next_event(&mut rx).await else {
+            panic!("expected the live Hermes price");
+        };
+        assert!(!hermes.backfilled);
+        for event in aggregator.ingest("hermes", OracleEvent::Price(hermes)) {
+            if let OracleEvent::Price(update) = event {
+                assert_eq!(calc.admit(&update, now + 1), Ok(true));
+            }
+        }
+        assert!(calc.rejection_counts().is_empty());
+        assert_eq!(calc.sample_count("SOL"), 6);
     }
 
     #[test]
     fn parse_update_scales_price() {
-        let (tx, _rx) = mpsc::channel(1);
-        let client = PythClient::new(tx, vec![Asset::Sol]);
-        let update = client
-            .parse_price_update(ParsedPrice {
-                id: Asset::Sol.feed_id().to_string(),
+        let update = parse_price_update(
+            &sol_registry(),
+            ParsedPrice {
+                id: SOL_FEED.to_string(),
                 price: PriceData {
                     price: "12345".to_string(),
                     conf: "67".to_string(),
@@ -421,16 +1250,57 @@ mod tests {
                     publish_time: 42,
                 },
                 ema_price: PriceData {
-                    price: "0".to_string(),
-                    conf: "0".to_string(),
-                    expo: 0,
+                    price: "12300".to_string(),
+                    conf: "50".to_string(),
+                    expo: -2,
                     publish_time: 42,
                 },
-            })
-            .unwrap();
+                metadata: Some(PriceMetadata {
+                    slot: Some(1234),
+                    prev_publish_time: Some(41),
+                }),
+            },
+            None,
+        )
+        .unwrap();
 
         assert_eq!(update.symbol, "SOL");
         assert!((update.price - 123.45).abs() < f64::EPSILON);
         assert!((update.confidence - 0.67).abs() < f64::EPSILON);
+        assert_eq!(update.fixed_price, Some(FixedPrice::new(12345, -2)));
+        assert_eq!(update.ema_price, Some(123.0));
+        assert_eq!(update.ema_confidence, Some(0.5));
+        assert_eq!(update.slot, Some(1234));
+        assert_eq!(update.prev_publish_time, Some(41));
+    }
+
+    #[test]
+    fn feeds_in_one_response_share_its_update_data() {
+        const ETH_FEED: &str = "0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace";
+        let assets = AssetRegistry::from_json(&format!(
+            r#"{{"assets": [{{"symbol": "SOL", "feed_id": "{SOL_FEED}", "decimals": 8, "tick_size": 0.0001}}, {{"symbol": "ETH", "feed_id": "{ETH_FEED}", "decimals": 8, "tick_size": 0.01}}]}}"#
+        ))
+        .unwrap();
+        let parsed = |feed: &str| {
+            format!(
+                r#"{{"id":"{}","price":{{"price":"10000","conf":"5","expo":-2,"publish_time":42}},"ema_price":{{"price":"10000","conf":"5","expo":-2,"publish_time":42}}}}"#,
+                &feed[2..]
+            )
+        };
+        let response: HermesPriceResponse = serde_json::from_str(&format!(
+            r#"{{"binary":{{"encoding":"hex","data":["{}"]}},"parsed":[{},{}]}}"#,
+            sol_update_data(42),
+            parsed(SOL_FEED),
+            parsed(ETH_FEED)
+        ))
+        .unwrap();
+
+        let updates = parse_price_updates(&assets, response.parsed, response.binary);
+        assert_eq!(updates.len(), 2);
+        let first = updates[0].update_data.as_ref().unwrap();
+        let second = updates[1].update_data.as_ref().unwrap();
+        assert!(Arc::ptr_eq(first, second));
+        assert_eq!(Arc::strong_count(first), 2);
+        assert_eq!(first.data, [sol_update_data(42)]);
     }
 }
@@ -0,0 +1,413 @@
+//! Expiry-driven settlement scheduler.
+//!
+//! Holds a calendar of expiries per asset and, at each one, calls
+//! [`TwapCalculator::calculate`] with `window_end` set to the expiry and
+//! emits the result as [`OracleEvent::Settlement`]. Downstream services then
+//! consume one authoritative settlement value instead of each calculating
+//! their own at (roughly) the right second. With a [`ProofStore`] attached,
+//! each settlement's [`SettlementProof`] is written before the event goes out.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::Weekday;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, RwLock};
+use tracing::{error, info};
+
+use crate::proof::{ProofStore, SettlementProof};
+use crate::twap_calculator::TwapCalculator;
+use crate::types::OracleEvent;
+use joyride_oracle_wire::TwapResult;
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Default delay between an expiry and its calculation. Samples published
+/// at the expiry second reach the oracle slightly after it; waiting lets
+/// them land in the window without moving `window_end`.
+pub const DEFAULT_SETTLEMENT_GRACE: Duration = Duration::from_secs(2);
+
+/// Expiry an asset settles on when its configuration names none.
+pub const DEFAULT_EXPIRY: ExpirySchedule = ExpirySchedule::Daily { hour: 8, minute: 0 };
+
+/// A recurring expiry time, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExpirySchedule {
+    /// Every day at `hour:minute` UTC.
+    Daily { hour: u32, minute: u32 },
+
+    /// Every week on `weekday` at `hour:minute` UTC.
+    Weekly {
+        weekday: Weekday,
+        hour: u32,
+        minute: u32,
+    },
+}
+
+impl ExpirySchedule {
+    pub fn daily(hour: u32, minute: u32) -> Self {
+        assert_time_of_day(hour, minute);
+        ExpirySchedule::Daily { hour, minute }
+    }
+
+    pub fn weekly(weekday: Weekday, hour: u32, minute: u32) -> Self {
+        assert_time_of_day(hour, minute);
+        ExpirySchedule::Weekly {
+            weekday,
+            hour,
+            minute,
+        }
+    }
+
+    /// Check the time of day of a schedule read from configuration, which
+    /// [`Self::daily`] and [`Self::weekly`] assert instead.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let (ExpirySchedule::Daily { hour, minute } | ExpirySchedule::Weekly { hour, minute, .. }) =
+            *self;
+        anyhow::ensure!(
+            hour < 24 && minute < 60,
+            "invalid expiry time {hour:02}:{minute:02}"
+        );
+        Ok(())
+    }
+
+    /// First expiry strictly after `after` (Unix timestamp in seconds).
+    pub fn next_after(&self, after: i64) -> i64 {
+        let day = after.div_euclid(SECS_PER_DAY);
+        match *self {
+            ExpirySchedule::Daily { hour, minute } => {
+                let candidate = day * SECS_PER_DAY + time_of_day_secs(hour, minute);
+                if candidate > after {
+                    candidate
+                } else {
+                    candidate + SECS_PER_DAY
+                }
+            }
+            ExpirySchedule::Weekly {
+                weekday,
+                hour,
+                minute,
+            } => {
+                // 1970-01-01 was a Thursday, three days after a Monday.
+                let today = (day + 3).rem_euclid(7);
+                let days_ahead = (i64::from(weekday.num_days_from_monday()) - today).rem_euclid(7);
+                let candidate = (day + days_ahead) * SECS_PER_DAY + time_of_day_secs(hour, minute);
+                if candidate > after {
+                    candidate
+                } else {
+                    candidate + 7 * SECS_PER_DAY
+                }
+            }
+        }
+    }
+}
+
+fn assert_time_of_day(hour: u32, minute: u32) {
+    assert!(
+        hour < 24 && minute < 60,
+        "invalid expiry time {hour:02}:{minute:02}"
+    );
+}
+
+fn time_of_day_secs(hour: u32, minute: u32) -> i64 {
+    i64::from(hour) * 3600 + i64::from(minute) * 60
+}
+
+/// Expiry schedules per asset symbol.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SettlementCalendar {
+    entries: Vec<(String, ExpirySchedule)>,
+}
+
+impl SettlementCalendar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Settle `symbol` on `schedule`, in addition to any schedules it
+    /// already has.
+    pub fn add(&mut self, symbol: impl Into<String>, schedule: ExpirySchedule) -> &mut Self {
+        self.entries.push((symbol.into(), schedule));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Earliest expiry strictly after `after`, with every symbol that settles
+    /// at that instant. Overlapping schedules (a daily and a weekly expiry at
+    /// the same time) settle a symbol once.
+    pub fn next_expiry(&self, after: i64) -> Option<(i64, Vec<String>)> {
+        let expiry = self
+            .entries
+            .iter()
+            .map(|(_, schedule)| schedule.next_after(after))
+            .min()?;
+        let symbols: BTreeSet<&str> = self
+            .entries
+            .iter()
+            .filter(|(_, schedule)| schedule.next_after(after) == expiry)
+            .map(|(symbol, _)| symbol.as_str())
+            .collect();
+        Some((expiry, symbols.into_iter().map(str::to_string).collect()))
+    }
+}
+
+/// Calls [`TwapCalculator::calculate`] at each expiry in a
+/// [`SettlementCalendar`] and emits [`OracleEvent::Settlement`].
+pub struct SettlementScheduler {
+    calendar: SettlementCalendar,
+    twap: Arc<RwLock<TwapCalculator>>,
+    event_tx: mpsc::Sender<OracleEvent>,
+    grace: Duration,
+    proofs: Option<ProofStore>,
+}
+
+impl SettlementScheduler {
+    pub fn new(
+        calendar: SettlementCalendar,
+        twap: Arc<RwLock<TwapCalculator>>,
+        event_tx: mpsc::Sender<OracleEvent>,
+    ) -> Self {
+        Self {
+            calendar,
+            twap,
+            event_tx,
+            grace: DEFAULT_SETTLEMENT_GRACE,
+            proofs: None,
+        }
+    }
+
+    /// Override [`DEFAULT_SETTLEMENT_GRACE`].
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Write a [`SettlementProof`] for every settlement to `proofs`.
+    pub fn with_proof_store(mut self, proofs: ProofStore) -> Self {
+        self.proofs = Some(proofs);
+        self
+    }
+
+    /// Sleep until each expiry (plus grace) and settle every asset due at
+    /// it. Runs until the event channel closes.
+    pub async fn run(&mut self) -> anyhow::Result<()> {
+        let mut cursor = unix_now_millis().div_euclid(1000);
+
+        while let Some((expiry, symbols)) = self.calendar.next_expiry(cursor) {
+            info!(
+                expiry,
+                symbols = %symbols.join(","),
+                "Next settlement scheduled"
+            );
+            let due_millis = expiry
+                .saturating_mul(1000)
+                .saturating_add(self.grace.as_millis() as i64);
+            let wait_millis = due_millis.saturating_sub(unix_now_millis()).max(0);
+            tokio::time::sleep(Duration::from_millis(wait_millis as u64)).await;
+
+            for symbol in &symbols {
+                if self.settle(symbol, expiry).await.is_none() && self.event_tx.is_closed() {
+                    return Ok(());
+                }
+            }
+            cursor = expiry;
+        }
+
+        Ok(())
+    }
+
+    /// Calculate and emit the settlement for `symbol` with `window_end` at
+    /// `expiry`. Returns `None` (and logs) if the calculator has no value.
+    /// A proof that fails to write is logged; the settlement still goes out.
+    pub async fn settle(&self, symbol: &str, expiry: i64) -> Option<TwapResult> {
+        let (result, proof) = {
+            let twap = self.twap.read().await;
+            let result = twap.calculate(symbol, expiry);
+            let proof = match (&result, &self.proofs) {
+                (Some(result), Some(_)) => Some(SettlementProof::build(&twap, result)),
+                _ => None,
+            };
+            (result, proof)
+        };
+        let Some(result) = result else {
+            error!(
+                asset = %symbol,
+                expiry,
+                "settlement_failed: no TWAP available at expiry"
+            );
+            return None;
+        };
+
+        info!(
+            asset = %symbol,
+            expiry,
+            twap = result.twap,
+            sample_count = result.sample_count,
+            coverage = result.coverage,
+            "settlement_emitted"
+        );
+        if let (Some(proofs), Some(proof)) = (&self.proofs, proof) {
+            match proofs.save(&proof) {
+                Ok(path) => info!(
+                    asset = %symbol,
+                    expiry,
+                    merkle_root = %proof.merkle_root,
+                    path = %path.display(),
+                    "settlement_proof_written"
+                ),
+                Err(e) => {
+                    error!(asset = %symbol, expiry, "Failed to write settlement proof: {}", e)
+                }
+            }
+        }
+        if let Err(e) = self
+            .event_tx
+            .send(OracleEvent::Settlement(result.clone()))
+            .await
+        {
+            error!("Failed to send settlement: {}", e);
+            return None;
+        }
+        Some(result)
+    }
+}
+
+fn unix_now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use joyride_oracle_wire::PriceUpdate;
+
+    // 2026-04-24 00:00:00 UTC, a Friday.
+    const FRIDAY_MIDNIGHT: i64 = 1_776_988_800;
+
+    #[test]
+    fn daily_schedule_rolls_to_next_day_once_passed() {
+        let schedule = ExpirySchedule::daily(8, 0);
+        let eight_am = FRIDAY_MIDNIGHT + 8 * 3600;
+
+        assert_eq!(schedule.next_after(FRIDAY_MIDNIGHT), eight_am);
+        assert_eq!(schedule.next_after(eight_am - 1), eight_am);
+        assert_eq!(schedule.next_after(eight_am), eight_am + SECS_PER_DAY);
+    }
+
+    #[test]
+    fn weekly_schedule_lands_on_requested_weekday() {
+        let friday = ExpirySchedule::weekly(Weekday::Fri, 8, 0);
+        let monday = ExpirySchedule::weekly(Weekday::Mon, 0, 30);
+        let friday_eight_am = FRIDAY_MIDNIGHT + 8 * 3600;
+
+        assert_eq!(friday.next_after(FRIDAY_MIDNIGHT), friday_eight_am);
+        assert_eq!(
+            friday.next_after(friday_eight_am),
+            friday_eight_am + 7 * SECS_PER_DAY
+        );
+        assert_eq!(
+            monday.next_after(FRIDAY_MIDNIGHT),
+            FRIDAY_MIDNIGHT + 3 * SECS_PER_DAY + 30 * 60
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid expiry time")]
+    fn schedule_rejects_invalid_time_of_day() {
+        let _ = ExpirySchedule::daily(24, 0);
+    }
+
+    #[test]
+    fn calendar_groups_symbols_and_dedupes_overlapping_schedules() {
+        let mut calendar = SettlementCalendar::new();
+        calendar
+            .add("BTC", ExpirySchedule::daily(8, 0))
+            .add("BTC", ExpirySchedule::weekly(Weekday::Fri, 8, 0))
+            .add("ETH", ExpirySchedule::daily(8, 0))
+            .add("SOL", ExpirySchedule::daily(16, 0));
+
+        let (expiry, symbols) = calendar.next_expiry(FRIDAY_MIDNIGHT).unwrap();
+        assert_eq!(expiry, FRIDAY_MIDNIGHT + 8 * 3600);
+        assert_eq!(symbols, vec!["BTC".to_string(), "ETH".to_string()]);
+
+        let (expiry, symbols) = calendar.next_expiry(expiry).unwrap();
+        assert_eq!(expiry, FRIDAY_MIDNIGHT + 16 * 3600);
+        assert_eq!(symbols, vec!["SOL".to_string()]);
+
+        assert!(SettlementCalendar::new().next_expiry(0).is_none());
+    }
+
+    #[tokio::test]
+    async fn settle_emits_settlement_event_for_window_ending_at_expiry() {
+        let mut calc = TwapCalculator::with_window(10);
+        for i in 0..=10 {
+            calc.record(&PriceUpdate {
+                symbol: "SOL".to_string(),
+                price: 100.0 + i as f64,
+                confidence: 0.01,
+                publish_time: 1000 + i,
+                feed_id: "0x123".to_string(),
+                ..Default::default()
+            });
+        }
+        let (tx, mut rx) = mpsc::channel(4);
+        let scheduler =
+            SettlementScheduler::new(SettlementCalendar::new(), Arc::new(RwLock::new(calc)), tx);
+
+        let result = scheduler.settle("SOL", 1010).await.unwrap();
+        assert_eq!(result.window_end, 1010);
+        assert_eq!(result.window_start, 1000);
+
+        match rx.recv().await {
+            Some(OracleEvent::Settlement(emitted)) => {
+                assert_eq!(emitted.symbol, "SOL");
+                assert_eq!(emitted.window_end, 1010);
+                assert!((emitted.twap - result.twap).abs() < f64::EPSILON);
+            }
+            other => panic!("expected Settlement, got {other:?}"),
+        }
+
+        assert!(scheduler.settle("BTC", 1010).await.is_none());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn settle_writes_proof_bundle() {
+        let dir =
+            std::env::temp_dir().join(format!("joyride-settlement-proofs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let proofs = ProofStore::open(&dir).unwrap();
+
+        let mut calc = TwapCalculator::with_window(10);
+        for i in 0..=10 {
+            calc.record(&PriceUpdate {
+                symbol: "SOL".to_string(),
+                price: 100.0 + i as f64,
+                confidence: 0.01,
+                publish_time: 1000 + i,
+                feed_id: "0x123".to_string(),
+                ..Default::default()
+            });
+        }
+        let (tx, _rx) = mpsc::channel(4);
+        let scheduler =
+            SettlementScheduler::new(SettlementCalendar::new(), Arc::new(RwLock::new(calc)), tx)
+                .with_proof_store(proofs.clone());
+
+        let result = scheduler.settle("SOL", 1010).await.unwrap();
+        let proof = proofs.load("SOL", 1010).unwrap().unwrap();
+        assert_eq!(proof.result, result);
+        assert_eq!(proof.samples.len(), 11);
+        assert!(proof.verify_root());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,226 @@
+//! Price sources.
+//!
+//! A [`PriceSource`] is an upstream feed streaming [`OracleEvent`]s
+//! (`Connected`, `Price`, `Disconnected`, `Error`) into the channel it was
+//! built with. [`PythClient`] reads Pyth Hermes over SSE, [`LazerClient`]
+//! reads Pyth Lazer (Pyth Pro) over WebSocket and [`TickerClient`] reads a
+//! Coinbase-style exchange ticker; the service picks one or more by
+//! [`PriceSourceKind`], combining several with a
+//! [`PriceAggregator`](crate::PriceAggregator).
+//!
+//! Sources share one reconnect policy: a failed connection is retried after
+//! a [`Backoff`] that doubles up to a minute and starts over once a session
+//! has delivered.
+//!
+//! [`OracleEvent`]: crate::OracleEvent
+
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::bail;
+use futures_util::future::BoxFuture;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use crate::assets::AssetRegistry;
+use crate::lazer::LazerClient;
+use crate::pyth::PythClient;
+use crate::ticker::TickerClient;
+use crate::types::OracleEvent;
+
+const INITIAL_RECONNECT_BACKOFF_SECS: u64 = 5;
+const MAX_RECONNECT_BACKOFF_SECS: u64 = 60;
+
+/// An upstream feed of price updates.
+pub trait PriceSource: Send {
+    /// Short name for logs, e.g. `hermes`.
+    fn name(&self) -> &'static str;
+
+    /// The assets this source streams.
+    fn assets(&self) -> &AssetRegistry;
+
+    /// Stream prices, reconnecting on errors. Returns only when the source
+    /// is misconfigured.
+    fn run(&mut self) -> BoxFuture<'_, anyhow::Result<()>>;
+}
+
+/// Which [`PriceSource`] the service streams from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PriceSourceKind {
+    /// Pyth Hermes SSE, see [`PythClient`].
+    #[default]
+    Hermes,
+    /// Pyth Lazer WebSocket, see [`LazerClient`].
+    Lazer,
+    /// Coinbase Exchange ticker WebSocket, see [`TickerClient`].
+    Coinbase,
+}
+
+impl PriceSourceKind {
+    /// The name the source goes by in logs and aggregated updates.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hermes => "hermes",
+            Self::Lazer => "lazer",
+            Self::Coinbase => "coinbase",
+        }
+    }
+}
+
+impl FromStr for PriceSourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "hermes" => Ok(Self::Hermes),
+            "lazer" => Ok(Self::Lazer),
+            "coinbase" => Ok(Self::Coinbase),
+            other => {
+                bail!("unknown price source `{other}` (expected hermes, lazer or coinbase)")
+            }
+        }
+    }
+}
+
+impl PriceSource for PythClient {
+    fn name(&self) -> &'static str {
+        PriceSourceKind::Hermes.name()
+    }
+
+    fn assets(&self) -> &AssetRegistry {
+        PythClient::assets(self)
+    }
+
+    fn run(&mut self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(PythClient::run(self))
+    }
+}
+
+impl PriceSource for LazerClient {
+    fn name(&self) -> &'static str {
+        PriceSourceKind::Lazer.name()
+    }
+
+    fn assets(&self) -> &AssetRegistry {
+        LazerClient::assets(self)
+    }
+
+    fn run(&mut self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(LazerClient::run(self))
+    }
+}
+
+impl PriceSource for TickerClient {
+    fn name(&self) -> &'static str {
+        PriceSourceKind::Coinbase.name()
+    }
+
+    fn assets(&self) -> &AssetRegistry {
+        TickerClient::assets(self)
+    }
+
+    fn run(&mut self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(TickerClient::run(self))
+    }
+}
+
+/// Exponential reconnect delay.
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    secs: u64,
+}
+
+impl Backoff {
+    pub(crate) fn new() -> Self {
+        Self {
+            secs: INITIAL_RECONNECT_BACKOFF_SECS,
+        }
+    }
+
+    /// Start over from the initial delay.
+    pub(crate) fn reset(&mut self) {
+        self.secs = INITIAL_RECONNECT_BACKOFF_SECS;
+    }
+
+    /// The delay before the next attempt; each call doubles the following
+    /// one, up to the cap.
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let delay = self.secs;
+        self.secs = self.secs.saturating_mul(2).min(MAX_RECONNECT_BACKOFF_SECS);
+        Duration::from_secs(delay)
+    }
+}
+
+/// Wind up one session of a single-connection source and wait out the
+/// backoff before the next: reports a failed `result` as an
+/// [`OracleEvent::Error`], and a session that had `connected` as
+/// [`OracleEvent::Disconnected`], which also resets `backoff`.
+pub(crate) async fn reconnect_after(
+    label: &str,
+    event_tx: &mpsc::Sender<OracleEvent>,
+    backoff: &mut Backoff,
+    result: anyhow::Result<()>,
+    connected: bool,
+) {
+    let reconnect_reason = match result {
+        Ok(()) => {
+            info!("{label} connection closed gracefully");
+            "stream_closed".to_string()
+        }
+        Err(e) => {
+            error!("{label} connection error: {:#}", e);
+            let _ = event_tx
+                .send(OracleEvent::Error {
+                    message: format!("{e:#}"),
+                })
+                .await;
+            e.to_string()
+        }
+    };
+
+    if connected {
+        backoff.reset();
+        let _ = event_tx.send(OracleEvent::Disconnected).await;
+    }
+    let delay = backoff.next_delay();
+    info!(
+        backoff_secs = delay.as_secs(),
+        reconnect_reason = %reconnect_reason,
+        "Reconnecting to {label} after backoff"
+    );
+    tokio::time::sleep(delay).await;
+}
+
+/// The wall clock as a Unix timestamp in seconds.
+pub(crate) fn unix_now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_backoff_caps_at_max() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<u64> = (0..6).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, [5, 10, 20, 40, 60, 60]);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn parses_source_kinds() {
+        for kind in [
+            PriceSourceKind::Hermes,
+            PriceSourceKind::Lazer,
+            PriceSourceKind::Coinbase,
+        ] {
+            assert_eq!(kind.name().parse::<PriceSourceKind>().unwrap(), kind);
+        }
+        assert!("binance".parse::<PriceSourceKind>().is_err());
+    }
+}
@@ -0,0 +1,524 @@
+//! Spike and outlier filter.
+//!
+//! A [`SpikeFilter`] sits ahead of the TWAP calculator and holds back any
+//! print that moves further from the asset's last accepted price than its
+//! [`SpikePolicy`] band allows: a fixed relative move per second, a multiple
+//! of the standard deviation of recent returns, or whichever of the two is
+//! wider. A print far from its own Pyth EMA price is held back too.
+//!
+//! Held prints are released, in order, once enough following prints confirm
+//! the move: each on the same side of the old price as the quarantined print
+//! and within the band of the quarantined print itself. They are dropped as
+//! soon as a print comes back within the band of the old price, or breaches
+//! it in a way that does not confirm the move, which opens a new episode.
+//! Both ends of an episode are reported as [`OracleEvent::PriceQuarantine`]
+//! events for review. Backfilled prints are checked against the same band
+//! but only ever pass or get discarded on their own.
+
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::types::OracleEvent;
+use joyride_oracle_wire::{PriceQuarantine, PriceUpdate, QuarantineStatus};
+
+/// Default number of following prints that confirm a move.
+pub const DEFAULT_SPIKE_CONFIRMATIONS: u32 = 2;
+/// Default number of recent returns the sigma band is measured over.
+pub const DEFAULT_SPIKE_HISTORY: usize = 60;
+/// Returns needed before the sigma band applies.
+const MIN_SIGMA_HISTORY: usize = 10;
+
+/// How far one asset's price may move before a print is quarantined.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpikePolicy {
+    /// Largest relative move per second of publish time, e.g. `0.02` for
+    /// 2% a second.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_move_per_sec: Option<f64>,
+    /// Largest move in standard deviations of the recent returns, scaled
+    /// by the square root of the seconds since the last accepted price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_sigma: Option<f64>,
+    /// Largest relative distance from the print's own EMA price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ema_deviation: Option<f64>,
+    /// Following prints that must confirm a move before it is released.
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u32,
+    /// Recent returns the sigma band is measured over.
+    #[serde(default = "default_history")]
+    pub history: usize,
+}
+
+fn default_confirmations() -> u32 {
+    DEFAULT_SPIKE_CONFIRMATIONS
+}
+
+fn default_history() -> usize {
+    DEFAULT_SPIKE_HISTORY
+}
+
+impl SpikePolicy {
+    /// A policy quarantining moves beyond `max_move_per_sec`.
+    pub fn max_move_per_sec(max_move_per_sec: f64) -> Self {
+        Self {
+            max_move_per_sec: Some(max_move_per_sec),
+            max_sigma: None,
+            max_ema_deviation: None,
+            confirmations: DEFAULT_SPIKE_CONFIRMATIONS,
+            history: DEFAULT_SPIKE_HISTORY,
+        }
+    }
+
+    /// Check the policy has a band and that every limit is positive.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.max_move_per_sec.is_some()
+                || self.max_sigma.is_some()
+                || self.max_ema_deviation.is_some(),
+            "spike policy needs max_move_per_sec, max_sigma or max_ema_deviation"
+        );
+        for (name, limit) in [
+            ("max_move_per_sec", self.max_move_per_sec),
+            ("max_sigma", self.max_sigma),
+            ("max_ema_deviation", self.max_ema_deviation),
+        ] {
+            if let Some(limit) = limit {
+                ensure!(
+                    limit.is_finite() && limit > 0.0,
+                    "{name} must be positive (got {limit})"
+                );
+            }
+        }
+        ensure!(self.confirmations > 0, "confirmations must be positive");
+        ensure!(
+            self.max_sigma.is_none() || self.history >= MIN_SIGMA_HISTORY,
+            "history must be at least {MIN_SIGMA_HISTORY} for max_sigma (got {})",
+            self.history
+        );
+        Ok(())
+    }
+}
+
+/// Why a print fell outside the band.
+struct Breach {
+    deviation: f64,
+    band: Option<f64>,
+    ema_deviation: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct AssetSpikes {
+    /// Last accepted price and its publish time.
+    reference: Option<(f64, i64)>,
+    /// Recent relative returns, each divided by the square root of the
+    /// seconds it spanned.
+    returns: VecDeque<f64>,
+    held: Vec<PriceUpdate>,
+    episode: Option<PriceQuarantine>,
+}
+
+impl AssetSpikes {
+    fn band(&self, policy: &SpikePolicy, secs: f64) -> Option<f64> {
+        let by_move = policy.max_move_per_sec.map(|max| max * secs);
+        let by_sigma = policy
+            .max_sigma
+            .filter(|_| self.returns.len() >= MIN_SIGMA_HISTORY)
+            .map(|sigmas| sigmas * std_dev(&self.returns) * secs.sqrt());
+        match (by_move, by_sigma) {
+            (Some(by_move), Some(by_sigma)) => Some(by_move.max(by_sigma)),
+            (band, None) | (None, band) => band,
+        }
+    }
+
+    fn breach(&self, policy: &SpikePolicy, update: &PriceUpdate) -> Option<Breach> {
+        let (reference, reference_time) = self.reference?;
+        let secs = (update.publish_time - reference_time).abs().max(1) as f64;
+        let deviation = (update.price - reference).abs() / reference;
+        let band = self.band(policy, secs);
+        let ema_deviation = policy.max_ema_deviation.and_then(|max| {
+            let ema = update.ema_price.filter(|ema| *ema > 0.0)?;
+            let ema_deviation = (update.price - ema).abs() / ema;
+            (ema_deviation > max).then_some(ema_deviation)
+        });
+        (band.is_some_and(|band| deviation > band) || ema_deviation.is_some()).then_some(Breach {
+            deviation,
+            band,
+            ema_deviation,
+        })
+    }
+
+    /// Whether `update` confirms `episode`'s move: on the same side of the
+    /// reference as the quarantined print and within the band of that print.
+    fn confirms(
+        &self,
+        policy: &SpikePolicy,
+        episode: &PriceQuarantine,
+        update: &PriceUpdate,
+    ) -> bool {
+        let same_side = (update.price - episode.reference_price).signum()
+            == (episode.price - episode.reference_price).signum();
+        let secs = (update.publish_time - episode.publish_time).max(1) as f64;
+        let deviation = (update.price - episode.price).abs() / episode.price;
+        same_side && self.band(policy, secs).is_none_or(|band| deviation <= band)
+    }
+
+    /// Report of `update` breaching the band, as the first print of an
+    /// episode.
+    fn report(&self, breach: Breach, update: &PriceUpdate) -> PriceQuarantine {
+        let (reference_price, reference_time) = self.reference.expect("a breach needs a reference");
+        PriceQuarantine {
+            symbol: update.symbol.clone(),
+            status: QuarantineStatus::Quarantined,
+            price: update.price,
+            publish_time: update.publish_time,
+            reference_price,
+            reference_time,
+            deviation: breach.deviation,
+            band: breach.band,
+            ema_deviation: breach.ema_deviation,
+            held: 1,
+        }
+    }
+
+    /// Hold `update` back as the start of a new episode.
+    fn quarantine(&mut self, breach: Breach, update: PriceUpdate) -> PriceQuarantine {
+        let episode = self.report(breach, &update);
+        self.held.push(update);
+        self.episode = Some(episode.clone());
+        episode
+    }
+
+    fn accept(&mut self, policy: &SpikePolicy, update: &PriceUpdate) {
+        if let Some((reference, reference_time)) = self.reference {
+            let secs = update.publish_time - reference_time;
+            if secs > 0 {
+                self.returns
+                    .push_back((update.price - reference) / reference / (secs as f64).sqrt());
+                while self.returns.len() > policy.history {
+                    self.returns.pop_front();
+                }
+            }
+        }
+        if update.price > 0.0 {
+            self.reference = Some((update.price, update.publish_time));
+        }
+    }
+}
+
+fn std_dev(values: &VecDeque<f64>) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt()
+}
+
+/// Quarantines price prints that move beyond their asset's band until the
+/// move is confirmed or reverts. Assets without a policy pass straight
+/// through.
+#[derive(Debug, Default)]
+pub struct SpikeFilter {
+    policies: HashMap<String, SpikePolicy>,
+    assets: HashMap<String, AssetSpikes>,
+}
+
+impl SpikeFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter `symbol` by `policy`.
+    ///
+    /// # Panics
+    ///
+    /// If the policy is invalid, see [`SpikePolicy::validate`].
+    pub fn with_symbol_policy(mut self, symbol: impl Into<String>, policy: SpikePolicy) -> Self {
+        if let Err(e) = policy.validate() {
+            panic!("invalid spike policy: {e}");
+        }
+        self.policies.insert(symbol.into(), policy);
+        self
+    }
+
+    /// The policy `symbol` is filtered by, if any.
+    pub fn policy(&self, symbol: &str) -> Option<&SpikePolicy> {
+        self.policies.get(symbol)
+    }
+
+    /// Whether any asset is filtered.
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// Pass `event` through, unless it is a price print to hold back.
+    /// Returns the events to forward, in order: quarantine reports, and
+    /// the prints released by this one.
+    pub fn ingest(&mut self, event: OracleEvent) -> Vec<OracleEvent> {
+        let update = match event {
+            OracleEvent::Price(update) => update,
+            event => return vec![event],
+        };
+        let Some(policy) = self.policies.get(&update.symbol).copied() else {
+            return vec![OracleEvent::Price(update)];
+        };
+        let asset = self.assets.entry(update.symbol.clone()).or_default();
+        let breach = asset.breach(&policy, &update);
+
+        if update.backfilled {
+            // Backfill is historical and the reference may already be newer,
+            // so it is checked against the reference without moving it or
+            // joining an episode: an outlier is dropped on its own.
+            return match breach {
+                None => vec![OracleEvent::Price(update)],
+                Some(breach) => vec![OracleEvent::PriceQuarantine(PriceQuarantine {
+                    status: QuarantineStatus::Discarded,
+                    ..asset.report(breach, &update)
+                })],
+            };
+        }
+
+        match (breach, asset.episode.take()) {
+            (None, None) => {
+                asset.accept(&policy, &update);
+                vec![OracleEvent::Price(update)]
+            }
+            (None, Some(mut episode)) => {
+                episode.status = QuarantineStatus::Discarded;
+                episode.held = std::mem::take(&mut asset.held).len();
+                asset.accept(&policy, &update);
+                vec![
+                    OracleEvent::PriceQuarantine(episode),
+                    OracleEvent::Price(update),
+                ]
+            }
+            (Some(breach), None) => {
+                vec![OracleEvent::PriceQuarantine(
+                    asset.quarantine(breach, update),
+                )]
+            }
+            (Some(breach), Some(mut episode)) if !asset.confirms(&policy, &episode, &update) => {
+                // A swing to the other side, or further out than the
+                // quarantined print, is a new outlier rather than a level.
+                episode.status = QuarantineStatus::Discarded;
+                episode.held = std::mem::take(&mut asset.held).len();
+                vec![
+                    OracleEvent::PriceQuarantine(episode),
+                    OracleEvent::PriceQuarantine(asset.quarantine(breach, update)),
+                ]
+            }
+            (Some(_), Some(mut episode)) => {
+                asset.held.push(update);
+                if asset.held.len() <= policy.confirmations as usize {
+                    asset.episode = Some(episode);
+                    return Vec::new();
+                }
+                episode.status = QuarantineStatus::Released;
+                episode.held = asset.held.len();
+                let mut events = vec![OracleEvent::PriceQuarantine(episode)];
+                for update in std::mem::take(&mut asset.held) {
+                    asset.accept(&policy, &update);
+                    events.push(OracleEvent::Price(update));
+                }
+                events
+            }
+        }
+    }
+
+    /// Forward `inputs` to `output` through the filter until either side
+    /// closes.
+    pub async fn run(
+        mut self,
+        mut inputs: mpsc::Receiver<OracleEvent>,
+        output: mpsc::Sender<OracleEvent>,
+    ) {
+        while let Some(event) = inputs.recv().await {
+            for event in self.ingest(event) {
+                if output.send(event).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(price: f64, publish_time: i64) -> OracleEvent {
+        OracleEvent::Price(PriceUpdate {
+            symbol: "BTC".to_string(),
+            price,
+            confidence: 1.0,
+            publish_time,
+            feed_id: "0x123".to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn kinds(events: &[OracleEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                OracleEvent::Price(update) => format!("price {}", update.price),
+                OracleEvent::PriceQuarantine(q) => format!("{:?} {}", q.status, q.held),
+                other => format!("{other:?}"),
+            })
+            .collect()
+    }
+
+    fn filter() -> SpikeFilter {
+        SpikeFilter::new().with_symbol_policy("BTC", SpikePolicy::max_move_per_sec(0.02))
+    }
+
+    #[test]
+    fn discards_a_print_that_reverts() {
+        let mut filter = filter();
+        assert_eq!(kinds(&filter.ingest(price(100.0, 1))), ["price 100"]);
+        // 1.5% in a second is within the band, 30% is not.
+        assert_eq!(kinds(&filter.ingest(price(101.5, 2))), ["price 101.5"]);
+        let events = filter.ingest(price(130.0, 3));
+        assert_eq!(kinds(&events), ["Quarantined 1"]);
+        match &events[0] {
+            OracleEvent::PriceQuarantine(q) => {
+                assert_eq!(q.reference_price, 101.5);
+                assert_eq!(q.band, Some(0.02));
+                assert!((q.deviation - 28.5 / 101.5).abs() < 1e-12);
+            }
+            other => panic!("expected a quarantine, got {other:?}"),
+        }
+        assert_eq!(
+            kinds(&filter.ingest(price(101.0, 4))),
+            ["Discarded 1", "price 101"]
+        );
+        // Other assets and other events pass untouched.
+        let mut eth = price(5000.0, 4);
+        if let OracleEvent::Price(update) = &mut eth {
+            update.symbol = "ETH".to_string();
+        }
+        assert_eq!(kinds(&filter.ingest(eth)), ["price 5000"]);
+        assert!(matches!(
+            filter.ingest(OracleEvent::Connected)[..],
+            [OracleEvent::Connected]
+        ));
+    }
+
+    #[test]
+    fn releases_a_confirmed_move_in_order() {
+        let mut filter = filter();
+        filter.ingest(price(100.0, 1));
+        assert_eq!(kinds(&filter.ingest(price(90.0, 2))), ["Quarantined 1"]);
+        assert!(filter.ingest(price(89.5, 3)).is_empty());
+        assert_eq!(
+            kinds(&filter.ingest(price(89.0, 4))),
+            ["Released 3", "price 90", "price 89.5", "price 89"]
+        );
+        // The new level is the reference from now on.
+        assert_eq!(kinds(&filter.ingest(price(88.5, 5))), ["price 88.5"]);
+    }
+
+    #[test]
+    fn alternating_spikes_never_confirm() {
+        let mut filter = filter();
+        filter.ingest(price(100.0, 1));
+        assert_eq!(kinds(&filter.ingest(price(120.0, 2))), ["Quarantined 1"]);
+        for (t, p) in [(3, 80.0), (4, 120.0), (5, 80.0), (6, 120.0)] {
+            assert_eq!(
+                kinds(&filter.ingest(price(p, t))),
+                ["Discarded 1", "Quarantined 1"]
+            );
+        }
+        // Further out on the same side does not confirm either.
+        assert_eq!(
+            kinds(&filter.ingest(price(150.0, 7))),
+            ["Discarded 1", "Quarantined 1"]
+        );
+        assert_eq!(
+            kinds(&filter.ingest(price(100.5, 8))),
+            ["Discarded 1", "price 100.5"]
+        );
+    }
+
+    #[test]
+    fn checks_backfill_without_moving_the_reference() {
+        let mut filter = filter();
+        filter.ingest(price(100.0, 10));
+        let backfill = |p, t| match price(p, t) {
+            OracleEvent::Price(update) => OracleEvent::Price(PriceUpdate {
+                backfilled: true,
+                ..update
+            }),
+            _ => unreachable!(),
+        };
+        assert_eq!(kinds(&filter.ingest(backfill(101.0, 11))), ["price 101"]);
+        // An outlier is dropped at once, even one published before the
+        // reference.
+        assert_eq!(kinds(&filter.ingest(backfill(130.0, 12))), ["Discarded 1"]);
+        assert_eq!(kinds(&filter.ingest(backfill(90.0, 9))), ["Discarded 1"]);
+        // The live reference is still 100 at 10: 103 at 12 is within 4%.
+        assert_eq!(kinds(&filter.ingest(price(103.0, 12))), ["price 103"]);
+    }
+
+    #[test]
+    fn sigma_and_ema_bands() {
+        let mut filter = SpikeFilter::new().with_symbol_policy(
+            "BTC",
+            SpikePolicy {
+                max_sigma: Some(5.0),
+                max_ema_deviation: Some(0.05),
+                ..SpikePolicy::max_move_per_sec(0.015)
+            },
+        );
+        // Alternating 1% moves: once enough are in, the sigma band (5%) is
+        // wider than the fixed 1.5%.
+        for t in 0..12 {
+            let p = if t % 2 == 0 { 100.0 } else { 101.0 };
+            assert_eq!(filter.ingest(price(p, t)).len(), 1);
+        }
+        assert_eq!(kinds(&filter.ingest(price(103.0, 12))), ["price 103"]);
+        assert_eq!(kinds(&filter.ingest(price(112.0, 13))), ["Quarantined 1"]);
+
+        let mut far_from_ema = price(102.0, 14);
+        if let OracleEvent::Price(update) = &mut far_from_ema {
+            update.ema_price = Some(90.0);
+        }
+        // Below the old price while the held print is above it, so the
+        // episode is dropped and the print held back on its own.
+        let events = filter.ingest(far_from_ema);
+        assert_eq!(kinds(&events), ["Discarded 1", "Quarantined 1"]);
+        match &events[1] {
+            OracleEvent::PriceQuarantine(q) => {
+                assert!((q.ema_deviation.unwrap() - 12.0 / 90.0).abs() < 1e-12)
+            }
+            other => panic!("expected a quarantine, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn validates_policies() {
+        assert!(SpikePolicy::max_move_per_sec(0.02).validate().is_ok());
+        assert!(SpikePolicy {
+            max_move_per_sec: None,
+            ..SpikePolicy::max_move_per_sec(0.02)
+        }
+        .validate()
+        .is_err());
+        assert!(SpikePolicy::max_move_per_sec(-0.1).validate().is_err());
+        assert!(SpikePolicy {
+            confirmations: 0,
+            ..SpikePolicy::max_move_per_sec(0.02)
+        }
+        .validate()
+        .is_err());
+        assert!(SpikePolicy {
+            max_sigma: Some(4.0),
+            history: 5,
+            ..SpikePolicy::max_move_per_sec(0.02)
+        }
+        .validate()
+        .is_err());
+    }
+}
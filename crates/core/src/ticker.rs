@@ -0,0 +1,317 @@
+//! Exchange ticker WebSocket client.
+//!
+//! A secondary price source speaking the Coinbase Exchange public feed
+//! protocol: the client subscribes every asset's `ticker_product` (by
+//! default `{symbol}-USD`) on the `ticker` and `heartbeat` channels and
+//! forwards each ticker message as a [`PriceUpdate`]. The price is the last
+//! trade, exact as quoted; the confidence is half the best bid/ask spread.
+//! Heartbeats keep quiet products from tripping the idle timeout.
+
+use anyhow::{anyhow, bail, Context};
+use chrono::DateTime;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, info, warn};
+
+use crate::assets::AssetRegistry;
+use crate::source::{reconnect_after, Backoff};
+use crate::types::OracleEvent;
+use joyride_oracle_wire::{FixedPrice, PriceUpdate};
+
+/// Default exchange feed endpoint.
+pub const COINBASE_URL: &str = "wss://ws-feed.exchange.coinbase.com";
+const TICKER_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize)]
+struct SubscribeRequest {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    product_ids: Vec<String>,
+    channels: &'static [&'static str],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TickerMessage {
+    Subscriptions {},
+    Ticker(Ticker),
+    Error {
+        message: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ticker {
+    product_id: String,
+    price: String,
+    #[serde(default)]
+    best_bid: Option<String>,
+    #[serde(default)]
+    best_ask: Option<String>,
+    time: String,
+}
+
+/// Client for a Coinbase-style exchange ticker WebSocket.
+pub struct TickerClient {
+    event_tx: mpsc::Sender<OracleEvent>,
+    assets: AssetRegistry,
+    url: String,
+    /// Whether the current session has been subscribed, so `Disconnected`
+    /// only follows a `Connected`.
+    connected: bool,
+}
+
+impl TickerClient {
+    pub fn new(event_tx: mpsc::Sender<OracleEvent>, assets: AssetRegistry) -> Self {
+        Self {
+            event_tx,
+            assets,
+            url: COINBASE_URL.to_string(),
+            connected: false,
+        }
+    }
+
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    pub fn assets(&self) -> &AssetRegistry {
+        &self.assets
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn run(&mut self) -> anyhow::Result<()> {
+        let mut backoff = Backoff::new();
+
+        loop {
+            let result = self.connect_and_stream().await;
+            let connected = std::mem::take(&mut self.connected);
+            reconnect_after(
+                "exchange ticker",
+                &self.event_tx,
+                &mut backoff,
+                result,
+                connected,
+            )
+            .await;
+        }
+    }
+
+    fn subscribe_request(&self) -> SubscribeRequest {
+        SubscribeRequest {
+            kind: "subscribe",
+            product_ids: self.assets.iter().map(|a| a.ticker_product()).collect(),
+            channels: &["ticker", "heartbeat"],
+        }
+    }
+
+    async fn connect_and_stream(&mut self) -> anyhow::Result<()> {
+        info!("Connecting to exchange ticker: {}", self.url);
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(self.url.as_str())
+            .await
+            .context("connecting to exchange ticker")?;
+        let subscribe = serde_json::to_string(&self.subscribe_request())?;
+        socket.send(Message::Text(subscribe)).await?;
+
+        loop {
+            let message = match tokio::time::timeout(TICKER_IDLE_TIMEOUT, socket.next()).await {
+                Ok(Some(message)) => message?,
+                Ok(None) => {
+                    info!("Exchange ticker stream ended");
+                    return Ok(());
+                }
+                Err(_) => {
+                    return Err(anyhow!(
+                        "exchange ticker idle for {}s",
+                        TICKER_IDLE_TIMEOUT.as_secs()
+                    ));
+                }
+            };
+
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(frame) => {
+                    info!(?frame, "Exchange ticker closed the connection");
+                    return Ok(());
+                }
+                // Pings are answered by tungstenite.
+                _ => continue,
+            };
+            match serde_json::from_str::<TickerMessage>(&text) {
+                Ok(TickerMessage::Subscriptions {}) => {
+                    info!("Subscribed to exchange ticker");
+                    self.connected = true;
+                    let _ = self.event_tx.send(OracleEvent::Connected).await;
+                }
+                Ok(TickerMessage::Error { message, reason }) => match reason {
+                    Some(reason) => bail!("exchange ticker error: {message}: {reason}"),
+                    None => bail!("exchange ticker error: {message}"),
+                },
+                Ok(TickerMessage::Ticker(ticker)) => {
+                    let Some(update) = self.parse_ticker(ticker) else {
+                        continue;
+                    };
+                    if let Err(e) = self.event_tx.send(OracleEvent::Price(update)).await {
+                        error!("Failed to send price update: {}", e);
+                    }
+                }
+                Ok(TickerMessage::Other) => {}
+                Err(e) => {
+                    warn!("Failed to parse exchange ticker message: {}", e);
+                }
+            }
+        }
+    }
+
+    /// The update for a ticker on one of our products, or `None` for other
+    /// products and malformed tickers.
+    fn parse_ticker(&self, ticker: Ticker) -> Option<PriceUpdate> {
+        let asset = self.assets.by_ticker_product(&ticker.product_id)?;
+        let Some(price) = parse_decimal(&ticker.price) else {
+            warn!(product = %ticker.product_id, price = %ticker.price, "Invalid ticker price");
+            return None;
+        };
+        let Ok(time) = DateTime::parse_from_rfc3339(&ticker.time) else {
+            warn!(product = %ticker.product_id, time = %ticker.time, "Invalid ticker time");
+            return None;
+        };
+        let quote = |side: Option<String>| side.and_then(|s| s.parse::<f64>().ok());
+        let confidence = match (quote(ticker.best_bid), quote(ticker.best_ask)) {
+            (Some(bid), Some(ask)) if ask >= bid => (ask - bid) / 2.0,
+            _ => 0.0,
+        };
+        Some(PriceUpdate {
+            symbol: asset.symbol.clone(),
+            price: price.to_f64(),
+            confidence,
+            publish_time: time.timestamp(),
+            feed_id: format!("coinbase:{}", ticker.product_id),
+            fixed_price: Some(price),
+            ..Default::default()
+        })
+    }
+}
+
+/// Parse a plain positive decimal such as `75123.45` exactly.
+fn parse_decimal(s: &str) -> Option<FixedPrice> {
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty() || !(whole.bytes().chain(fraction.bytes())).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mantissa = format!("{whole}{fraction}").parse().ok()?;
+    Some(FixedPrice::new(
+        mantissa,
+        -i32::try_from(fraction.len()).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::PriceSource;
+    use tokio::net::TcpListener;
+
+    /// Messages recorded from the exchange feed after subscribing to
+    /// BTC-USD and SOL-USD, plus an ETH-USD ticker we don't track.
+    const RECORDED: &str = include_str!("../fixtures/coinbase_ticker.jsonl");
+
+    fn registry() -> AssetRegistry {
+        AssetRegistry::from_json(
+            r#"{"assets": [
+                {"symbol": "BTC", "decimals": 8, "tick_size": 0.01},
+                {"symbol": "JITOSOL", "ticker_product": "SOL-USD", "decimals": 8, "tick_size": 0.0001}
+            ]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_tickers() {
+        let (tx, _rx) = mpsc::channel(1);
+        let client = TickerClient::new(tx, registry());
+        let updates: Vec<PriceUpdate> = RECORDED
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line).unwrap() {
+                TickerMessage::Ticker(ticker) => client.parse_ticker(ticker),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(updates.len(), 2);
+        let btc = &updates[0];
+        assert_eq!(btc.symbol, "BTC");
+        assert_eq!(btc.feed_id, "coinbase:BTC-USD");
+        assert_eq!(btc.publish_time, 1_730_986_152);
+        assert_eq!(btc.fixed_price, Some(FixedPrice::new(7_512_345, -2)));
+        assert!((btc.confidence - 0.01).abs() < 1e-6);
+        assert_eq!(updates[1].symbol, "JITOSOL");
+        assert_eq!(updates[1].fixed_price, Some(FixedPrice::new(2015, -1)));
+
+        assert_eq!(parse_decimal("60000"), Some(FixedPrice::new(60000, 0)));
+        assert_eq!(parse_decimal("-1.5"), None);
+        assert_eq!(parse_decimal(".5"), None);
+        assert_eq!(parse_decimal("1e5"), None);
+    }
+
+    #[tokio::test]
+    async fn streams_from_recorded_messages() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let Some(Ok(Message::Text(subscribe))) = socket.next().await else {
+                panic!("expected a subscribe request");
+            };
+            let subscribe: serde_json::Value = serde_json::from_str(&subscribe).unwrap();
+            assert_eq!(subscribe["type"], "subscribe");
+            assert_eq!(
+                subscribe["product_ids"],
+                serde_json::json!(["BTC-USD", "SOL-USD"])
+            );
+            for line in RECORDED.lines() {
+                socket.send(Message::Text(line.to_string())).await.unwrap();
+            }
+            socket.close(None).await.unwrap();
+        });
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut source: Box<dyn PriceSource> =
+            Box::new(TickerClient::new(tx, registry()).with_url(&url));
+        assert_eq!(source.name(), "coinbase");
+        tokio::spawn(async move { source.run().await });
+
+        let mut events = Vec::new();
+        while events.len() < 4 {
+            events.push(
+                tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                    .await
+                    .expect("no event within 5s")
+                    .unwrap(),
+            );
+        }
+        assert!(matches!(events[0], OracleEvent::Connected));
+        let symbols: Vec<&str> = events[1..3]
+            .iter()
+            .map(|event| match event {
+                OracleEvent::Price(update) => update.symbol.as_str(),
+                other => panic!("expected a price, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(symbols, ["BTC", "JITOSOL"]);
+        assert!(matches!(events[3], OracleEvent::Disconnected));
+        server.await.unwrap();
+    }
+}
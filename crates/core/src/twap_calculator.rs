@@ -4,67 +4,277 @@
 //! the time-weighted average for settlement pricing.
 
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
-use tracing::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
 
-use joyride_oracle_wire::{PriceUpdate, TwapPreview};
+use joyride_oracle_wire::{
+    FixedPrice, GapInterval, PriceUpdate, RoundingMode, SampleRejected, SampleRejection,
+    SettlementRung, TwapMethod, TwapPreview, TwapResult, UpdateData,
+};
 
 /// A single recorded TWAP sample.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TwapSample {
     /// The price at this sample time.
     pub price: f64,
     /// Unix timestamp in seconds.
     pub timestamp: i64,
+    /// Pyth confidence interval published with the price, used by
+    /// [`TwapMethod::ConfidenceWeighted`].
+    #[serde(default)]
+    pub confidence: f64,
+    /// The exact published price, when the source provides one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_price: Option<FixedPrice>,
+    /// `true` when the sample was synthesized by [`GapPolicy::CarryForward`]
+    /// rather than observed from the feed.
+    #[serde(default)]
+    pub filled: bool,
+    /// `true` when the confidence was wider than the asset's
+    /// [`ConfidencePolicy`] allows and the sample was kept anyway.
+    #[serde(default)]
+    pub wide: bool,
+    /// Weight in the mean-style averages: 1.0 unless the sample was
+    /// down-weighted by [`ConfidenceAction::DownWeight`].
+    #[serde(default = "full_weight")]
+    pub weight: f64,
+    /// [`UpdateData::id`] of the signed Pyth update the price came from,
+    /// so the data behind a settlement can be posted on chain. The
+    /// calculator keeps the update itself once for all the feeds it covers;
+    /// see [`TwapCalculator::update_data`]. `None` for filled samples and
+    /// sources without signed data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_id: Option<String>,
 }
 
-/// A completed TWAP calculation over a closed window.
-///
-/// Produced by [`TwapCalculator::calculate`] for callers that want to
-/// settle or persist a window value. Not on the oracle wire contract —
-/// the oracle service only broadcasts rolling previews — but available
-/// for embedders implementing their own settlement logic.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TwapResult {
-    /// The asset this TWAP is for.
-    pub symbol: String,
+fn full_weight() -> f64 {
+    1.0
+}
+
+/// What [`TwapCalculator`] does about seconds in which no sample arrived.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum GapPolicy {
+    /// Leave missing seconds empty; they only lower `coverage`.
+    #[default]
+    Leave,
+
+    /// Carry the last observed price forward into each missing second, for
+    /// gaps of at most `max_gap_secs` seconds. Longer gaps are left empty.
+    CarryForward { max_gap_secs: i64 },
+
+    /// Any missing second invalidates the window: `calculate` returns `None`.
+    Invalidate,
+}
+
+/// One rung of the chain [`TwapCalculator::calculate`] walks when a window
+/// falls short of its asset's [`CoveragePolicy::min_coverage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SettlementFallback {
+    /// Extend the window backwards by up to `max_extra_secs` and accept it
+    /// once it holds as many samples as the threshold demands of the
+    /// original window.
+    ExtendWindow { max_extra_secs: i64 },
+
+    /// Use the latest Pyth EMA price published inside the window.
+    EmaPrice,
 
-    /// The calculated TWAP price.
-    pub twap: f64,
+    /// Use the most recent observed (not carried-forward) sample at or
+    /// before `window_end`.
+    LastGoodPrice,
 
-    /// Start of the TWAP window (Unix timestamp in seconds).
-    pub window_start: i64,
+    /// Stop and return no result. Also implied at the end of the chain.
+    Refuse,
+}
+
+/// Per-asset minimum coverage and the fallback chain used below it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoveragePolicy {
+    /// Minimum `coverage` (0.0 to 1.0) for the window TWAP to be used as is.
+    pub min_coverage: f64,
+    /// Rungs tried in order when the window falls short.
+    pub fallbacks: Vec<SettlementFallback>,
+}
+
+/// What [`TwapCalculator::record`] does with a sample whose confidence is
+/// wider than its asset's [`ConfidencePolicy::max_ratio`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConfidenceAction {
+    /// Leave the sample out; the second counts as missing.
+    #[default]
+    Drop,
 
-    /// End of the TWAP window (Unix timestamp in seconds).
-    pub window_end: i64,
+    /// Keep the sample at full weight and count it as flagged.
+    Flag,
 
-    /// Number of samples used in calculation.
-    pub sample_count: usize,
+    /// Keep the sample at `weight` (above 0.0, at most 1.0) in the mean,
+    /// time-weighted and confidence-weighted methods. The median and
+    /// trimmed mean are order statistics and count it fully.
+    DownWeight { weight: f64 },
+}
+
+/// Per-asset ceiling on a sample's confidence relative to its price.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ConfidencePolicy {
+    /// Widest accepted `confidence / price`, e.g. `0.01` for 1%.
+    pub max_ratio: f64,
+    #[serde(default)]
+    pub action: ConfidenceAction,
+}
 
-    /// Percentage of expected samples that were collected (0.0 to 1.0).
-    pub coverage: f64,
+impl ConfidencePolicy {
+    /// Whether `confidence` is too wide for `price`. A zero or non-finite
+    /// price always is.
+    pub fn is_wide(&self, price: f64, confidence: f64) -> bool {
+        let ratio = confidence / price.abs();
+        ratio.is_nan() || ratio > self.max_ratio
+    }
+}
+
+/// Lifetime counts of one symbol's samples caught by its
+/// [`ConfidencePolicy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConfidenceCounts {
+    /// Seconds whose sample was dropped.
+    pub rejected: u64,
+    /// Samples kept flagged or down-weighted.
+    pub flagged: u64,
+}
+
+/// Lifetime counts of one symbol's prices turned away by
+/// [`TwapCalculator::admit`] for their publish time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RejectionCounts {
+    /// Published before the newest sample.
+    pub regressions: u64,
+    /// Resends of the newest sample's print.
+    pub duplicates: u64,
+    /// Published too far ahead of the clock.
+    pub future: u64,
+}
+
+/// Identity of the print behind a symbol's newest sample, enough to tell a
+/// resend of it from a sub-second update.
+#[derive(Debug, Clone, Copy)]
+struct NewestPrint {
+    publish_time: i64,
+    slot: Option<u64>,
+    prev_publish_time: Option<i64>,
+    /// Clock second it was admitted in.
+    received_at: i64,
+}
+
+/// How an asset's exact TWAP is rounded to its settlement precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundingRule {
+    #[serde(default)]
+    pub mode: RoundingMode,
+    /// Exponent of the settled price, e.g. `-2` for cents. `None` keeps the
+    /// feed's own exponent.
+    #[serde(default)]
+    pub expo: Option<i32>,
 }
 
 /// Default TWAP window duration in seconds (30 minutes).
 pub const DEFAULT_TWAP_WINDOW_SECS: i64 = 30 * 60;
 
+/// A named TWAP window. One [`TwapCalculator`] serves any number of these
+/// from a single sample store per symbol.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwapWindow {
+    /// Name carried on results and previews, e.g. `"5m"`.
+    pub name: String,
+    /// Window length in seconds.
+    pub secs: i64,
+}
+
+impl TwapWindow {
+    pub fn new(name: impl Into<String>, secs: i64) -> Self {
+        Self {
+            name: name.into(),
+            secs,
+        }
+    }
+
+    /// Window named after its length: `45s`, `5m`, `1h`, or `90s` when the
+    /// length is not a whole number of larger units.
+    pub fn from_secs(secs: i64) -> Self {
+        let name = if secs > 0 && secs % 3600 == 0 {
+            format!("{}h", secs / 3600)
+        } else if secs > 0 && secs % 60 == 0 {
+            format!("{}m", secs / 60)
+        } else {
+            format!("{secs}s")
+        };
+        Self::new(name, secs)
+    }
+}
+
 /// Default retention horizon, in windows. Kept at two so `calculate()` can
 /// still be called with a `window_end` up to one window in the past.
 pub const DEFAULT_RETENTION_WINDOWS: u32 = 2;
 
+/// Default tolerance, in seconds, for publish times ahead of the local
+/// clock.
+pub const DEFAULT_MAX_FUTURE_SKEW_SECS: i64 = 5;
+
 /// Sampling interval in seconds (1 second). Not configurable — `with_window`
 /// locks this in — and thus not part of the public API.
 const DEFAULT_SAMPLE_INTERVAL_SECS: i64 = 1;
 
 /// TWAP calculator that accumulates samples and computes averages.
+///
+/// Samples are stored once per symbol and shared by every configured
+/// window. The first window is the primary one, used by `calculate`,
+/// `calculate_preview` and `expected_samples`; the `*_window` variants
+/// address any window by name.
+///
+/// Each symbol's samples carry running price sums and price-seconds
+/// integrals, so a window's mean or time-weighted average is the difference
+/// of two prefixes located by binary search. The per-second preview cost
+/// does not grow with the window length or the number of windows' samples.
+///
+/// Samples with an exact published price are also summed as integers, so
+/// results over them carry a `fixed_twap` rounded by the asset's
+/// [`RoundingRule`], and `twap` is that value as an `f64`.
 pub struct TwapCalculator {
-    samples: HashMap<String, VecDeque<TwapSample>>,
-    window_secs: i64,
-    /// How many windows of history to retain. Caps how far in the past
+    samples: HashMap<String, SampleStore>,
+    windows: Vec<TwapWindow>,
+    /// How many of the longest window to retain. Caps how far in the past
     /// `calculate()` returns full coverage and bounds memory.
     retention_windows: u32,
     sample_interval_secs: i64,
     last_sample_time: HashMap<String, i64>,
+    method: TwapMethod,
+    /// Per-symbol overrides of `method`.
+    methods: HashMap<String, TwapMethod>,
+    gap_policy: GapPolicy,
+    rounding: RoundingRule,
+    /// Per-symbol overrides of `rounding`.
+    roundings: HashMap<String, RoundingRule>,
+    coverage_policies: HashMap<String, CoveragePolicy>,
+    confidence_policies: HashMap<String, ConfidencePolicy>,
+    /// Timestamps of dropped wide samples per symbol, kept for the same
+    /// retention horizon as samples so results can count them.
+    rejected: HashMap<String, VecDeque<i64>>,
+    confidence_counts: HashMap<String, ConfidenceCounts>,
+    /// How far ahead of `now` [`Self::admit`] accepts a publish time.
+    max_future_skew_secs: i64,
+    rejection_counts: HashMap<String, RejectionCounts>,
+    /// The print behind each symbol's newest admitted sample.
+    newest_prints: HashMap<String, NewestPrint>,
+    /// Publish time of the last duplicate reported per symbol.
+    reported_duplicates: HashMap<String, i64>,
+    /// Recent `(publish_time, ema_price)` per symbol, for the
+    /// [`SettlementFallback::EmaPrice`] rung.
+    ema_prices: HashMap<String, VecDeque<(i64, f64)>>,
+    /// Signed update data behind retained samples, once per
+    /// `(publish_time, id)` however many feeds it covers. Dropped once no
+    /// symbol retains samples that old.
+    update_data: BTreeMap<(i64, String), Arc<UpdateData>>,
 }
 
 impl TwapCalculator {
@@ -80,22 +290,294 @@ impl TwapCalculator {
     /// must be `>= 1`; a larger value lets `calculate()` look further into
     /// the past at the cost of memory.
     pub fn with_retention(window_secs: i64, retention_windows: u32) -> Self {
+        Self::with_windows(vec![TwapWindow::from_secs(window_secs)], retention_windows)
+    }
+
+    /// Construct a calculator serving several named windows from one sample
+    /// store. The first window is the primary one. Retention is counted in
+    /// multiples of the longest window.
+    pub fn with_windows(windows: Vec<TwapWindow>, retention_windows: u32) -> Self {
         assert!(
             retention_windows >= 1,
             "retention_windows must be >= 1 (got {retention_windows})"
         );
+        assert!(!windows.is_empty(), "at least one TWAP window is required");
+        for (i, window) in windows.iter().enumerate() {
+            assert!(
+                window.secs > 0,
+                "TWAP window {} must be positive (got {}s)",
+                window.name,
+                window.secs
+            );
+            assert!(
+                windows[..i].iter().all(|other| other.name != window.name),
+                "duplicate TWAP window name {}",
+                window.name
+            );
+        }
         Self {
             samples: HashMap::new(),
-            window_secs,
+            windows,
             retention_windows,
             sample_interval_secs: DEFAULT_SAMPLE_INTERVAL_SECS,
             last_sample_time: HashMap::new(),
+            method: TwapMethod::default(),
+            methods: HashMap::new(),
+            gap_policy: GapPolicy::default(),
+            rounding: RoundingRule::default(),
+            roundings: HashMap::new(),
+            coverage_policies: HashMap::new(),
+            confidence_policies: HashMap::new(),
+            rejected: HashMap::new(),
+            confidence_counts: HashMap::new(),
+            max_future_skew_secs: DEFAULT_MAX_FUTURE_SKEW_SECS,
+            rejection_counts: HashMap::new(),
+            newest_prints: HashMap::new(),
+            reported_duplicates: HashMap::new(),
+            ema_prices: HashMap::new(),
+            update_data: BTreeMap::new(),
         }
     }
 
+    /// Select the averaging method used by `calculate` and
+    /// `calculate_preview` for symbols without their own. Defaults to
+    /// [`TwapMethod::Mean`].
+    pub fn with_method(mut self, method: TwapMethod) -> Self {
+        validate_method(method);
+        self.method = method;
+        self
+    }
+
+    pub fn method(&self) -> TwapMethod {
+        self.method
+    }
+
+    /// Average `symbol` with `method` instead of the calculator default.
+    pub fn with_symbol_method(mut self, symbol: impl Into<String>, method: TwapMethod) -> Self {
+        validate_method(method);
+        self.methods.insert(symbol.into(), method);
+        self
+    }
+
+    /// The method `symbol` is averaged with.
+    pub fn method_for(&self, symbol: &str) -> TwapMethod {
+        self.methods.get(symbol).copied().unwrap_or(self.method)
+    }
+
+    /// Select how seconds without a sample are treated. Defaults to
+    /// [`GapPolicy::Leave`].
+    pub fn with_gap_policy(mut self, gap_policy: GapPolicy) -> Self {
+        self.gap_policy = gap_policy;
+        self
+    }
+
+    pub fn gap_policy(&self) -> GapPolicy {
+        self.gap_policy
+    }
+
+    /// Select how exact TWAPs are rounded for symbols without their own
+    /// rule. Defaults to half-even at the feed's exponent.
+    pub fn with_rounding(mut self, rounding: RoundingRule) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Round `symbol`'s exact TWAPs with `rounding` instead of the default.
+    pub fn with_symbol_rounding(
+        mut self,
+        symbol: impl Into<String>,
+        rounding: RoundingRule,
+    ) -> Self {
+        self.roundings.insert(symbol.into(), rounding);
+        self
+    }
+
+    /// The rounding rule applied to `symbol`.
+    pub fn rounding_for(&self, symbol: &str) -> RoundingRule {
+        self.roundings.get(symbol).copied().unwrap_or(self.rounding)
+    }
+
+    /// Enforce a minimum coverage for `symbol`. Windows below it (or
+    /// invalidated by the gap policy) walk `policy.fallbacks` instead of
+    /// settling as is. Symbols without a policy keep the plain behavior.
+    pub fn with_coverage_policy(
+        mut self,
+        symbol: impl Into<String>,
+        policy: CoveragePolicy,
+    ) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&policy.min_coverage),
+            "min_coverage must be within 0.0..=1.0 (got {})",
+            policy.min_coverage
+        );
+        self.coverage_policies.insert(symbol.into(), policy);
+        self
+    }
+
+    pub fn coverage_policy(&self, symbol: &str) -> Option<&CoveragePolicy> {
+        self.coverage_policies.get(symbol)
+    }
+
+    /// Guard `symbol`'s TWAP against quotes whose confidence is wider than
+    /// `policy.max_ratio` of the price. Symbols without a policy take every
+    /// sample as is.
+    pub fn with_confidence_policy(
+        mut self,
+        symbol: impl Into<String>,
+        policy: ConfidencePolicy,
+    ) -> Self {
+        assert!(
+            policy.max_ratio > 0.0,
+            "max_ratio must be positive (got {})",
+            policy.max_ratio
+        );
+        if let ConfidenceAction::DownWeight { weight } = policy.action {
+            assert!(
+                weight > 0.0 && weight <= 1.0,
+                "down-weight must be within (0.0, 1.0] (got {weight})"
+            );
+        }
+        self.confidence_policies.insert(symbol.into(), policy);
+        self
+    }
+
+    pub fn confidence_policy(&self, symbol: &str) -> Option<&ConfidencePolicy> {
+        self.confidence_policies.get(symbol)
+    }
+
+    /// Lifetime confidence-policy counts for every symbol that has any,
+    /// sorted by symbol.
+    pub fn confidence_counts(&self) -> Vec<(String, ConfidenceCounts)> {
+        let mut counts: Vec<(String, ConfidenceCounts)> = self
+            .confidence_counts
+            .iter()
+            .map(|(symbol, counts)| (symbol.clone(), *counts))
+            .collect();
+        counts.sort_by(|a, b| a.0.cmp(&b.0));
+        counts
+    }
+
+    /// Tolerate publish times up to `secs` ahead of the clock passed to
+    /// [`Self::admit`]. Defaults to [`DEFAULT_MAX_FUTURE_SKEW_SECS`].
+    pub fn with_max_future_skew(mut self, secs: i64) -> Self {
+        assert!(
+            secs >= 0,
+            "max future skew must not be negative (got {secs})"
+        );
+        self.max_future_skew_secs = secs;
+        self
+    }
+
+    pub fn max_future_skew_secs(&self) -> i64 {
+        self.max_future_skew_secs
+    }
+
+    /// Lifetime publish-time rejection counts for every symbol that has
+    /// any, sorted by symbol.
+    pub fn rejection_counts(&self) -> Vec<(String, RejectionCounts)> {
+        let mut counts: Vec<(String, RejectionCounts)> = self
+            .rejection_counts
+            .iter()
+            .map(|(symbol, counts)| (symbol.clone(), *counts))
+            .collect();
+        counts.sort_by(|a, b| a.0.cmp(&b.0));
+        counts
+    }
+
+    /// [`Self::record`] a live price received at `now`, after checking its
+    /// publish time. A price published before the newest sample or more
+    /// than the skew tolerance ahead of `now` is counted and returned as
+    /// the error, leaving the window untouched.
+    ///
+    /// Further prints for an already sampled second are sub-second updates
+    /// (several Lazer prints, or repeated aggregated medians) and are
+    /// skipped quietly, as by `record`. Only a resend of the newest
+    /// sample's print is counted as a duplicate: the same publish time,
+    /// slot and previous publish time, received in a later second. Just
+    /// the first resend of each publish time is an error, so a stalled
+    /// feed is reported once. Prints without a slot are never resends.
+    pub fn admit(&mut self, update: &PriceUpdate, now: i64) -> Result<bool, SampleRejected> {
+        let symbol = &update.symbol;
+        let latest_time = self.last_sample_time.get(symbol).copied();
+        let reason = if update.publish_time > now.saturating_add(self.max_future_skew_secs) {
+            self.rejection_counts
+                .entry(symbol.clone())
+                .or_default()
+                .future += 1;
+            SampleRejection::Future
+        } else if latest_time.is_some_and(|latest| update.publish_time < latest) {
+            self.rejection_counts
+                .entry(symbol.clone())
+                .or_default()
+                .regressions += 1;
+            SampleRejection::Regression
+        } else if latest_time == Some(update.publish_time) {
+            let resend = update.slot.is_some()
+                && self.newest_prints.get(symbol).is_some_and(|newest| {
+                    newest.publish_time == update.publish_time
+                        && newest.slot == update.slot
+                        && newest.prev_publish_time == update.prev_publish_time
+                        && now > newest.received_at
+                });
+            if !resend {
+                return Ok(false);
+            }
+            self.rejection_counts
+                .entry(symbol.clone())
+                .or_default()
+                .duplicates += 1;
+            let reported = self
+                .reported_duplicates
+                .insert(symbol.clone(), update.publish_time);
+            if reported == Some(update.publish_time) {
+                return Ok(false);
+            }
+            SampleRejection::Duplicate
+        } else {
+            let recorded = self.record(update);
+            if recorded {
+                self.newest_prints.insert(
+                    symbol.clone(),
+                    NewestPrint {
+                        publish_time: update.publish_time,
+                        slot: update.slot,
+                        prev_publish_time: update.prev_publish_time,
+                        received_at: now,
+                    },
+                );
+            }
+            return Ok(recorded);
+        };
+
+        Err(SampleRejected {
+            symbol: symbol.clone(),
+            reason,
+            price: update.price,
+            publish_time: update.publish_time,
+            latest_time,
+            received_at: now,
+        })
+    }
+
+    /// Configured windows, primary first.
+    pub fn windows(&self) -> &[TwapWindow] {
+        &self.windows
+    }
+
+    fn primary_window(&self) -> &TwapWindow {
+        &self.windows[0]
+    }
+
+    fn window(&self, name: &str) -> Option<&TwapWindow> {
+        self.windows.iter().find(|window| window.name == name)
+    }
+
     pub fn record(&mut self, update: &PriceUpdate) -> bool {
         let symbol = &update.symbol;
         let timestamp = update.publish_time;
+        if let Some(ema_price) = update.ema_price {
+            self.record_ema(symbol, ema_price, timestamp);
+        }
 
         if let Some(&last_time) = self.last_sample_time.get(symbol) {
             if timestamp - last_time < self.sample_interval_secs {
@@ -103,26 +585,94 @@ impl TwapCalculator {
             }
         }
 
-        let sample = TwapSample {
+        let mut sample = TwapSample {
             price: update.price,
             timestamp,
+            confidence: update.confidence,
+            fixed_price: update.fixed_price,
+            filled: false,
+            wide: false,
+            weight: 1.0,
+            update_id: None,
         };
 
-        let deque = self.samples.entry(symbol.clone()).or_default();
-        deque.push_back(sample);
+        let retention_secs = self.retention_secs();
+        if let Some(policy) = self.confidence_policies.get(symbol).copied() {
+            if policy.is_wide(update.price, update.confidence) {
+                if policy.action == ConfidenceAction::Drop {
+                    // Leave the second open for a tighter quote.
+                    let rejected = self.rejected.entry(symbol.clone()).or_default();
+                    if rejected.back() != Some(&timestamp) {
+                        rejected.push_back(timestamp);
+                        self.confidence_counts
+                            .entry(symbol.clone())
+                            .or_default()
+                            .rejected += 1;
+                        debug!(
+                            "Dropped wide sample for {}: conf {} on ${:.4} at {}",
+                            symbol, update.confidence, update.price, timestamp
+                        );
+                    }
+                    let cutoff = timestamp.saturating_sub(retention_secs);
+                    while rejected.front().is_some_and(|&time| time < cutoff) {
+                        rejected.pop_front();
+                    }
+                    return false;
+                }
+                if let ConfidenceAction::DownWeight { weight } = policy.action {
+                    sample.weight = weight;
+                }
+                sample.wide = true;
+                self.confidence_counts
+                    .entry(symbol.clone())
+                    .or_default()
+                    .flagged += 1;
+            }
+        }
+
+        if let Some(data) = &update.update_data {
+            self.update_data
+                .entry((timestamp, data.id.clone()))
+                .or_insert_with(|| Arc::clone(data));
+            sample.update_id = Some(data.id.clone());
+        }
+
+        let store = self.samples.entry(symbol.clone()).or_default();
+        if let (GapPolicy::CarryForward { max_gap_secs }, Some(last)) =
+            (self.gap_policy, store.back().cloned())
+        {
+            let missing = timestamp - last.timestamp - 1;
+            if missing > 0 && missing <= max_gap_secs {
+                for filled_at in (last.timestamp + 1)..timestamp {
+                    store.push(TwapSample {
+                        price: last.price,
+                        timestamp: filled_at,
+                        confidence: last.confidence,
+                        fixed_price: last.fixed_price,
+                        filled: true,
+                        wide: last.wide,
+                        weight: last.weight,
+                        update_id: None,
+                    });
+                }
+                debug!(
+                    "Carried {} forward over {}s gap for {}",
+                    last.price, missing, symbol
+                );
+            }
+        }
+        store.push(sample);
 
         // The interval check above keeps timestamps strictly increasing, so
         // stale data always sits at the head and front-pop is enough.
-        let retention_secs = self
-            .window_secs
-            .saturating_mul(i64::from(self.retention_windows));
         let cutoff = timestamp.saturating_sub(retention_secs);
-        while let Some(front) = deque.front() {
-            if front.timestamp < cutoff {
-                deque.pop_front();
-            } else {
-                break;
-            }
+        store.trim_before(cutoff);
+        if self
+            .update_data
+            .first_key_value()
+            .is_some_and(|((time, _), _)| *time < cutoff)
+        {
+            self.trim_update_data();
         }
 
         self.last_sample_time.insert(symbol.clone(), timestamp);
@@ -135,101 +685,394 @@ impl TwapCalculator {
         true
     }
 
+    /// Record a Pyth EMA price for the [`SettlementFallback::EmaPrice`]
+    /// rung. Kept for the same retention horizon as samples.
+    pub fn record_ema(&mut self, symbol: &str, ema_price: f64, publish_time: i64) {
+        let cutoff = publish_time.saturating_sub(self.retention_secs());
+        let history = self.ema_prices.entry(symbol.to_string()).or_default();
+        if history
+            .back()
+            .is_some_and(|&(last, _)| publish_time <= last)
+        {
+            return;
+        }
+        history.push_back((publish_time, ema_price));
+        while history.front().is_some_and(|&(time, _)| time < cutoff) {
+            history.pop_front();
+        }
+    }
+
+    /// The most recent `(publish_time, ema_price)` recorded for `symbol`.
+    pub fn latest_ema(&self, symbol: &str) -> Option<(i64, f64)> {
+        self.ema_prices.get(symbol)?.back().copied()
+    }
+
+    /// How far behind the newest sample history is kept: the longest
+    /// window times `retention_windows`.
+    pub fn retention_secs(&self) -> i64 {
+        let longest_window_secs = self
+            .windows
+            .iter()
+            .map(|window| window.secs)
+            .max()
+            .unwrap_or_default();
+        longest_window_secs.saturating_mul(i64::from(self.retention_windows))
+    }
+
     pub fn sample_count(&self, symbol: &str) -> usize {
-        self.samples.get(symbol).map(|s| s.len()).unwrap_or(0)
+        self.samples.get(symbol).map(SampleStore::len).unwrap_or(0)
+    }
+
+    pub fn sample_interval_secs(&self) -> i64 {
+        self.sample_interval_secs
     }
 
     pub fn expected_samples(&self) -> usize {
-        (self.window_secs / self.sample_interval_secs) as usize
+        self.expected_samples_for(self.primary_window())
+    }
+
+    fn expected_samples_for(&self, window: &TwapWindow) -> usize {
+        (window.secs / self.sample_interval_secs) as usize
     }
 
     pub fn calculate(&self, symbol: &str, window_end: i64) -> Option<TwapResult> {
+        self.calculate_for(symbol, self.primary_window(), window_end)
+    }
+
+    /// Like [`calculate`](Self::calculate), for the window named `window`.
+    /// Returns `None` if no such window is configured.
+    pub fn calculate_window(
+        &self,
+        symbol: &str,
+        window: &str,
+        window_end: i64,
+    ) -> Option<TwapResult> {
+        self.calculate_for(symbol, self.window(window)?, window_end)
+    }
+
+    fn calculate_for(
+        &self,
+        symbol: &str,
+        window: &TwapWindow,
+        window_end: i64,
+    ) -> Option<TwapResult> {
         let samples = self.samples.get(symbol)?;
-        let window_start = window_end - self.window_secs;
-        let window_samples: Vec<&TwapSample> = samples
-            .iter()
-            .filter(|s| s.timestamp >= window_start && s.timestamp <= window_end)
-            .collect();
+        let window_start = window_end - window.secs;
+        let policy = self.coverage_policies.get(symbol);
+        let min_coverage = policy.map(|p| p.min_coverage).unwrap_or(0.0);
+
+        let primary = self.window_result(symbol, samples, window, window_start, window_end);
+        let shortfall = match &primary {
+            None => "no samples in window".to_string(),
+            Some(result) if !self.gaps_acceptable(&result.gaps) => {
+                let missing_secs: i64 = result.gaps.iter().map(GapInterval::len_secs).sum();
+                format!(
+                    "{} missing seconds in {} gaps",
+                    missing_secs,
+                    result.gaps.len()
+                )
+            }
+            Some(result) if result.coverage < min_coverage => format!(
+                "coverage {:.1}% below minimum {:.1}%",
+                result.coverage * 100.0,
+                min_coverage * 100.0
+            ),
+            Some(result) => {
+                info!(
+                    "{} TWAP calculated for {}: ${:.4} ({} samples, {:.1}% coverage, {:?})",
+                    window.name,
+                    symbol,
+                    result.twap,
+                    result.sample_count,
+                    result.coverage * 100.0,
+                    result.method
+                );
+                return primary;
+            }
+        };
 
-        if window_samples.is_empty() {
-            warn!("No samples found for {} in TWAP window", symbol);
+        let Some(policy) = policy else {
+            warn!(
+                "{} TWAP window for {} ending {} rejected: {}",
+                window.name, symbol, window_end, shortfall
+            );
             return None;
-        }
+        };
+        warn!(
+            "{} TWAP window for {} ending {} rejected: {}; walking fallback chain",
+            window.name, symbol, window_end, shortfall
+        );
+
+        // Rungs that substitute a single price still report the window's
+        // own sample statistics so the shortfall stays visible.
+        let base = primary.unwrap_or_else(|| TwapResult {
+            symbol: symbol.to_string(),
+            window: window.name.clone(),
+            twap: 0.0,
+            fixed_twap: None,
+            window_start,
+            window_end,
+            sample_count: 0,
+            coverage: 0.0,
+            method: self.method_for(symbol),
+            gaps: samples.gap_intervals(window_start, window_end),
+            rung: SettlementRung::Window,
+            rejected_samples: self.rejected_between(symbol, window_start, window_end),
+            flagged_samples: 0,
+        });
+        let required_samples = (min_coverage * self.expected_samples_for(window) as f64).ceil();
 
-        let sum: f64 = window_samples.iter().map(|s| s.price).sum();
-        let twap = sum / window_samples.len() as f64;
-        let expected = self.expected_samples();
-        let coverage = window_samples.len() as f64 / expected as f64;
+        for fallback in &policy.fallbacks {
+            let result = match *fallback {
+                SettlementFallback::ExtendWindow { max_extra_secs } => self
+                    .window_result(
+                        symbol,
+                        samples,
+                        window,
+                        window_start - max_extra_secs,
+                        window_end,
+                    )
+                    .filter(|result| {
+                        self.gaps_acceptable(&result.gaps)
+                            && result.sample_count as f64 >= required_samples
+                    })
+                    .map(|result| TwapResult {
+                        rung: SettlementRung::ExtendedWindow,
+                        ..result
+                    }),
+                SettlementFallback::EmaPrice => self
+                    .ema_prices
+                    .get(symbol)
+                    .and_then(|history| {
+                        history
+                            .iter()
+                            .rev()
+                            .find(|&&(time, _)| time <= window_end)
+                            .filter(|&&(time, _)| time > window_start)
+                    })
+                    .map(|&(_, ema_price)| TwapResult {
+                        twap: ema_price,
+                        fixed_twap: None,
+                        rung: SettlementRung::EmaPrice,
+                        ..base.clone()
+                    }),
+                SettlementFallback::LastGoodPrice => {
+                    samples.last_observed(window_end).map(|sample| {
+                        let rounding = self.rounding_for(symbol);
+                        let fixed_twap = sample.fixed_price.and_then(|fixed| {
+                            fixed.rescale(rounding.expo.unwrap_or(fixed.expo), rounding.mode)
+                        });
+                        TwapResult {
+                            twap: fixed_twap.map_or(sample.price, FixedPrice::to_f64),
+                            fixed_twap,
+                            rung: SettlementRung::LastGoodPrice,
+                            ..base.clone()
+                        }
+                    })
+                }
+                SettlementFallback::Refuse => break,
+            };
 
-        info!(
-            "TWAP calculated for {}: ${:.4} ({} samples, {:.1}% coverage)",
-            symbol,
-            twap,
-            window_samples.len(),
-            coverage * 100.0
+            if let Some(result) = result {
+                warn!(
+                    "{} TWAP for {} ending {} settled via {:?}: ${:.4}",
+                    window.name, symbol, window_end, fallback, result.twap
+                );
+                return Some(result);
+            }
+        }
+
+        error!(
+            "{} TWAP for {} ending {} refused: fallback chain exhausted",
+            window.name, symbol, window_end
         );
+        None
+    }
+
+    /// The TWAP over `(window_start, window_end]` with its gap report, or
+    /// `None` when no sample landed in it. Coverage is measured against the
+    /// span actually covered, so an extended window is not over-credited.
+    fn window_result(
+        &self,
+        symbol: &str,
+        samples: &SampleStore,
+        window: &TwapWindow,
+        window_start: i64,
+        window_end: i64,
+    ) -> Option<TwapResult> {
+        let method = self.method_for(symbol);
+        let aggregate = aggregate(
+            method,
+            self.rounding_for(symbol),
+            samples,
+            window_start,
+            window_end,
+        )?;
+        let sample_count = aggregate.sample_count;
+        let expected = ((window_end - window_start) / self.sample_interval_secs) as usize;
 
         Some(TwapResult {
             symbol: symbol.to_string(),
-            twap,
+            window: window.name.clone(),
+            twap: aggregate.twap,
+            fixed_twap: aggregate.fixed_twap,
             window_start,
             window_end,
-            sample_count: window_samples.len(),
-            coverage,
+            sample_count,
+            coverage: (sample_count as f64 / expected as f64).min(1.0),
+            method,
+            gaps: samples.gap_intervals(window_start, window_end),
+            rung: SettlementRung::Window,
+            rejected_samples: self.rejected_between(symbol, window_start, window_end),
+            flagged_samples: samples.wide_count(window_start, window_end),
+        })
+    }
+
+    /// Dropped wide samples timestamped in `(window_start, window_end]`.
+    fn rejected_between(&self, symbol: &str, window_start: i64, window_end: i64) -> usize {
+        self.rejected.get(symbol).map_or(0, |rejected| {
+            rejected.partition_point(|&time| time <= window_end)
+                - rejected.partition_point(|&time| time <= window_start)
         })
     }
 
+    fn gaps_acceptable(&self, gaps: &[GapInterval]) -> bool {
+        self.gap_policy != GapPolicy::Invalidate || gaps.iter().all(|gap| gap.filled)
+    }
+
+    /// Gap report for the primary window ending at `window_end`, regardless
+    /// of the gap policy. Useful for inspecting why a window was invalidated.
+    pub fn gaps(&self, symbol: &str, window_end: i64) -> Vec<GapInterval> {
+        self.gaps_for(symbol, self.primary_window(), window_end)
+    }
+
+    /// Like [`gaps`](Self::gaps), for the window named `window`.
+    pub fn gaps_window(&self, symbol: &str, window: &str, window_end: i64) -> Vec<GapInterval> {
+        self.window(window)
+            .map(|window| self.gaps_for(symbol, window, window_end))
+            .unwrap_or_default()
+    }
+
+    fn gaps_for(&self, symbol: &str, window: &TwapWindow, window_end: i64) -> Vec<GapInterval> {
+        self.samples
+            .get(symbol)
+            .map(|samples| samples.gap_intervals(window_end - window.secs, window_end))
+            .unwrap_or_default()
+    }
+
     pub fn calculate_preview(&self, symbol: &str, current_time: i64) -> Option<TwapPreview> {
-        let samples = self.samples.get(symbol)?;
-        let window_start = current_time - self.window_secs;
-        let window_samples: Vec<&TwapSample> = samples
+        self.preview_for(symbol, self.primary_window(), current_time)
+    }
+
+    /// Like [`calculate_preview`](Self::calculate_preview), for the window
+    /// named `window`. Returns `None` if no such window is configured.
+    pub fn calculate_preview_window(
+        &self,
+        symbol: &str,
+        window: &str,
+        current_time: i64,
+    ) -> Option<TwapPreview> {
+        self.preview_for(symbol, self.window(window)?, current_time)
+    }
+
+    /// Previews for every configured window, primary first.
+    pub fn calculate_previews(&self, symbol: &str, current_time: i64) -> Vec<TwapPreview> {
+        self.windows
             .iter()
-            .filter(|s| s.timestamp >= window_start && s.timestamp <= current_time)
-            .collect();
+            .filter_map(|window| self.preview_for(symbol, window, current_time))
+            .collect()
+    }
 
-        if window_samples.is_empty() {
+    fn preview_for(
+        &self,
+        symbol: &str,
+        window: &TwapWindow,
+        current_time: i64,
+    ) -> Option<TwapPreview> {
+        let samples = self.samples.get(symbol)?;
+        let window_start = current_time - window.secs;
+        let method = self.method_for(symbol);
+        let rounding = self.rounding_for(symbol);
+        let Some(Aggregate {
+            twap, sample_count, ..
+        }) = aggregate(method, rounding, samples, window_start, current_time)
+        else {
             return Some(TwapPreview {
                 symbol: symbol.to_string(),
+                window: window.name.clone(),
                 twap: 0.0,
                 sample_count: 0,
                 coverage: 0.0,
             });
-        }
+        };
 
-        let sum: f64 = window_samples.iter().map(|s| s.price).sum();
-        let twap = sum / window_samples.len() as f64;
-        let expected = self.expected_samples();
-        let coverage = (window_samples.len() as f64 / expected as f64).min(1.0);
+        let expected = self.expected_samples_for(window);
+        let coverage = (sample_count as f64 / expected as f64).min(1.0);
 
         Some(TwapPreview {
             symbol: symbol.to_string(),
+            window: window.name.clone(),
             twap,
-            sample_count: window_samples.len(),
+            sample_count,
             coverage,
         })
     }
 
+    /// Forget everything recorded for `symbol`: samples, EMA history and
+    /// the confidence and rejection counts. Its configured method and
+    /// policies stay.
     pub fn clear(&mut self, symbol: &str) {
         self.samples.remove(symbol);
         self.last_sample_time.remove(symbol);
+        self.rejected.remove(symbol);
+        self.confidence_counts.remove(symbol);
+        self.rejection_counts.remove(symbol);
+        self.newest_prints.remove(symbol);
+        self.reported_duplicates.remove(symbol);
+        self.ema_prices.remove(symbol);
+        self.trim_update_data();
         info!("Cleared TWAP samples for {}", symbol);
     }
 
     pub fn prune(&mut self, before_timestamp: i64) {
         for (symbol, samples) in &mut self.samples {
-            let original_len = samples.len();
-            while let Some(front) = samples.front() {
-                if front.timestamp < before_timestamp {
-                    samples.pop_front();
-                } else {
-                    break;
-                }
-            }
-            let pruned = original_len - samples.len();
+            let pruned = samples.trim_before(before_timestamp);
             if pruned > 0 {
                 debug!("Pruned {} old samples for {}", pruned, symbol);
             }
         }
+        for rejected in self.rejected.values_mut() {
+            while rejected
+                .front()
+                .is_some_and(|&time| time < before_timestamp)
+            {
+                rejected.pop_front();
+            }
+        }
+        self.trim_update_data();
+    }
+
+    /// The signed update with `id` that a sample recorded at `timestamp`
+    /// refers to, while the calculator retains it.
+    pub fn update_data(&self, timestamp: i64, id: &str) -> Option<&Arc<UpdateData>> {
+        self.update_data.get(&(timestamp, id.to_string()))
+    }
+
+    /// Drop signed update data older than every symbol's oldest retained
+    /// sample.
+    fn trim_update_data(&mut self) {
+        let oldest = self
+            .samples
+            .values()
+            .filter_map(|store| store.samples.front())
+            .map(|sample| sample.timestamp)
+            .min();
+        match oldest {
+            Some(oldest) => {
+                self.update_data = self.update_data.split_off(&(oldest, String::new()));
+            }
+            None => self.update_data.clear(),
+        }
     }
 
     /// Owned snapshot of retained samples for `symbol`. Returns a `Vec` so
@@ -237,7 +1080,501 @@ impl TwapCalculator {
     pub fn snapshot_samples(&self, symbol: &str) -> Option<Vec<TwapSample>> {
         self.samples
             .get(symbol)
-            .map(|d| d.iter().cloned().collect())
+            .map(|store| store.samples.iter().cloned().collect())
+    }
+}
+
+/// One window's average: the float price, its exact decimal form when
+/// every contributing sample carried one, and the in-window sample count.
+struct Aggregate {
+    twap: f64,
+    fixed_twap: Option<FixedPrice>,
+    sample_count: usize,
+}
+
+/// Average the samples in `(window_start, window_end]` with `method`, or
+/// `None` when no sample landed in the window. The mean-style methods
+/// difference prefix aggregates; the order statistics sort the window's
+/// prices. When the inputs are all fixed-point the average is also taken in
+/// integers and rounded once by `rounding`, and that value is the price.
+fn aggregate(
+    method: TwapMethod,
+    rounding: RoundingRule,
+    samples: &SampleStore,
+    window_start: i64,
+    window_end: i64,
+) -> Option<Aggregate> {
+    let (from, to) = samples.range(window_start, window_end);
+    if from >= to {
+        return None;
+    }
+
+    let twap = match method {
+        TwapMethod::Mean => samples.mean(from, to),
+        TwapMethod::TimeWeighted => samples
+            .time_weighted(window_start, window_end)
+            .unwrap_or_else(|| samples.samples[to - 1].price),
+        TwapMethod::ConfidenceWeighted => samples
+            .confidence_weighted(from, to)
+            .unwrap_or_else(|| samples.mean(from, to)),
+        TwapMethod::Median => {
+            let prices = samples.sorted_prices(from, to);
+            let mid = prices.len() / 2;
+            if prices.len().is_multiple_of(2) {
+                (prices[mid - 1] + prices[mid]) / 2.0
+            } else {
+                prices[mid]
+            }
+        }
+        TwapMethod::TrimmedMean { trim_pct } => {
+            let prices = samples.sorted_prices(from, to);
+            // trim_pct < 50 keeps at least one price after trimming.
+            let trim = prices.len() * usize::from(trim_pct) / 100;
+            let kept = &prices[trim..prices.len() - trim];
+            kept.iter().sum::<f64>() / kept.len() as f64
+        }
+    };
+
+    let fixed_twap = samples
+        .exact_ratio(method, window_start, window_end, from, to)
+        .and_then(|(numerator, denominator, expo)| {
+            FixedPrice::from_ratio(
+                numerator,
+                denominator,
+                expo,
+                rounding.expo.unwrap_or(expo),
+                rounding.mode,
+            )
+        });
+
+    Some(Aggregate {
+        twap: fixed_twap.map_or(twap, FixedPrice::to_f64),
+        fixed_twap,
+        sample_count: to - from,
+    })
+}
+
+/// `10^exp` for the non-negative exponent gaps between fixed-point samples,
+/// saturating rather than overflowing.
+fn pow10(exp: i32) -> i128 {
+    10i128.saturating_pow(exp.unsigned_abs())
+}
+
+/// Reject method parameters `aggregate` cannot honor.
+fn validate_method(method: TwapMethod) {
+    if let TwapMethod::TrimmedMean { trim_pct } = method {
+        assert!(
+            trim_pct < 50,
+            "trimmed mean must keep at least one sample (trim_pct {trim_pct} >= 50)"
+        );
+    }
+}
+
+/// Running totals up to one sample: the sum of the prices before it, the
+/// inverse-confidence weights and weighted prices before it, and the
+/// price-seconds integral from the first retained sample up to its timestamp.
+/// Prices, sums and integrals are scaled by each sample's `weight`, and
+/// `sample_weight_sum` and `weight_area` total the weights themselves. The
+/// `fixed_` fields repeat the sum and integral in mantissas at the store's
+/// exponent, counting only fixed-point samples.
+#[derive(Debug, Clone, Copy, Default)]
+struct Prefix {
+    price_sum: f64,
+    sample_weight_sum: f64,
+    weight_sum: f64,
+    weighted_price_sum: f64,
+    area: f64,
+    weight_area: f64,
+    wide_count: usize,
+    reduced_count: usize,
+    fixed_count: usize,
+    fixed_sum: i128,
+    fixed_area: i128,
+}
+
+impl Prefix {
+    fn minus(self, base: Prefix) -> Prefix {
+        Prefix {
+            price_sum: self.price_sum - base.price_sum,
+            sample_weight_sum: self.sample_weight_sum - base.sample_weight_sum,
+            weight_sum: self.weight_sum - base.weight_sum,
+            weighted_price_sum: self.weighted_price_sum - base.weighted_price_sum,
+            area: self.area - base.area,
+            weight_area: self.weight_area - base.weight_area,
+            wide_count: self.wide_count - base.wide_count,
+            reduced_count: self.reduced_count - base.reduced_count,
+            fixed_count: self.fixed_count - base.fixed_count,
+            fixed_sum: self.fixed_sum - base.fixed_sum,
+            fixed_area: self.fixed_area - base.fixed_area,
+        }
+    }
+
+    /// The fixed-point totals multiplied by `factor`, for moving the store
+    /// to a finer exponent.
+    fn scaled(self, factor: i128) -> Prefix {
+        Prefix {
+            fixed_sum: self.fixed_sum.saturating_mul(factor),
+            fixed_area: self.fixed_area.saturating_mul(factor),
+            ..self
+        }
+    }
+
+    /// Totals after adding `sample`, whose scaled mantissa is `fixed`.
+    fn plus(self, sample: &TwapSample, fixed: Option<i128>) -> Prefix {
+        let weight = if sample.confidence > 0.0 {
+            sample.weight / sample.confidence
+        } else {
+            0.0
+        };
+        Prefix {
+            price_sum: self.price_sum + sample.weight * sample.price,
+            sample_weight_sum: self.sample_weight_sum + sample.weight,
+            weight_sum: self.weight_sum + weight,
+            weighted_price_sum: self.weighted_price_sum + weight * sample.price,
+            area: self.area,
+            weight_area: self.weight_area,
+            wide_count: self.wide_count + usize::from(sample.wide),
+            reduced_count: self.reduced_count + usize::from(sample.weight != 1.0),
+            fixed_count: self.fixed_count + usize::from(fixed.is_some()),
+            fixed_sum: self.fixed_sum + fixed.unwrap_or(0),
+            fixed_area: self.fixed_area,
+        }
+    }
+}
+
+/// Samples for one symbol alongside prefix aggregates and the gap runs
+/// between them, so any window is answered by differencing two prefixes
+/// found by binary search instead of walking its samples.
+#[derive(Debug, Default)]
+struct SampleStore {
+    samples: VecDeque<TwapSample>,
+    /// `prefix[i]` holds the totals before `samples[i]`.
+    prefix: VecDeque<Prefix>,
+    /// Totals through the newest sample.
+    total: Prefix,
+    /// Missing and synthesized runs between retained samples, oldest first.
+    gaps: VecDeque<GapInterval>,
+    /// Front pops since the prefixes were last rebased to zero.
+    popped_since_rebase: usize,
+    /// Smallest exponent among fixed-point samples; the `fixed_` totals are
+    /// mantissas at this exponent.
+    expo: Option<i32>,
+}
+
+impl SampleStore {
+    fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    fn back(&self) -> Option<&TwapSample> {
+        self.samples.back()
+    }
+
+    /// Append a sample. Timestamps must be strictly increasing.
+    fn push(&mut self, sample: TwapSample) {
+        if let Some(fixed) = sample.fixed_price {
+            match self.expo {
+                Some(expo) if expo <= fixed.expo => {}
+                Some(expo) => {
+                    let factor = pow10(expo - fixed.expo);
+                    for prefix in &mut self.prefix {
+                        *prefix = prefix.scaled(factor);
+                    }
+                    self.total = self.total.scaled(factor);
+                    self.expo = Some(fixed.expo);
+                }
+                None => self.expo = Some(fixed.expo),
+            }
+        }
+
+        let before = match self.samples.back() {
+            Some(last) => {
+                if sample.timestamp > last.timestamp + 1 {
+                    self.gaps.push_back(GapInterval {
+                        start: last.timestamp + 1,
+                        end: sample.timestamp - 1,
+                        filled: false,
+                    });
+                }
+                let secs = sample.timestamp - last.timestamp;
+                Prefix {
+                    area: self.total.area + last.weight * last.price * secs as f64,
+                    weight_area: self.total.weight_area + last.weight * secs as f64,
+                    fixed_area: self.total.fixed_area
+                        + self.mantissa(last).map_or(0, |m| m * i128::from(secs)),
+                    ..self.total
+                }
+            }
+            None => Prefix::default(),
+        };
+        if sample.filled {
+            match self.gaps.back_mut() {
+                Some(last) if last.filled && last.end + 1 == sample.timestamp => {
+                    last.end = sample.timestamp
+                }
+                _ => self.gaps.push_back(GapInterval {
+                    start: sample.timestamp,
+                    end: sample.timestamp,
+                    filled: true,
+                }),
+            }
+        }
+        self.total = before.plus(&sample, self.mantissa(&sample));
+        self.prefix.push_back(before);
+        self.samples.push_back(sample);
+    }
+
+    /// Drop samples older than `cutoff`, returning how many went.
+    fn trim_before(&mut self, cutoff: i64) -> usize {
+        let mut popped = 0;
+        while self.samples.front().is_some_and(|s| s.timestamp < cutoff) {
+            self.samples.pop_front();
+            self.prefix.pop_front();
+            popped += 1;
+        }
+        if popped == 0 {
+            return 0;
+        }
+
+        match self.samples.front() {
+            Some(front) => {
+                let front_ts = front.timestamp;
+                while self.gaps.front().is_some_and(|gap| gap.end < front_ts) {
+                    self.gaps.pop_front();
+                }
+                if let Some(gap) = self.gaps.front_mut() {
+                    gap.start = gap.start.max(front_ts);
+                }
+            }
+            None => {
+                self.gaps.clear();
+                self.total = Prefix::default();
+            }
+        }
+
+        // Rebase once as many samples have left as remain, keeping the
+        // prefixes small (and the differences exact) at amortized O(1).
+        self.popped_since_rebase += popped;
+        if self.popped_since_rebase >= self.samples.len() {
+            if let Some(&base) = self.prefix.front() {
+                for prefix in &mut self.prefix {
+                    *prefix = prefix.minus(base);
+                }
+                self.total = self.total.minus(base);
+            }
+            self.popped_since_rebase = 0;
+        }
+        popped
+    }
+
+    /// `sample`'s mantissa at the store's exponent, if it is fixed-point.
+    fn mantissa(&self, sample: &TwapSample) -> Option<i128> {
+        let fixed = sample.fixed_price?;
+        Some(i128::from(fixed.mantissa).saturating_mul(pow10(fixed.expo - self.expo?)))
+    }
+
+    /// Whether every sample in `samples[from..to]` is fixed-point at full
+    /// weight, so the exact sums apply.
+    fn all_fixed(&self, from: usize, to: usize) -> bool {
+        let totals = self.prefix_before(to).minus(self.prefix[from]);
+        totals.fixed_count == to - from && totals.reduced_count == 0
+    }
+
+    /// Kept wide samples in `(window_start, window_end]`.
+    fn wide_count(&self, window_start: i64, window_end: i64) -> usize {
+        let (from, to) = self.range(window_start, window_end);
+        if from >= to {
+            return 0;
+        }
+        self.prefix_before(to).wide_count - self.prefix[from].wide_count
+    }
+
+    /// Index one past the last sample with `timestamp <= t`.
+    fn upper_bound(&self, t: i64) -> usize {
+        self.samples.partition_point(|s| s.timestamp <= t)
+    }
+
+    fn prefix_before(&self, index: usize) -> Prefix {
+        self.prefix.get(index).copied().unwrap_or(self.total)
+    }
+
+    /// Weighted price-seconds and weight-seconds integrals from the first
+    /// sample up to `t`, where `t` is at or after the first sample.
+    fn area_at(&self, t: i64) -> (f64, f64) {
+        let index = self.upper_bound(t) - 1;
+        let sample = &self.samples[index];
+        let secs = (t - sample.timestamp) as f64;
+        (
+            self.prefix[index].area + sample.weight * sample.price * secs,
+            self.prefix[index].weight_area + sample.weight * secs,
+        )
+    }
+
+    /// Index range of the samples in `(window_start, window_end]`, the
+    /// `window_secs` seconds that make up a full window. A sample exactly at
+    /// `window_start` belongs to the previous window.
+    fn range(&self, window_start: i64, window_end: i64) -> (usize, usize) {
+        (self.upper_bound(window_start), self.upper_bound(window_end))
+    }
+
+    /// Weighted mean of `samples[from..to]`.
+    fn mean(&self, from: usize, to: usize) -> f64 {
+        let totals = self.prefix_before(to).minus(self.prefix[from]);
+        totals.price_sum / totals.sample_weight_sum
+    }
+
+    /// Inverse-confidence weighted mean of `samples[from..to]`, or `None`
+    /// when none of them carries a positive confidence.
+    fn confidence_weighted(&self, from: usize, to: usize) -> Option<f64> {
+        let totals = self.prefix_before(to).minus(self.prefix[from]);
+        (totals.weight_sum > 0.0).then(|| totals.weighted_price_sum / totals.weight_sum)
+    }
+
+    fn sorted_prices(&self, from: usize, to: usize) -> Vec<f64> {
+        let mut prices: Vec<f64> = self.samples.range(from..to).map(|s| s.price).collect();
+        prices.sort_by(f64::total_cmp);
+        prices
+    }
+
+    /// Duration-weighted average over `[window_start, window_end]`. Each
+    /// sample's price holds until the next sample (or `window_end`); the last
+    /// sample at or before `window_start` covers the leading edge. Returns
+    /// `None` when no time elapses inside the window, e.g. a single sample
+    /// exactly at `window_end`.
+    fn time_weighted(&self, window_start: i64, window_end: i64) -> Option<f64> {
+        let first = self.samples.front()?;
+        let from = window_start.max(first.timestamp);
+        let total_secs = window_end - from;
+        (total_secs > 0).then(|| {
+            let (end_area, end_weight) = self.area_at(window_end);
+            let (start_area, start_weight) = self.area_at(from);
+            (end_area - start_area) / (end_weight - start_weight)
+        })
+    }
+
+    /// The window average as an exact fraction of mantissas, `(numerator,
+    /// denominator, expo)`, mirroring the float methods above. `None` when a
+    /// contributing sample has no fixed-point price or, for the confidence
+    /// weighting, because its weights are not decimal.
+    fn exact_ratio(
+        &self,
+        method: TwapMethod,
+        window_start: i64,
+        window_end: i64,
+        from: usize,
+        to: usize,
+    ) -> Option<(i128, i128, i32)> {
+        let expo = self.expo?;
+        let ratio = match method {
+            TwapMethod::Mean => {
+                self.all_fixed(from, to).then_some(())?;
+                let sum = self.prefix_before(to).fixed_sum - self.prefix[from].fixed_sum;
+                (sum, (to - from) as i128)
+            }
+            TwapMethod::TimeWeighted => {
+                let first = self.samples.front()?;
+                let start = window_start.max(first.timestamp);
+                let total_secs = window_end - start;
+                if total_secs > 0 {
+                    self.all_fixed(self.upper_bound(start) - 1, to)
+                        .then_some(())?;
+                    let area = self.fixed_area_at(window_end) - self.fixed_area_at(start);
+                    (area, i128::from(total_secs))
+                } else {
+                    (self.mantissa(&self.samples[to - 1])?, 1)
+                }
+            }
+            TwapMethod::ConfidenceWeighted => return None,
+            TwapMethod::Median => {
+                let mantissas = self.sorted_mantissas(from, to)?;
+                let mid = mantissas.len() / 2;
+                if mantissas.len().is_multiple_of(2) {
+                    (mantissas[mid - 1] + mantissas[mid], 2)
+                } else {
+                    (mantissas[mid], 1)
+                }
+            }
+            TwapMethod::TrimmedMean { trim_pct } => {
+                let mantissas = self.sorted_mantissas(from, to)?;
+                let trim = mantissas.len() * usize::from(trim_pct) / 100;
+                let kept = &mantissas[trim..mantissas.len() - trim];
+                (kept.iter().sum(), kept.len() as i128)
+            }
+        };
+        Some((ratio.0, ratio.1, expo))
+    }
+
+    /// Mantissa-seconds integral up to `t`, like [`Self::area_at`]; callers
+    /// check the samples involved are fixed-point.
+    fn fixed_area_at(&self, t: i64) -> i128 {
+        let index = self.upper_bound(t) - 1;
+        let sample = &self.samples[index];
+        self.prefix[index].fixed_area
+            + self.mantissa(sample).unwrap_or(0) * i128::from(t - sample.timestamp)
+    }
+
+    fn sorted_mantissas(&self, from: usize, to: usize) -> Option<Vec<i128>> {
+        let mut mantissas = self
+            .samples
+            .range(from..to)
+            .map(|sample| self.mantissa(sample))
+            .collect::<Option<Vec<_>>>()?;
+        mantissas.sort_unstable();
+        Some(mantissas)
+    }
+
+    /// Most recent observed (not carried-forward) sample at or before `t`.
+    fn last_observed(&self, t: i64) -> Option<&TwapSample> {
+        self.samples
+            .range(..self.upper_bound(t))
+            .rev()
+            .find(|sample| !sample.filled)
+    }
+
+    /// Missing and synthesized seconds in `(window_start, window_end]`, the
+    /// same seconds [`Self::range`] aggregates. Adjacent seconds of
+    /// the same kind are merged into one interval.
+    fn gap_intervals(&self, window_start: i64, window_end: i64) -> Vec<GapInterval> {
+        let lo = window_start + 1;
+        let mut gaps = Vec::new();
+        if lo > window_end {
+            return gaps;
+        }
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            gaps.push(GapInterval {
+                start: lo,
+                end: window_end,
+                filled: false,
+            });
+            return gaps;
+        };
+
+        if lo < first.timestamp {
+            gaps.push(GapInterval {
+                start: lo,
+                end: window_end.min(first.timestamp - 1),
+                filled: false,
+            });
+        }
+        let skip = self.gaps.partition_point(|gap| gap.end < lo);
+        gaps.extend(
+            self.gaps
+                .range(skip..)
+                .take_while(|gap| gap.start <= window_end)
+                .map(|gap| GapInterval {
+                    start: gap.start.max(lo),
+                    end: gap.end.min(window_end),
+                    filled: gap.filled,
+                }),
+        );
+        if last.timestamp < window_end {
+            gaps.push(GapInterval {
+                start: lo.max(last.timestamp + 1),
+                end: window_end,
+                filled: false,
+            });
+        }
+        gaps
     }
 }
 
@@ -258,6 +1595,7 @@ mod tests {
             confidence: 0.01,
             publish_time: timestamp,
             feed_id: "0x123".to_string(),
+            ..Default::default()
         }
     }
 
@@ -270,6 +1608,72 @@ mod tests {
         assert_eq!(calc.sample_count("SOL"), 3);
     }
 
+    #[test]
+    fn admit_rejects_bad_publish_times() {
+        let hermes = |price, publish_time| PriceUpdate {
+            slot: Some(7),
+            prev_publish_time: Some(999),
+            ..make_update("SOL", price, publish_time)
+        };
+        let mut calc = TwapCalculator::new().with_max_future_skew(5);
+        assert_eq!(calc.admit(&hermes(200.0, 1000), 1000), Ok(true));
+        // A sub-second update is skipped quietly, as is the same print
+        // again within the second it arrived in.
+        assert_eq!(
+            calc.admit(&make_update("SOL", 201.0, 1000), 1000),
+            Ok(false)
+        );
+        assert_eq!(calc.admit(&hermes(200.0, 1000), 1000), Ok(false));
+
+        // A resend in a later second is reported once per publish time,
+        // and counted every time.
+        let duplicate = calc.admit(&hermes(200.0, 1000), 1001).unwrap_err();
+        assert_eq!(duplicate.reason, SampleRejection::Duplicate);
+        assert_eq!(calc.admit(&hermes(200.0, 1000), 1002), Ok(false));
+        let regression = calc
+            .admit(&make_update("SOL", 199.0, 999), 1001)
+            .unwrap_err();
+        assert_eq!(regression.reason, SampleRejection::Regression);
+        assert_eq!(regression.latest_time, Some(1000));
+
+        // Within the tolerance is fine; beyond it would have evicted the
+        // window and blocked every live sample until the clock caught up.
+        assert_eq!(calc.admit(&make_update("SOL", 200.0, 1006), 1001), Ok(true));
+        let future = calc
+            .admit(&make_update("SOL", 200.0, 1_000_000), 1002)
+            .unwrap_err();
+        assert_eq!(future.reason, SampleRejection::Future);
+        assert_eq!(future.received_at, 1002);
+        assert_eq!(calc.sample_count("SOL"), 2);
+
+        assert_eq!(
+            calc.rejection_counts(),
+            [(
+                "SOL".to_string(),
+                RejectionCounts {
+                    regressions: 1,
+                    duplicates: 2,
+                    future: 1
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn admit_skips_flat_sub_second_prints_quietly() {
+        // Lazer at fixed_rate@200ms: five prints a second under one
+        // publish time, identical in a flat market and with no slot.
+        let mut calc = TwapCalculator::new();
+        for now in 1000..1003 {
+            for print in 0..5 {
+                let admitted = calc.admit(&make_update("SOL", 100.0, 1000), now);
+                assert_eq!(admitted, Ok(now == 1000 && print == 0));
+            }
+        }
+        assert!(calc.rejection_counts().is_empty());
+        assert_eq!(calc.sample_count("SOL"), 1);
+    }
+
     #[test]
     fn test_sample_interval() {
         let mut calc = TwapCalculator::new();
@@ -289,6 +1693,580 @@ mod tests {
         assert!((result.twap - 204.5).abs() < 0.0001);
     }
 
+    #[test]
+    fn test_window_boundary_is_half_open() {
+        // A sample at window_start belongs to the previous window, so a full
+        // window is exactly window_secs samples and a second missing just
+        // after window_start shows up in the gap report.
+        let mut calc = TwapCalculator::with_window(10);
+        for ts in 1000..=1010 {
+            calc.record(&make_update("SOL", 100.0 + ts as f64, ts));
+        }
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.sample_count, 10);
+        assert!((result.coverage - 1.0).abs() < f64::EPSILON);
+        assert!(result.gaps.is_empty());
+        assert!((result.twap - 1105.5).abs() < 0.0001);
+
+        let mut calc = TwapCalculator::with_window(10);
+        calc.record(&make_update("SOL", 100.0, 1000));
+        for ts in 1002..=1010 {
+            calc.record(&make_update("SOL", 100.0, ts));
+        }
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.sample_count, 9);
+        assert!((result.coverage - 0.9).abs() < f64::EPSILON);
+        assert_eq!(
+            result.gaps,
+            vec![GapInterval {
+                start: 1001,
+                end: 1001,
+                filled: false
+            }]
+        );
+    }
+
+    #[test]
+    fn test_time_weighted_weights_by_duration() {
+        // 100 holds for 8s after a burst, 200 for the final 2s.
+        let mut calc = TwapCalculator::with_window(10).with_method(TwapMethod::TimeWeighted);
+        calc.record(&make_update("SOL", 100.0, 1000));
+        calc.record(&make_update("SOL", 200.0, 1008));
+        calc.record(&make_update("SOL", 200.0, 1009));
+
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.method, TwapMethod::TimeWeighted);
+        // The sample at window_start is in effect but not counted.
+        assert_eq!(result.sample_count, 2);
+        assert!((result.twap - 120.0).abs() < 0.0001);
+
+        // Same samples under the arithmetic mean overweight the burst.
+        let mut mean = TwapCalculator::with_window(10);
+        mean.record(&make_update("SOL", 100.0, 1001));
+        mean.record(&make_update("SOL", 200.0, 1008));
+        mean.record(&make_update("SOL", 200.0, 1009));
+        let result = mean.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.method, TwapMethod::Mean);
+        assert!((result.twap - 166.6667).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_time_weighted_clamps_at_window_edges() {
+        let mut calc = TwapCalculator::with_window(10).with_method(TwapMethod::TimeWeighted);
+        // Published before the window opens; in effect for its first 5s.
+        calc.record(&make_update("SOL", 100.0, 990));
+        calc.record(&make_update("SOL", 300.0, 1005));
+
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.sample_count, 1);
+        assert!((result.twap - 200.0).abs() < 0.0001);
+
+        let preview = calc.calculate_preview("SOL", 1010).unwrap();
+        assert!((preview.twap - 200.0).abs() < 0.0001);
+    }
+
+    fn make_fixed(symbol: &str, mantissa: i64, expo: i32, timestamp: i64) -> PriceUpdate {
+        let fixed = FixedPrice::new(mantissa, expo);
+        PriceUpdate {
+            fixed_price: Some(fixed),
+            ..make_update(symbol, fixed.to_f64(), timestamp)
+        }
+    }
+
+    #[test]
+    fn test_fixed_point_mean_is_exact() {
+        let mut calc = TwapCalculator::with_window(10);
+        for (i, mantissa) in [1, 2, 3].into_iter().enumerate() {
+            calc.record(&make_fixed("SOL", mantissa, -1, 1000 + i as i64));
+        }
+
+        // (0.1 + 0.2 + 0.3) / 3 is 0.20000000000000004 in floats.
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.fixed_twap, Some(FixedPrice::new(2, -1)));
+        assert_eq!(result.twap, 0.2);
+    }
+
+    #[test]
+    fn test_fixed_point_time_weighted_spans_exponents() {
+        let mut calc = TwapCalculator::with_window(20)
+            .with_method(TwapMethod::TimeWeighted)
+            .with_symbol_rounding(
+                "SOL",
+                RoundingRule {
+                    mode: RoundingMode::HalfUp,
+                    expo: Some(-3),
+                },
+            );
+        calc.record(&make_fixed("SOL", 100, -2, 1000));
+        calc.record(&make_fixed("SOL", 1005, -3, 1010));
+        calc.record(&make_fixed("BTC", 100, -2, 1000));
+        calc.record(&make_fixed("BTC", 1005, -3, 1010));
+
+        // 1.00 for 10s and 1.005 for 10s average to 1.0025.
+        let result = calc.calculate("SOL", 1020).unwrap();
+        assert_eq!(result.fixed_twap, Some(FixedPrice::new(1003, -3)));
+        // Without a rule the result keeps the finest exponent, half-even.
+        let result = calc.calculate("BTC", 1020).unwrap();
+        assert_eq!(result.fixed_twap, Some(FixedPrice::new(1002, -3)));
+    }
+
+    #[test]
+    fn test_fixed_point_needs_every_sample_fixed() {
+        let mut calc = TwapCalculator::with_window(10)
+            .with_method(TwapMethod::Median)
+            .with_rounding(RoundingRule {
+                mode: RoundingMode::HalfEven,
+                expo: Some(-2),
+            });
+        for (i, mantissa) in [10_001, 10_004, 10_010, 10_050].into_iter().enumerate() {
+            calc.record(&make_fixed("SOL", mantissa, -3, 1001 + i as i64));
+        }
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.fixed_twap, Some(FixedPrice::new(1001, -2)));
+        assert!((result.twap - 10.01).abs() < f64::EPSILON);
+
+        calc.record(&make_update("SOL", 10.02, 1005));
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.fixed_twap, None);
+        assert!((result.twap - 10.01).abs() < f64::EPSILON);
+
+        let calc = calc.with_symbol_method("SOL", TwapMethod::ConfidenceWeighted);
+        assert_eq!(calc.calculate("SOL", 1010).unwrap().fixed_twap, None);
+    }
+
+    #[test]
+    fn test_order_statistic_methods_resist_outliers() {
+        let prices = [
+            100.0, 101.0, 99.0, 100.0, 5000.0, 102.0, 98.0, 100.0, 101.0, 0.5,
+        ];
+        let record = |calc: &mut TwapCalculator| {
+            for (i, &price) in prices.iter().enumerate() {
+                calc.record(&make_update("SOL", price, 1000 + i as i64));
+            }
+        };
+
+        let mut median = TwapCalculator::with_window(10).with_method(TwapMethod::Median);
+        record(&mut median);
+        let result = median.calculate("SOL", 1009).unwrap();
+        assert_eq!(result.method, TwapMethod::Median);
+        assert!((result.twap - 100.0).abs() < 0.0001);
+
+        // 10% of 10 samples drops the single highest and lowest price.
+        let method = TwapMethod::TrimmedMean { trim_pct: 10 };
+        let mut trimmed = TwapCalculator::with_window(10).with_method(method);
+        record(&mut trimmed);
+        let result = trimmed.calculate("SOL", 1009).unwrap();
+        assert_eq!(result.method, method);
+        assert!((result.twap - 100.125).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_confidence_weighted_favors_tight_quotes() {
+        let mut calc = TwapCalculator::with_window(10).with_method(TwapMethod::ConfidenceWeighted);
+        let mut update = make_update("SOL", 100.0, 1000);
+        update.confidence = 0.1;
+        calc.record(&update);
+        let mut update = make_update("SOL", 110.0, 1001);
+        update.confidence = 0.9;
+        calc.record(&update);
+
+        // Weights 10 and 10/9: (1000 + 122.2) / 11.1
+        let result = calc.calculate("SOL", 1001).unwrap();
+        assert!((result.twap - 101.0).abs() < 0.0001);
+    }
+
+    fn make_wide(symbol: &str, price: f64, timestamp: i64) -> PriceUpdate {
+        PriceUpdate {
+            confidence: price * 0.05,
+            ..make_update(symbol, price, timestamp)
+        }
+    }
+
+    #[test]
+    fn test_confidence_policy_drops_wide_quotes() {
+        let mut calc = TwapCalculator::with_window(10).with_confidence_policy(
+            "SOL",
+            ConfidencePolicy {
+                max_ratio: 0.01,
+                action: ConfidenceAction::Drop,
+            },
+        );
+        assert!(calc.record(&make_update("SOL", 100.0, 1000)));
+        assert!(!calc.record(&make_wide("SOL", 200.0, 1001)));
+        // The second stays open for a tight quote.
+        assert!(calc.record(&make_update("SOL", 101.0, 1001)));
+        assert!(!calc.record(&make_wide("SOL", 200.0, 1002)));
+        assert!(!calc.record(&make_wide("SOL", 200.0, 1002)));
+        assert!(calc.record(&make_update("SOL", 103.0, 1003)));
+        // Other symbols are unguarded.
+        assert!(calc.record(&make_wide("BTC", 200.0, 1000)));
+
+        let result = calc.calculate("SOL", 1003).unwrap();
+        assert_eq!(result.sample_count, 3);
+        assert!((result.twap - 304.0 / 3.0).abs() < 1e-9);
+        assert_eq!(result.rejected_samples, 2);
+        assert_eq!(result.flagged_samples, 0);
+        assert_eq!(calc.calculate("SOL", 1001).unwrap().rejected_samples, 1);
+        assert_eq!(
+            calc.confidence_counts(),
+            [(
+                "SOL".to_string(),
+                ConfidenceCounts {
+                    rejected: 2,
+                    flagged: 0
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_carried_wide_samples_stay_flagged() {
+        let mut calc = TwapCalculator::with_window(10)
+            .with_gap_policy(GapPolicy::CarryForward { max_gap_secs: 5 })
+            .with_confidence_policy(
+                "SOL",
+                ConfidencePolicy {
+                    max_ratio: 0.01,
+                    action: ConfidenceAction::DownWeight { weight: 0.5 },
+                },
+            );
+        calc.record(&make_update("SOL", 100.0, 1000));
+        calc.record(&make_wide("SOL", 130.0, 1001));
+        calc.record(&make_update("SOL", 100.0, 1004));
+
+        // 1002 and 1003 carry the wide quote at its reduced weight, so they
+        // count as flagged too.
+        let result = calc.calculate("SOL", 1004).unwrap();
+        assert_eq!(result.sample_count, 5);
+        assert_eq!(result.flagged_samples, 3);
+        // (100 + 3 * 0.5 * 130 + 100) / (1 + 3 * 0.5 + 1)
+        assert!((result.twap - 395.0 / 3.5).abs() < 1e-9);
+        assert_eq!(calc.confidence_counts()[0].1.flagged, 1);
+    }
+
+    #[test]
+    fn test_clear_forgets_counts_and_ema() {
+        let mut calc = TwapCalculator::with_window(10);
+        calc.record(&PriceUpdate {
+            ema_price: Some(99.0),
+            ..make_update("SOL", 100.0, 1000)
+        });
+        assert!(calc.admit(&make_update("SOL", 100.0, 999), 1000).is_err());
+        assert_eq!(calc.rejection_counts()[0].1.regressions, 1);
+
+        calc.clear("SOL");
+        assert_eq!(calc.sample_count("SOL"), 0);
+        assert_eq!(calc.latest_ema("SOL"), None);
+        assert!(calc.rejection_counts().is_empty());
+        assert!(calc.confidence_counts().is_empty());
+    }
+
+    #[test]
+    fn test_update_data_is_kept_once_across_feeds() {
+        let mut calc = TwapCalculator::with_retention(10, 1);
+        let data = |t: i64| {
+            Arc::new(UpdateData {
+                id: format!("id-{t}"),
+                data: vec![format!("504e4155{t:016x}")],
+            })
+        };
+        for t in [1000, 1001] {
+            let shared = data(t);
+            for symbol in ["SOL", "BTC", "ETH"] {
+                assert!(calc.record(&PriceUpdate {
+                    update_data: Some(Arc::clone(&shared)),
+                    ..make_update(symbol, 100.0, t)
+                }));
+            }
+            // One copy in the calculator, one here, whatever the feed count.
+            assert_eq!(Arc::strong_count(&shared), 2);
+        }
+        assert_eq!(calc.update_data.len(), 2);
+        let samples = calc.snapshot_samples("ETH").unwrap();
+        assert_eq!(samples[1].update_id.as_deref(), Some("id-1001"));
+        assert_eq!(calc.update_data(1001, "id-1001").unwrap().id, "id-1001");
+
+        // Retention drops the update once no feed holds a sample that old.
+        for symbol in ["SOL", "BTC"] {
+            calc.record(&make_update(symbol, 100.0, 1015));
+        }
+        assert!(calc.update_data(1000, "id-1000").is_some());
+        calc.record(&make_update("ETH", 100.0, 1015));
+        assert_eq!(calc.update_data.len(), 0);
+    }
+
+    #[test]
+    fn test_confidence_policy_flags_or_down_weights_wide_quotes() {
+        let policy = |action| ConfidencePolicy {
+            max_ratio: 0.01,
+            action,
+        };
+        let mut flagged = TwapCalculator::with_window(10)
+            .with_confidence_policy("SOL", policy(ConfidenceAction::Flag));
+        let mut weighted = TwapCalculator::with_window(10)
+            .with_method(TwapMethod::TimeWeighted)
+            .with_confidence_policy("SOL", policy(ConfidenceAction::DownWeight { weight: 0.5 }));
+        for calc in [&mut flagged, &mut weighted] {
+            calc.record(&make_fixed("SOL", 10000, -2, 1000));
+            let wide = make_fixed("SOL", 13000, -2, 1001);
+            assert!(calc.record(&PriceUpdate {
+                confidence: 6.5,
+                ..wide
+            }));
+        }
+
+        let result = flagged.calculate("SOL", 1002).unwrap();
+        assert_eq!(result.fixed_twap, Some(FixedPrice::new(11500, -2)));
+        assert_eq!(result.flagged_samples, 1);
+        assert_eq!(result.rejected_samples, 0);
+
+        // 100 for one second at weight 1, then 130 for one at weight 0.5.
+        let result = weighted.calculate("SOL", 1002).unwrap();
+        assert!((result.twap - 110.0).abs() < 1e-9);
+        assert_eq!(result.fixed_twap, None);
+        assert_eq!(result.flagged_samples, 1);
+        assert_eq!(weighted.confidence_counts()[0].1.flagged, 1);
+    }
+
+    #[test]
+    fn test_symbol_method_overrides_default() {
+        let mut calc =
+            TwapCalculator::with_window(10).with_symbol_method("BTC", TwapMethod::Median);
+        for (i, price) in [1.0, 2.0, 9.0].into_iter().enumerate() {
+            calc.record(&make_update("SOL", price, 1000 + i as i64));
+            calc.record(&make_update("BTC", price, 1000 + i as i64));
+        }
+
+        assert_eq!(calc.method_for("SOL"), TwapMethod::Mean);
+        let sol = calc.calculate("SOL", 1002).unwrap();
+        assert_eq!(sol.method, TwapMethod::Mean);
+        assert!((sol.twap - 4.0).abs() < 0.0001);
+        let btc = calc.calculate("BTC", 1002).unwrap();
+        assert_eq!(btc.method, TwapMethod::Median);
+        assert!((btc.twap - 2.0).abs() < 0.0001);
+    }
+
+    #[test]
+    #[should_panic(expected = "trim_pct")]
+    fn test_trimmed_mean_rejects_half_trim() {
+        let _ = TwapCalculator::new().with_method(TwapMethod::TrimmedMean { trim_pct: 50 });
+    }
+
+    #[test]
+    fn test_gap_policy_leave_reports_missing_seconds() {
+        let mut calc = TwapCalculator::with_window(10);
+        for ts in [1001, 1002, 1006, 1007, 1008] {
+            calc.record(&make_update("SOL", 200.0, ts));
+        }
+
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.sample_count, 5);
+        assert_eq!(
+            result.gaps,
+            vec![
+                GapInterval {
+                    start: 1003,
+                    end: 1005,
+                    filled: false
+                },
+                GapInterval {
+                    start: 1009,
+                    end: 1010,
+                    filled: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gap_policy_carry_forward_fills_short_gaps() {
+        let mut calc = TwapCalculator::with_window(10)
+            .with_gap_policy(GapPolicy::CarryForward { max_gap_secs: 3 });
+        calc.record(&make_update("SOL", 100.0, 1001));
+        calc.record(&make_update("SOL", 200.0, 1004));
+        // Six missing seconds exceed the limit and stay empty.
+        calc.record(&make_update("SOL", 300.0, 1010));
+
+        assert_eq!(calc.sample_count("SOL"), 5);
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.sample_count, 5);
+        assert!((result.twap - 160.0).abs() < 0.0001);
+        assert_eq!(
+            result.gaps,
+            vec![
+                GapInterval {
+                    start: 1002,
+                    end: 1003,
+                    filled: true
+                },
+                GapInterval {
+                    start: 1005,
+                    end: 1009,
+                    filled: false
+                },
+            ]
+        );
+
+        let samples = calc.snapshot_samples("SOL").unwrap();
+        assert!(samples[1].filled && samples[2].filled);
+        assert!(!samples[3].filled);
+    }
+
+    #[test]
+    fn test_gap_policy_invalidate_rejects_incomplete_window() {
+        let mut calc = TwapCalculator::with_window(10).with_gap_policy(GapPolicy::Invalidate);
+        for ts in 1001..=1010 {
+            calc.record(&make_update("SOL", 200.0, ts));
+        }
+        assert!(calc.calculate("SOL", 1010).unwrap().gaps.is_empty());
+
+        assert!(calc.calculate("SOL", 1012).is_none());
+        assert_eq!(
+            calc.gaps("SOL", 1012),
+            vec![GapInterval {
+                start: 1011,
+                end: 1012,
+                filled: false
+            }]
+        );
+    }
+
+    #[test]
+    fn test_windows_share_one_sample_store() {
+        let mut calc = TwapCalculator::with_windows(
+            vec![TwapWindow::new("10s", 10), TwapWindow::new("3s", 3)],
+            DEFAULT_RETENTION_WINDOWS,
+        );
+        for i in 0..30 {
+            calc.record(&make_update("SOL", 200.0 + i as f64, 1000 + i));
+        }
+
+        // Retention follows the longest window: two 10s windows plus one.
+        assert_eq!(calc.sample_count("SOL"), 21);
+
+        let long = calc.calculate("SOL", 1029).unwrap();
+        assert_eq!(long.window, "10s");
+        assert_eq!(long.sample_count, 10);
+        assert!((long.twap - 224.5).abs() < 0.0001);
+
+        let short = calc.calculate_window("SOL", "3s", 1029).unwrap();
+        assert_eq!(short.window, "3s");
+        assert_eq!(short.sample_count, 3);
+        assert!((short.twap - 228.0).abs() < 0.0001);
+        assert!(calc.calculate_window("SOL", "1h", 1029).is_none());
+
+        let previews = calc.calculate_previews("SOL", 1029);
+        assert_eq!(previews.len(), 2);
+        assert_eq!(previews[0].window, "10s");
+        assert_eq!(previews[1].window, "3s");
+        assert!((previews[1].coverage - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_window_names_from_secs() {
+        assert_eq!(TwapWindow::from_secs(60).name, "1m");
+        assert_eq!(TwapWindow::from_secs(DEFAULT_TWAP_WINDOW_SECS).name, "30m");
+        assert_eq!(TwapWindow::from_secs(3600).name, "1h");
+        assert_eq!(TwapWindow::from_secs(90).name, "90s");
+    }
+
+    fn sparse_calc(policy: CoveragePolicy) -> TwapCalculator {
+        // Full coverage in 1000..=1010, then only two samples in 1011..=1020,
+        // so the window ending at 1020 holds two samples.
+        let mut calc = TwapCalculator::with_window(10).with_coverage_policy("SOL", policy);
+        for ts in 1000..=1010 {
+            calc.record(&make_update("SOL", 100.0, ts));
+        }
+        calc.record(&make_update("SOL", 110.0, 1015));
+        calc.record(&make_update("SOL", 120.0, 1017));
+        calc
+    }
+
+    #[test]
+    fn test_coverage_policy_accepts_window_above_minimum() {
+        let calc = sparse_calc(CoveragePolicy {
+            min_coverage: 0.9,
+            fallbacks: vec![SettlementFallback::Refuse],
+        });
+        let result = calc.calculate("SOL", 1010).unwrap();
+        assert_eq!(result.rung, SettlementRung::Window);
+    }
+
+    #[test]
+    fn test_coverage_policy_refuses_below_minimum() {
+        let calc = sparse_calc(CoveragePolicy {
+            min_coverage: 0.9,
+            fallbacks: vec![
+                SettlementFallback::Refuse,
+                SettlementFallback::LastGoodPrice,
+            ],
+        });
+        assert!(calc.calculate("SOL", 1020).is_none());
+
+        // Without a policy the same window settles as before.
+        let mut plain = TwapCalculator::with_window(10);
+        plain.record(&make_update("SOL", 110.0, 1015));
+        assert_eq!(plain.calculate("SOL", 1020).unwrap().sample_count, 1);
+    }
+
+    #[test]
+    fn test_coverage_fallback_extends_window() {
+        let calc = sparse_calc(CoveragePolicy {
+            min_coverage: 0.7,
+            fallbacks: vec![
+                SettlementFallback::ExtendWindow { max_extra_secs: 2 },
+                SettlementFallback::ExtendWindow { max_extra_secs: 10 },
+            ],
+        });
+        let result = calc.calculate("SOL", 1020).unwrap();
+        assert_eq!(result.rung, SettlementRung::ExtendedWindow);
+        assert_eq!(result.window_start, 1000);
+        // 1001..=1010 plus the two later samples.
+        assert_eq!(result.sample_count, 12);
+    }
+
+    #[test]
+    fn test_coverage_fallback_uses_ema_then_last_good_price() {
+        let mut calc = sparse_calc(CoveragePolicy {
+            min_coverage: 0.9,
+            fallbacks: vec![
+                SettlementFallback::EmaPrice,
+                SettlementFallback::LastGoodPrice,
+            ],
+        });
+
+        let result = calc.calculate("SOL", 1020).unwrap();
+        assert_eq!(result.rung, SettlementRung::LastGoodPrice);
+        assert!((result.twap - 120.0).abs() < f64::EPSILON);
+        assert_eq!(result.sample_count, 2);
+
+        calc.record_ema("SOL", 104.0, 1012);
+        // Published after window_end, so it must not be used.
+        calc.record_ema("SOL", 150.0, 1021);
+        let result = calc.calculate("SOL", 1020).unwrap();
+        assert_eq!(result.rung, SettlementRung::EmaPrice);
+        assert!((result.twap - 104.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_record_keeps_ema_from_updates() {
+        let mut calc = sparse_calc(CoveragePolicy {
+            min_coverage: 0.9,
+            fallbacks: vec![SettlementFallback::EmaPrice],
+        });
+        assert_eq!(calc.latest_ema("SOL"), None);
+
+        calc.record(&PriceUpdate {
+            ema_price: Some(104.0),
+            ema_confidence: Some(0.2),
+            ..make_update("SOL", 121.0, 1019)
+        });
+        assert_eq!(calc.latest_ema("SOL"), Some((1019, 104.0)));
+        let result = calc.calculate("SOL", 1020).unwrap();
+        assert_eq!(result.rung, SettlementRung::EmaPrice);
+        assert!((result.twap - 104.0).abs() < f64::EPSILON);
+    }
+
     #[test]
     fn test_prune_old_samples() {
         let mut calc = TwapCalculator::new();
@@ -316,11 +2294,94 @@ mod tests {
         assert_eq!(calc.sample_count("SOL"), 21);
 
         let result = calc.calculate("SOL", 1099).unwrap();
-        assert_eq!(result.sample_count, 11);
+        assert_eq!(result.sample_count, 10);
 
         // window_end one window in the past still sits inside retention.
         let past = calc.calculate("SOL", 1089).unwrap();
-        assert_eq!(past.sample_count, 11);
+        assert_eq!(past.sample_count, 10);
+    }
+
+    #[test]
+    fn test_prefix_aggregates_match_rescan_after_trimming() {
+        // Irregular prices and gaps over many retention horizons, so the
+        // prefixes are trimmed and rebased repeatedly.
+        let mut mean = TwapCalculator::with_window(30);
+        let mut weighted = TwapCalculator::with_window(30).with_method(TwapMethod::TimeWeighted);
+        let mut timestamp = 1000;
+        for i in 0..2000 {
+            timestamp += 1 + (i % 7) / 5;
+            let update = make_update("BTC", 60_000.0 + ((i * 37) % 101) as f64, timestamp);
+            mean.record(&update);
+            weighted.record(&update);
+        }
+
+        let samples = mean.snapshot_samples("BTC").unwrap();
+        for window_end in [timestamp, timestamp - 7, timestamp - 29] {
+            let window_start = window_end - 30;
+            let in_window: Vec<&TwapSample> = samples
+                .iter()
+                .filter(|s| s.timestamp > window_start && s.timestamp <= window_end)
+                .collect();
+            let expected_mean =
+                in_window.iter().map(|s| s.price).sum::<f64>() / in_window.len() as f64;
+
+            let mut area = 0.0;
+            for second in window_start..window_end {
+                let holding = samples.iter().rev().find(|s| s.timestamp <= second);
+                area += holding.map_or(0.0, |s| s.price);
+            }
+            let expected_weighted = area / 30.0;
+
+            let result = mean.calculate("BTC", window_end).unwrap();
+            assert_eq!(result.sample_count, in_window.len());
+            assert!((result.twap - expected_mean).abs() < 1e-6);
+            let result = weighted.calculate("BTC", window_end).unwrap();
+            assert!((result.twap - expected_weighted).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_gap_report_survives_trimming() {
+        let mut calc = TwapCalculator::with_window(10)
+            .with_gap_policy(GapPolicy::CarryForward { max_gap_secs: 3 });
+        calc.record(&make_update("SOL", 100.0, 1000));
+        calc.record(&make_update("SOL", 101.0, 1004)); // fills 1001..=1003
+        calc.record(&make_update("SOL", 102.0, 1015)); // leaves 1005..=1014
+        calc.record(&make_update("SOL", 103.0, 1022)); // trims before 1002
+
+        assert_eq!(calc.sample_count("SOL"), 5);
+        let gaps = calc.gaps("SOL", 1012);
+        assert_eq!(
+            gaps,
+            vec![
+                GapInterval {
+                    start: 1003,
+                    end: 1003,
+                    filled: true
+                },
+                GapInterval {
+                    start: 1005,
+                    end: 1012,
+                    filled: false
+                },
+            ]
+        );
+        let gaps = calc.gaps("SOL", 1024);
+        assert_eq!(
+            gaps,
+            vec![
+                GapInterval {
+                    start: 1016,
+                    end: 1021,
+                    filled: false
+                },
+                GapInterval {
+                    start: 1023,
+                    end: 1024,
+                    filled: false
+                },
+            ]
+        );
     }
 
     #[test]
@@ -334,7 +2395,7 @@ mod tests {
         assert_eq!(calc.sample_count("SOL"), 51);
 
         let past = calc.calculate("SOL", 1059).unwrap();
-        assert_eq!(past.sample_count, 11);
+        assert_eq!(past.sample_count, 10);
     }
 
     #[test]
@@ -3,7 +3,8 @@
 //! Wire-format types (`PriceUpdate`, `TwapPreview`, `TwapResult`, `WirePayload`,
 //! `BroadcastFrame`) live in the `joyride-oracle-wire` crate and are re-exported
 //! at the `joyride_oracle_core` crate root. This module holds the in-process
-//! domain vocabulary: [`OracleEvent`] and [`Asset`].
+//! domain vocabulary: [`OracleEvent`]. Tracked assets are configuration and
+//! live in [`crate::assets`].
 //!
 //! [`OracleEvent`] intentionally does *not* have a `Heartbeat` variant:
 //! heartbeats are a WebSocket transport concern and never flow through the
@@ -13,9 +14,9 @@
 use serde::{Deserialize, Serialize};
 
 /// Events produced by the oracle's in-process pipeline (Pyth ingestion,
-/// TWAP calculator, upstream connection status). Fanned out over the
-/// internal broadcast channel to the WebSocket server and to any in-process
-/// consumer.
+/// TWAP calculator, settlement scheduler, upstream connection status).
+/// Fanned out over the internal broadcast channel to the WebSocket server
+/// and to any in-process consumer.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum OracleEvent {
@@ -25,6 +26,10 @@ pub enum OracleEvent {
     /// Rolling TWAP preview (every few seconds).
     TwapPreview(joyride_oracle_wire::TwapPreview),
 
+    /// Settlement TWAP for one asset, emitted by the settlement scheduler at
+    /// the asset's expiry.
+    Settlement(joyride_oracle_wire::TwapResult),
+
     /// Upstream Pyth connection established.
     Connected,
 
@@ -33,53 +38,21 @@ pub enum OracleEvent {
 
     /// An error occurred on the upstream connection.
     Error { message: String },
-}
-
-/// Supported assets and their Pyth feed IDs.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Asset {
-    Sol,
-    Btc,
-    Eth,
-}
-
-impl Asset {
-    /// Returns the Pyth price feed ID for this asset.
-    pub fn feed_id(&self) -> &'static str {
-        match self {
-            Asset::Sol => "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d",
-            Asset::Btc => "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43",
-            Asset::Eth => "0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace",
-        }
-    }
 
-    /// Returns the symbol string for this asset.
-    pub fn symbol(&self) -> &'static str {
-        match self {
-            Asset::Sol => "SOL",
-            Asset::Btc => "BTC",
-            Asset::Eth => "ETH",
-        }
-    }
+    /// A price source strayed from the median of all sources, raised by
+    /// the `PriceAggregator`.
+    DeviationAlarm(joyride_oracle_wire::DeviationAlarm),
 
-    /// Parse an asset from its feed ID.
-    pub fn from_feed_id(feed_id: &str) -> Option<Self> {
-        match feed_id {
-            id if id == Asset::Sol.feed_id() => Some(Asset::Sol),
-            id if id == Asset::Btc.feed_id() => Some(Asset::Btc),
-            id if id == Asset::Eth.feed_id() => Some(Asset::Eth),
-            _ => None,
-        }
-    }
+    /// An asset's feed changed health state, raised by the
+    /// `FeedHealthMonitor`.
+    FeedHealth(joyride_oracle_wire::FeedHealthChange),
 
-    /// Returns all supported assets.
-    pub fn all() -> &'static [Asset] {
-        &[Asset::Sol, Asset::Btc, Asset::Eth]
-    }
-}
+    /// A price print was quarantined by the `SpikeFilter`, or its
+    /// quarantine resolved.
+    PriceQuarantine(joyride_oracle_wire::PriceQuarantine),
 
-impl std::fmt::Display for Asset {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.symbol())
-    }
+    /// The `TwapCalculator` refused a price for a publish time that went
+    /// backwards, resent the newest sample's print, or ran ahead of the
+    /// clock.
+    SampleRejected(joyride_oracle_wire::SampleRejected),
 }
@@ -14,16 +14,127 @@
 //! [`OracleEvent`]: https://docs.rs/joyride-oracle-core
 //! [`Asset`]: https://docs.rs/joyride-oracle-core
 
+use std::sync::Arc;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// An exact decimal price, `mantissa * 10^expo`, in the form Pyth publishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FixedPrice {
+    pub mantissa: i64,
+    pub expo: i32,
+}
+
+impl FixedPrice {
+    pub fn new(mantissa: i64, expo: i32) -> Self {
+        Self { mantissa, expo }
+    }
+
+    /// The nearest `f64`. Negative exponents divide by an exact power of
+    /// ten, so a price published as `123, -2` becomes the literal `1.23`.
+    pub fn to_f64(self) -> f64 {
+        if (-22..0).contains(&self.expo) {
+            self.mantissa as f64 / 10f64.powi(-self.expo)
+        } else {
+            self.mantissa as f64 * 10f64.powi(self.expo)
+        }
+    }
+
+    /// This price at exponent `expo`, rounded by `mode` when digits are
+    /// dropped. `None` if the mantissa would overflow.
+    pub fn rescale(self, expo: i32, mode: RoundingMode) -> Option<Self> {
+        Self::from_ratio(i128::from(self.mantissa), 1, self.expo, expo, mode)
+    }
+
+    /// The exact quotient `numerator / denominator * 10^numerator_expo` at
+    /// exponent `expo`, rounded by `mode`. `None` if `denominator` is not
+    /// positive or the result does not fit.
+    pub fn from_ratio(
+        numerator: i128,
+        denominator: i128,
+        numerator_expo: i32,
+        expo: i32,
+        mode: RoundingMode,
+    ) -> Option<Self> {
+        if denominator <= 0 {
+            return None;
+        }
+        let shift = numerator_expo.checked_sub(expo)?;
+        let (numerator, denominator) = if shift >= 0 {
+            (numerator.checked_mul(pow10(shift)?)?, denominator)
+        } else {
+            (numerator, denominator.checked_mul(pow10(-shift)?)?)
+        };
+        let mantissa = mode.divide(numerator, denominator);
+        Some(Self::new(i64::try_from(mantissa).ok()?, expo))
+    }
+}
+
+fn pow10(exponent: i32) -> Option<i128> {
+    10i128.checked_pow(u32::try_from(exponent).ok()?)
+}
+
+/// How an exact quotient is rounded to the last kept digit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Nearest, ties to the even digit (banker's rounding).
+    #[default]
+    HalfEven,
+    /// Nearest, ties away from zero.
+    HalfUp,
+    /// Toward zero.
+    Down,
+    /// Toward negative infinity.
+    Floor,
+    /// Toward positive infinity.
+    Ceiling,
+}
+
+impl RoundingMode {
+    /// `numerator / denominator` rounded by this mode; `denominator > 0`.
+    pub fn divide(self, numerator: i128, denominator: i128) -> i128 {
+        let floor = numerator.div_euclid(denominator);
+        let remainder = numerator.rem_euclid(denominator);
+        if remainder == 0 {
+            return floor;
+        }
+        let twice = remainder * 2;
+        match self {
+            RoundingMode::Floor => floor,
+            RoundingMode::Ceiling => floor + 1,
+            RoundingMode::Down if numerator < 0 => floor + 1,
+            RoundingMode::Down => floor,
+            _ if twice < denominator => floor,
+            _ if twice > denominator => floor + 1,
+            RoundingMode::HalfUp if numerator < 0 => floor,
+            RoundingMode::HalfUp => floor + 1,
+            RoundingMode::HalfEven => floor + floor.rem_euclid(2),
+        }
+    }
+}
+
+/// A signed Pyth price update (Hermes accumulator update data), ready to
+/// post to Pyth's on-chain receiver. One update covers every feed of the
+/// Hermes response it arrived in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateData {
+    /// Hex-encoded SHA-256 of `data`, by which TWAP samples, journal lines
+    /// and settlement proofs refer to the update.
+    pub id: String,
+
+    /// The update, hex-encoded.
+    pub data: Vec<String>,
+}
+
 /// A price update from Pyth.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PriceUpdate {
     /// The asset symbol (e.g., "SOL", "BTC", "ETH")
     pub symbol: String,
 
-    /// Price in USD (as f64 for simplicity; production may use fixed-point)
+    /// Price in USD. Derived from `fixed_price` when the source publishes one.
     pub price: f64,
 
     /// Confidence interval (+/- this amount)
@@ -34,6 +145,208 @@ pub struct PriceUpdate {
 
     /// The Pyth feed ID (hex string)
     pub feed_id: String,
+
+    /// The exact price as published (Pyth's integer price and exponent).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_price: Option<FixedPrice>,
+
+    /// Pyth's exponentially weighted moving average price, published
+    /// alongside the spot price. Absent for sources without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ema_price: Option<f64>,
+
+    /// Confidence interval of `ema_price`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ema_confidence: Option<f64>,
+
+    /// Fetched after a reconnect to fill the seconds the stream missed,
+    /// rather than received live. Omitted from JSON when false.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub backfilled: bool,
+
+    /// Names of the price sources (e.g. `hermes`, `lazer`) combined into
+    /// this price when the oracle aggregates several. Omitted from JSON for
+    /// a single source.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+
+    /// Pythnet slot the price was aggregated in, from Hermes metadata.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+
+    /// Publish time of the feed's previous price (Unix seconds), from
+    /// Hermes metadata. A value newer than the last price seen means
+    /// updates were missed in between.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_publish_time: Option<i64>,
+
+    /// Signed Pyth update this price came from. Every price parsed from one
+    /// Hermes response shares the same allocation. Absent for sources
+    /// without signed data and for aggregated prices; omitted from JSON
+    /// when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_data: Option<Arc<UpdateData>>,
+}
+
+/// One price source strayed from the median of all sources for an asset by
+/// more than the aggregator's threshold. Raised when the source crosses the
+/// threshold, not again until it has come back within it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviationAlarm {
+    /// The asset symbol
+    pub symbol: String,
+
+    /// Name of the deviating source
+    pub source: String,
+
+    /// The source's price
+    pub price: f64,
+
+    /// Median of every fresh source's price, this one included
+    pub median: f64,
+
+    /// `|price - median| / median`
+    pub deviation: f64,
+
+    /// Relative deviation the aggregator alarms above
+    pub threshold: f64,
+
+    /// Publish time of the deviating price (Unix seconds)
+    pub publish_time: i64,
+}
+
+/// How current an asset's price is, worst last.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedHealth {
+    /// Publish times are advancing and recent.
+    Fresh,
+    /// Updates lag or repeat their publish time, but the price is still
+    /// recent enough to use.
+    Degraded,
+    /// The newest price is too old to quote against. Also the state of an
+    /// asset that has had no price yet.
+    #[default]
+    Stale,
+    /// No new price for long enough that the feed looks halted, e.g. a
+    /// market closure.
+    Halted,
+}
+
+/// An asset's feed moved from one [`FeedHealth`] state to another. Sent on
+/// every transition, so consumers can stop quoting an asset the moment it
+/// leaves `fresh`/`degraded` and resume on the way back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedHealthChange {
+    /// The asset symbol
+    pub symbol: String,
+
+    /// The new state
+    pub state: FeedHealth,
+
+    /// The state before this transition
+    pub previous: FeedHealth,
+
+    /// Newest publish time seen for the asset (Unix seconds), if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_time: Option<i64>,
+
+    /// Seconds between `publish_time` and the moment of the transition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<i64>,
+}
+
+/// Where a [`PriceQuarantine`] episode stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuarantineStatus {
+    /// A print moved beyond the asset's band and is being held back.
+    Quarantined,
+    /// The following prints confirmed the move; the held prints were
+    /// released in order.
+    Released,
+    /// The price came back within the band; the held prints were dropped.
+    Discarded,
+}
+
+/// A price print held back by the spike filter for moving further than the
+/// asset's band allows. Sent when the print is quarantined and again when
+/// the episode is resolved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceQuarantine {
+    /// The asset symbol
+    pub symbol: String,
+
+    /// What happened to the held prints
+    pub status: QuarantineStatus,
+
+    /// The first quarantined price
+    pub price: f64,
+
+    /// Publish time of the first quarantined price (Unix seconds)
+    pub publish_time: i64,
+
+    /// Last accepted price the move was measured from
+    pub reference_price: f64,
+
+    /// Publish time of the reference price (Unix seconds)
+    pub reference_time: i64,
+
+    /// `|price - reference_price| / reference_price`
+    pub deviation: f64,
+
+    /// Largest relative move the band allowed over that interval; absent
+    /// when only the EMA check was in force
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub band: Option<f64>,
+
+    /// The print's distance from its own EMA price, when that tripped the
+    /// filter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ema_deviation: Option<f64>,
+
+    /// Prints held in this episode, the first included
+    pub held: usize,
+}
+
+/// Why the TWAP calculator turned a price away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleRejection {
+    /// Published before the asset's newest sample.
+    Regression,
+    /// A resend of the newest sample's print: the same publish time, slot
+    /// and previous publish time, received in a later second.
+    Duplicate,
+    /// Published further ahead of the oracle's clock than the skew
+    /// tolerance.
+    Future,
+}
+
+/// A price the TWAP calculator refused to sample because of its publish
+/// time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleRejected {
+    /// The asset symbol
+    pub symbol: String,
+
+    /// What was wrong with the publish time
+    pub reason: SampleRejection,
+
+    /// The rejected price
+    pub price: f64,
+
+    /// Publish time of the rejected price (Unix seconds)
+    pub publish_time: i64,
+
+    /// Publish time of the asset's newest sample, if any (Unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_time: Option<i64>,
+
+    /// The oracle's clock when the price arrived (Unix seconds)
+    pub received_at: i64,
 }
 
 /// Rolling TWAP preview (what settlement price would be if it happened now).
@@ -42,7 +355,12 @@ pub struct TwapPreview {
     /// The asset symbol
     pub symbol: String,
 
-    /// Rolling 30-minute TWAP price
+    /// Name of the rolling window this preview covers (e.g. "30m"). The
+    /// oracle publishes one preview per configured window per asset.
+    #[serde(default)]
+    pub window: String,
+
+    /// Rolling TWAP price over `window`
     pub twap: f64,
 
     /// Number of samples in the current window
@@ -52,10 +370,139 @@ pub struct TwapPreview {
     pub coverage: f64,
 }
 
+/// A run of consecutive seconds inside a TWAP window with no observed sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GapInterval {
+    /// First missing second (Unix timestamp, inclusive).
+    pub start: i64,
+    /// Last missing second (Unix timestamp, inclusive).
+    pub end: i64,
+    /// `true` when these seconds were synthesized by carry-forward, `false`
+    /// when they are simply absent from the window.
+    pub filled: bool,
+}
+
+impl GapInterval {
+    /// Number of seconds covered by this interval.
+    pub fn len_secs(&self) -> i64 {
+        self.end - self.start + 1
+    }
+}
+
+/// How the samples inside a window are averaged into a single price.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TwapMethod {
+    /// Arithmetic mean of the samples that landed in the window. Every
+    /// sample counts once, however long its price was actually in effect.
+    #[default]
+    Mean,
+
+    /// Each price is weighted by how long it was in effect, i.e. until the
+    /// next sample, clamped at the window edges. The price in effect at
+    /// `window_start` is the last sample published at or before it.
+    TimeWeighted,
+
+    /// Median of the window's samples; the mean of the two middle prices
+    /// when the count is even.
+    Median,
+
+    /// Arithmetic mean after dropping the highest and lowest `trim_pct`
+    /// percent of the window's samples (rounded down), each side.
+    TrimmedMean { trim_pct: u8 },
+
+    /// Mean weighted by the inverse of each sample's Pyth confidence
+    /// interval, so tightly quoted prices count for more. Samples without a
+    /// positive confidence carry no weight.
+    ConfidenceWeighted,
+}
+
+/// Which rung of the settlement fallback chain produced a [`TwapResult`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementRung {
+    /// The TWAP over the requested window, which met its coverage minimum
+    #[default]
+    Window,
+
+    /// The TWAP over a window extended backwards to gather enough samples
+    ExtendedWindow,
+
+    /// The Pyth EMA price published inside the window
+    EmaPrice,
+
+    /// The most recent observed price at or before `window_end`
+    LastGoodPrice,
+}
+
+/// A completed TWAP calculation over a closed window.
+///
+/// Produced by `TwapCalculator::calculate` in the core crate. On the wire it
+/// appears as the body of a `settlement` frame, emitted once per asset at
+/// each scheduled expiry; embedders can also calculate it themselves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TwapResult {
+    /// The asset this TWAP is for.
+    pub symbol: String,
+
+    /// Name of the window this TWAP was computed over (e.g. `"30m"`).
+    #[serde(default)]
+    pub window: String,
+
+    /// The calculated TWAP price. Equal to `fixed_twap` as an `f64` when
+    /// that is present.
+    pub twap: f64,
+
+    /// The TWAP as an exact decimal, computed in integer arithmetic from the
+    /// samples' published prices and rounded by the asset's rounding rule.
+    /// Absent when any input lacks an exact price or the method or rung has
+    /// no exact form.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_twap: Option<FixedPrice>,
+
+    /// Start of the TWAP window (Unix timestamp in seconds).
+    pub window_start: i64,
+
+    /// End of the TWAP window (Unix timestamp in seconds).
+    pub window_end: i64,
+
+    /// Number of samples used in calculation.
+    pub sample_count: usize,
+
+    /// Percentage of expected samples that were collected (0.0 to 1.0).
+    pub coverage: f64,
+
+    /// Averaging method the calculator used to produce `twap`.
+    #[serde(default)]
+    pub method: TwapMethod,
+
+    /// Seconds inside the window without an observed sample, including the
+    /// ones synthesized by carry-forward (`filled == true`).
+    #[serde(default)]
+    pub gaps: Vec<GapInterval>,
+
+    /// Which rung of the fallback chain produced `twap`. Anything other than
+    /// `window` means the window missed its asset's coverage minimum; the
+    /// sample statistics above still describe the requested window, except
+    /// for `extended_window` where they describe the extended span.
+    #[serde(default)]
+    pub rung: SettlementRung,
+
+    /// Samples in the window dropped because their confidence was wider
+    /// than the asset's confidence policy allows, at most one per second.
+    #[serde(default)]
+    pub rejected_samples: usize,
+
+    /// Samples in `sample_count` whose confidence was too wide but which
+    /// were kept, flagged or at reduced weight.
+    #[serde(default)]
+    pub flagged_samples: usize,
+}
+
 /// The `type`-tagged payload carried by every [`BroadcastFrame`].
 ///
-/// Includes both domain events (price updates, rolling TWAP previews, upstream
-/// connection status) and transport-only frames (heartbeats). This is
+/// Includes both domain events (price updates, rolling TWAP previews,
+/// settlements, upstream connection status) and transport-only frames (heartbeats). This is
 /// deliberately separate from the in-process `OracleEvent` enum in the
 /// `joyride-oracle-core` crate: heartbeats are a WebSocket keepalive and
 /// never flow through the in-process event channel.
@@ -68,6 +515,9 @@ pub enum WirePayload {
     /// Rolling TWAP preview (every few seconds).
     TwapPreview(TwapPreview),
 
+    /// Authoritative settlement TWAP, emitted once per asset at each expiry.
+    Settlement(TwapResult),
+
     /// Upstream Pyth connection established.
     Connected,
 
@@ -77,6 +527,18 @@ pub enum WirePayload {
     /// An error occurred on the upstream connection.
     Error { message: String },
 
+    /// A price source strayed from the cross-source median.
+    DeviationAlarm(DeviationAlarm),
+
+    /// An asset's feed changed health state.
+    FeedHealth(FeedHealthChange),
+
+    /// The spike filter quarantined a print or resolved a quarantine.
+    PriceQuarantine(PriceQuarantine),
+
+    /// The TWAP calculator refused a price for its publish time.
+    SampleRejected(SampleRejected),
+
     /// WebSocket keepalive; emitted by the server on a fixed interval.
     Heartbeat,
 }
@@ -108,6 +570,7 @@ mod tests {
             WirePayload::Price(p) => {
                 assert_eq!(p.symbol, "SOL");
                 assert_eq!(p.publish_time, 1776947696);
+                assert_eq!(p.ema_price, None);
             }
             other => panic!("expected Price, got {other:?}"),
         }
@@ -121,6 +584,142 @@ mod tests {
         assert!(matches!(reparsed.payload, WirePayload::Price(_)));
     }
 
+    #[test]
+    fn price_update_carries_optional_ema() {
+        let json = r#"{"symbol":"SOL","price":123.45,"confidence":0.12,"publish_time":1776947696,"feed_id":"0xef","ema_price":123.4,"ema_confidence":0.1}"#;
+        let update: PriceUpdate = serde_json::from_str(json).unwrap();
+        assert_eq!(update.ema_price, Some(123.4));
+        assert_eq!(update.ema_confidence, Some(0.1));
+        assert_eq!(serde_json::to_string(&update).unwrap(), json);
+        assert!(update.sources.is_empty());
+    }
+
+    #[test]
+    fn aggregated_price_and_deviation_alarm_round_trip() {
+        let json = r#"{"symbol":"BTC","price":75000.5,"confidence":12.0,"publish_time":1776947696,"feed_id":"0xe6","sources":["hermes","lazer"]}"#;
+        let update: PriceUpdate = serde_json::from_str(json).unwrap();
+        assert_eq!(update.sources, ["hermes", "lazer"]);
+        assert_eq!(serde_json::to_string(&update).unwrap(), json);
+
+        let json = r#"{"timestamp":"2026-04-22T12:34:56.789Z","type":"deviation_alarm","symbol":"BTC","source":"lazer","price":76000.0,"median":75000.0,"deviation":0.0133,"threshold":0.005,"publish_time":1776947696}"#;
+        let frame: BroadcastFrame = serde_json::from_str(json).unwrap();
+        match frame.payload {
+            WirePayload::DeviationAlarm(alarm) => {
+                assert_eq!(alarm.source, "lazer");
+                assert_eq!(alarm.median, 75000.0);
+            }
+            other => panic!("expected DeviationAlarm, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn price_quarantine_round_trips() {
+        let json = r#"{"timestamp":"2026-04-22T12:34:56.789Z","type":"price_quarantine","symbol":"BTC","status":"discarded","price":90000.0,"publish_time":1776947696,"reference_price":75000.0,"reference_time":1776947695,"deviation":0.2,"band":0.02,"held":2}"#;
+        let frame: BroadcastFrame = serde_json::from_str(json).unwrap();
+        match frame.payload {
+            WirePayload::PriceQuarantine(quarantine) => {
+                assert_eq!(quarantine.status, QuarantineStatus::Discarded);
+                assert_eq!(quarantine.held, 2);
+                assert_eq!(quarantine.ema_deviation, None);
+            }
+            other => panic!("expected PriceQuarantine, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn sample_rejected_round_trips() {
+        let json = r#"{"timestamp":"2026-04-22T12:34:56.789Z","type":"sample_rejected","symbol":"SOL","reason":"future","price":150.0,"publish_time":1776947796,"latest_time":1776947695,"received_at":1776947696}"#;
+        let frame: BroadcastFrame = serde_json::from_str(json).unwrap();
+        match frame.payload {
+            WirePayload::SampleRejected(rejected) => {
+                assert_eq!(rejected.reason, SampleRejection::Future);
+                assert_eq!(rejected.latest_time, Some(1776947695));
+            }
+            other => panic!("expected SampleRejected, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn feed_health_round_trips() {
+        let json = r#"{"timestamp":"2026-04-22T12:34:56.789Z","type":"feed_health","symbol":"SOL","state":"stale","previous":"fresh","publish_time":1776947696,"age_secs":31}"#;
+        let frame: BroadcastFrame = serde_json::from_str(json).unwrap();
+        match frame.payload {
+            WirePayload::FeedHealth(change) => {
+                assert_eq!(change.state, FeedHealth::Stale);
+                assert_eq!(change.previous, FeedHealth::Fresh);
+                assert_eq!(change.age_secs, Some(31));
+            }
+            other => panic!("expected FeedHealth, got {other:?}"),
+        }
+        assert!(FeedHealth::Fresh < FeedHealth::Degraded);
+        assert!(FeedHealth::Stale < FeedHealth::Halted);
+    }
+
+    #[test]
+    fn fixed_price_rounds_by_mode() {
+        let cases = [
+            (RoundingMode::HalfEven, [2, 2, -2, 3]),
+            (RoundingMode::HalfUp, [3, 2, -3, 3]),
+            (RoundingMode::Down, [2, 2, -2, 2]),
+            (RoundingMode::Floor, [2, 2, -3, 2]),
+            (RoundingMode::Ceiling, [3, 3, -2, 3]),
+        ];
+        // 2.5, 2.25, -2.5 and 2.75 rounded to whole units.
+        let inputs = [(5, 2), (9, 4), (-5, 2), (11, 4)];
+        for (mode, expected) in cases {
+            for ((numerator, denominator), want) in inputs.into_iter().zip(expected) {
+                let rounded = FixedPrice::from_ratio(numerator, denominator, 0, 0, mode).unwrap();
+                assert_eq!(rounded.mantissa, want, "{mode:?} {numerator}/{denominator}");
+            }
+        }
+
+        let price = FixedPrice::new(6_400_012_345_678, -8);
+        assert_eq!(
+            price.rescale(-2, RoundingMode::HalfEven),
+            Some(FixedPrice::new(6_400_012, -2))
+        );
+        assert_eq!(
+            price.rescale(-10, RoundingMode::HalfEven),
+            Some(FixedPrice::new(640_001_234_567_800, -10))
+        );
+        assert_eq!(FixedPrice::new(123, -2).to_f64(), 1.23);
+        assert_eq!(
+            FixedPrice::from_ratio(1, 0, 0, 0, RoundingMode::Floor),
+            None
+        );
+    }
+
+    #[test]
+    fn twap_method_serializes_parameters() {
+        let method = TwapMethod::TrimmedMean { trim_pct: 10 };
+        let json = serde_json::to_string(&method).unwrap();
+        assert_eq!(json, r#"{"trimmed_mean":{"trim_pct":10}}"#);
+        assert_eq!(serde_json::from_str::<TwapMethod>(&json).unwrap(), method);
+        assert_eq!(
+            serde_json::from_str::<TwapMethod>(r#""confidence_weighted""#).unwrap(),
+            TwapMethod::ConfidenceWeighted
+        );
+    }
+
+    #[test]
+    fn broadcast_frame_round_trips_settlement() {
+        let json = r#"{"timestamp":"2026-04-24T08:00:02.001Z","type":"settlement","symbol":"BTC","window":"30m","twap":64000.5,"window_start":1777017600,"window_end":1777019400,"sample_count":1800,"coverage":1.0,"method":"time_weighted","gaps":[{"start":1777018000,"end":1777018001,"filled":true}]}"#;
+        let frame: BroadcastFrame = serde_json::from_str(json).unwrap();
+        match &frame.payload {
+            WirePayload::Settlement(result) => {
+                assert_eq!(result.symbol, "BTC");
+                assert_eq!(result.window_end, 1777019400);
+                assert_eq!(result.method, TwapMethod::TimeWeighted);
+                assert_eq!(result.gaps[0].len_secs(), 2);
+            }
+            other => panic!("expected Settlement, got {other:?}"),
+        }
+
+        let reserialized = serde_json::to_string(&frame).unwrap();
+        let reparsed: BroadcastFrame = serde_json::from_str(&reserialized).unwrap();
+        assert!(matches!(reparsed.payload, WirePayload::Settlement(_)));
+    }
+
     #[test]
     fn broadcast_frame_round_trips_heartbeat_and_status() {
         for (json, expected) in [
@@ -0,0 +1,382 @@
+//! Offline `verify` and `recompute` subcommands.
+//!
+//! Both rebuild a `TwapCalculator` from recorded inputs and compare the
+//! result with a claimed one, exiting non-zero on any mismatch so disputes
+//! can be settled from a shell instead of a spreadsheet.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+use joyride_oracle::{
+    recompute, result_mismatches, GapPolicy, PriceUpdate, ProofParams, RoundingMode, RoundingRule,
+    SampleJournal, SettlementProof, SettlementRung, TwapCalculator, TwapMethod, TwapResult,
+    TwapSample, TwapWindow, DEFAULT_TWAP_WINDOW_SECS, PROOF_VERSION,
+};
+
+pub const USAGE: &str = "\
+Usage:
+  joyride-oracle                       Run the oracle service
+  joyride-oracle verify <BUNDLE>       Check a settlement proof bundle
+  joyride-oracle recompute <INPUT> [OPTIONS]
+                                       Rebuild a TWAP from recorded inputs
+
+verify checks the bundle's Merkle root and recomputes its result from the
+bundled samples and parameters.
+
+recompute reads INPUT, which is a sample journal directory, a JSON-lines
+file of price updates, or a JSON array of samples, and prints the TWAP
+for the given window as JSON.
+
+Options for recompute:
+  --claimed <FILE>       Compare with a TwapResult or proof bundle; its
+                         symbol, window, window_end and method (and, for a
+                         bundle, all parameters) become the defaults
+  --symbol <SYMBOL>      Asset to recompute
+  --window-end <TS>      Window end, Unix seconds
+  --window-secs <SECS>   Window length [default: the claimed window's,
+                         or 1800]
+  --method <METHOD>      mean | time_weighted | median | confidence_weighted
+                         | trimmed_mean:<PCT> [default: mean]
+  --gap-policy <POLICY>  leave | invalidate | carry_forward:<SECS>
+                         [default: leave]
+  --rounding <RULE>      <MODE>[:<EXPO>], MODE one of half_even | half_up
+                         | down | floor | ceiling [default: half_even at
+                         the feed's exponent]
+
+Exit status: 0 when everything matches, 1 on a mismatch or when no TWAP
+can be calculated, 2 on bad arguments or unreadable input.";
+
+/// Run the subcommand in `args` (without the program name) and return the
+/// process exit status.
+pub fn run(args: &[String]) -> i32 {
+    let outcome = match args.first().map(String::as_str) {
+        Some("verify") => verify(&args[1..]),
+        Some("recompute") => recompute_command(&args[1..]),
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            return 0;
+        }
+        Some(other) => Err(anyhow::anyhow!("unknown command `{other}`")),
+        None => Err(anyhow::anyhow!("missing command")),
+    };
+    match outcome {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("error: {e:#}\n\n{USAGE}");
+            2
+        }
+    }
+}
+
+fn verify(args: &[String]) -> anyhow::Result<bool> {
+    let [path] = args else {
+        bail!("verify takes exactly one bundle path");
+    };
+    let proof: SettlementProof = read_json(Path::new(path))?;
+    anyhow::ensure!(
+        proof.version == PROOF_VERSION,
+        "unsupported proof bundle version {} (expected {PROOF_VERSION})",
+        proof.version
+    );
+
+    let root_ok = proof.verify_root();
+    println!(
+        "merkle root: {} ({} samples)",
+        if root_ok { "ok" } else { "MISMATCH" },
+        proof.samples.len()
+    );
+    if proof.result.rung == SettlementRung::EmaPrice {
+        println!("note: settled on the Pyth EMA price, which is taken from the bundle");
+    }
+    let recomputed = proof.recompute()?;
+    Ok(report(&proof.result, recomputed.as_ref()) && root_ok)
+}
+
+fn recompute_command(args: &[String]) -> anyhow::Result<bool> {
+    let Some((input, options)) = args.split_first() else {
+        bail!("recompute needs an input path");
+    };
+
+    let mut claimed: Option<TwapResult> = None;
+    let mut params: Option<ProofParams> = None;
+    let mut symbol = None;
+    let mut window_end = None;
+    let mut window_secs = None;
+    let mut method = None;
+    let mut gap_policy = None;
+    let mut rounding = None;
+
+    let mut options = options.iter();
+    while let Some(flag) = options.next() {
+        let mut value = || {
+            options
+                .next()
+                .with_context(|| format!("{flag} needs a value"))
+        };
+        match flag.as_str() {
+            "--claimed" => {
+                let path = Path::new(value()?);
+                match read_json::<SettlementProof>(path) {
+                    Ok(proof) => {
+                        params = Some(proof.params);
+                        claimed = Some(proof.result);
+                    }
+                    Err(_) => claimed = Some(read_json::<TwapResult>(path)?),
+                }
+            }
+            "--symbol" => symbol = Some(value()?.clone()),
+            "--window-end" => window_end = Some(parse_number(flag, value()?)?),
+            "--window-secs" => window_secs = Some(parse_number(flag, value()?)?),
+            "--method" => method = Some(parse_method(value()?)?),
+            "--gap-policy" => gap_policy = Some(parse_gap_policy(value()?)?),
+            "--rounding" => rounding = Some(parse_rounding(value()?)?),
+            other => bail!("unknown option `{other}`"),
+        }
+    }
+
+    let symbol = symbol
+        .or_else(|| claimed.as_ref().map(|c| c.symbol.clone()))
+        .context("--symbol is required without --claimed")?;
+    let window_end = window_end
+        .or_else(|| claimed.as_ref().map(|c| c.window_end))
+        .context("--window-end is required without --claimed")?;
+    // A bare result only implies its window length when it was not extended.
+    let claimed_window_secs = claimed
+        .as_ref()
+        .filter(|c| c.rung != SettlementRung::ExtendedWindow)
+        .map(|c| c.window_end - c.window_start);
+    let mut params = params.unwrap_or_else(|| ProofParams {
+        window_secs: claimed_window_secs.unwrap_or(DEFAULT_TWAP_WINDOW_SECS),
+        sample_interval_secs: TwapCalculator::new().sample_interval_secs(),
+        method: claimed.as_ref().map(|c| c.method).unwrap_or_default(),
+        gap_policy: GapPolicy::default(),
+        coverage_policy: None,
+        rounding: RoundingRule::default(),
+        confidence_policy: None,
+    });
+    params.window_secs = window_secs.unwrap_or(params.window_secs);
+    params.method = method.unwrap_or(params.method);
+    params.gap_policy = gap_policy.unwrap_or(params.gap_policy);
+    params.rounding = rounding.unwrap_or(params.rounding);
+    let window = match &claimed {
+        Some(claimed) if window_secs.is_none() => claimed.window.clone(),
+        _ => TwapWindow::from_secs(params.window_secs).name,
+    };
+
+    let updates = load_updates(Path::new(input), &symbol)?;
+    let recomputed = recompute(&params, &symbol, &window, window_end, updates, None)?;
+
+    match claimed {
+        Some(claimed) => Ok(report(&claimed, recomputed.as_ref())),
+        None => match recomputed {
+            Some(result) => {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                Ok(true)
+            }
+            None => {
+                println!("no TWAP for {symbol} ending {window_end}");
+                Ok(false)
+            }
+        },
+    }
+}
+
+/// Print the comparison and return whether it matched.
+fn report(claimed: &TwapResult, recomputed: Option<&TwapResult>) -> bool {
+    let Some(recomputed) = recomputed else {
+        println!("result: MISMATCH (no TWAP could be recomputed)");
+        return false;
+    };
+    let mismatches = result_mismatches(claimed, recomputed);
+    if mismatches.is_empty() {
+        println!(
+            "result: ok ({} {} ending {}: {})",
+            recomputed.symbol, recomputed.window, recomputed.window_end, recomputed.twap
+        );
+        return true;
+    }
+    println!("result: MISMATCH");
+    for mismatch in mismatches {
+        println!("  {mismatch}");
+    }
+    false
+}
+
+/// Price updates from a journal directory, a JSON-lines file of updates, or
+/// a JSON array of samples (carried-forward samples are dropped and
+/// regenerated by the gap policy).
+fn load_updates(path: &Path, symbol: &str) -> anyhow::Result<Vec<PriceUpdate>> {
+    if path.is_dir() {
+        let journal = SampleJournal::open(path, i64::MAX)?;
+        return journal
+            .updates()
+            .with_context(|| format!("reading journal {}", path.display()));
+    }
+
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    if text.trim_start().starts_with('[') {
+        let samples: Vec<TwapSample> = serde_json::from_str(&text)
+            .with_context(|| format!("parsing samples in {}", path.display()))?;
+        return Ok(samples
+            .into_iter()
+            .filter(|sample| !sample.filled)
+            .map(|sample| PriceUpdate {
+                symbol: symbol.to_string(),
+                price: sample.price,
+                confidence: sample.confidence,
+                publish_time: sample.timestamp,
+                fixed_price: sample.fixed_price,
+                ..Default::default()
+            })
+            .collect());
+    }
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_no, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("parsing {}:{}", path.display(), line_no + 1))
+        })
+        .collect()
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+}
+
+fn parse_number(flag: &str, value: &str) -> anyhow::Result<i64> {
+    value
+        .parse()
+        .with_context(|| format!("{flag} expects an integer, got `{value}`"))
+}
+
+fn parse_method(value: &str) -> anyhow::Result<TwapMethod> {
+    Ok(match value.split_once(':') {
+        None => match value {
+            "mean" => TwapMethod::Mean,
+            "time_weighted" => TwapMethod::TimeWeighted,
+            "median" => TwapMethod::Median,
+            "confidence_weighted" => TwapMethod::ConfidenceWeighted,
+            _ => bail!("unknown method `{value}`"),
+        },
+        Some(("trimmed_mean", pct)) => TwapMethod::TrimmedMean {
+            trim_pct: pct
+                .parse()
+                .with_context(|| format!("invalid trim percentage `{pct}`"))?,
+        },
+        Some(_) => bail!("unknown method `{value}`"),
+    })
+}
+
+fn parse_gap_policy(value: &str) -> anyhow::Result<GapPolicy> {
+    Ok(match value.split_once(':') {
+        None => match value {
+            "leave" => GapPolicy::Leave,
+            "invalidate" => GapPolicy::Invalidate,
+            _ => bail!("unknown gap policy `{value}`"),
+        },
+        Some(("carry_forward", secs)) => GapPolicy::CarryForward {
+            max_gap_secs: parse_number("carry_forward", secs)?,
+        },
+        Some(_) => bail!("unknown gap policy `{value}`"),
+    })
+}
+
+fn parse_rounding(value: &str) -> anyhow::Result<RoundingRule> {
+    let (mode, expo) = match value.split_once(':') {
+        Some((mode, expo)) => (
+            mode,
+            Some(
+                expo.parse()
+                    .with_context(|| format!("invalid rounding exponent `{expo}`"))?,
+            ),
+        ),
+        None => (value, None),
+    };
+    let mode = match mode {
+        "half_even" => RoundingMode::HalfEven,
+        "half_up" => RoundingMode::HalfUp,
+        "down" => RoundingMode::Down,
+        "floor" => RoundingMode::Floor,
+        "ceiling" => RoundingMode::Ceiling,
+        _ => bail!("unknown rounding mode `{mode}`"),
+    };
+    Ok(RoundingRule { mode, expo })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("joyride-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn verify_accepts_bundle_and_rejects_tampering() {
+        let dir = scratch_dir("verify");
+        let mut calc = TwapCalculator::with_window(10).with_method(TwapMethod::Median);
+        for i in 0..20 {
+            calc.record(&PriceUpdate {
+                symbol: "SOL".to_string(),
+                price: 100.0 + (i % 3) as f64,
+                confidence: 0.1,
+                publish_time: 1000 + i,
+                feed_id: "0x123".to_string(),
+                ..Default::default()
+            });
+        }
+        let mut proof = SettlementProof::build(&calc, &calc.calculate("SOL", 1019).unwrap());
+        let path = dir.join("bundle.json");
+        fs::write(&path, serde_json::to_string(&proof).unwrap()).unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(run(&strings(&["verify", path])), 0);
+
+        proof.samples[3].price += 1.0;
+        fs::write(path, serde_json::to_string(&proof).unwrap()).unwrap();
+        assert_eq!(run(&strings(&["verify", path])), 1);
+
+        proof.version = PROOF_VERSION - 1;
+        fs::write(path, serde_json::to_string(&proof).unwrap()).unwrap();
+        assert_eq!(run(&strings(&["verify", path])), 2);
+
+        assert_eq!(run(&strings(&["verify"])), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_methods_and_gap_policies() {
+        assert_eq!(
+            parse_method("trimmed_mean:10").unwrap(),
+            TwapMethod::TrimmedMean { trim_pct: 10 }
+        );
+        assert_eq!(parse_method("median").unwrap(), TwapMethod::Median);
+        assert!(parse_method("mode").is_err());
+        assert_eq!(
+            parse_gap_policy("carry_forward:5").unwrap(),
+            GapPolicy::CarryForward { max_gap_secs: 5 }
+        );
+        assert!(parse_gap_policy("carry_forward").is_err());
+        assert_eq!(
+            parse_rounding("half_up:-2").unwrap(),
+            RoundingRule {
+                mode: RoundingMode::HalfUp,
+                expo: Some(-2)
+            }
+        );
+        assert_eq!(parse_rounding("floor").unwrap().expo, None);
+        assert!(parse_rounding("nearest").is_err());
+    }
+}
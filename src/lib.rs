@@ -8,21 +8,21 @@
 //!
 //! - **Real-time price streaming** via Pyth Hermes SSE API
 //! - **TWAP calculation** for settlement pricing
-//! - **Multi-asset support** (SOL, BTC, ETH)
+//! - **Multi-asset support** from a configurable asset registry
 //!
 //! # Example
 //!
 //! ```no_run
-//! use joyride_oracle_core::{PythClient, TwapCalculator, Asset, OracleEvent};
+//! use joyride_oracle_core::{AssetRegistry, OracleEvent, PythClient, TwapCalculator};
 //! use tokio::sync::mpsc;
 //!
 //! #[tokio::main]
 //! async fn main() {
 //!     let (tx, mut rx) = mpsc::channel(256);
-//!     let assets = vec![Asset::Sol, Asset::Btc, Asset::Eth];
+//!     let assets = AssetRegistry::load("assets.json").unwrap();
 //!
+//!     let mut twap = assets.configure(TwapCalculator::new());
 //!     let mut client = PythClient::new(tx, assets);
-//!     let mut twap = TwapCalculator::new();
 //!
 //!     tokio::spawn(async move { client.run().await });
 //!
@@ -37,8 +37,19 @@
 
 pub mod server;
 pub use joyride_oracle_core::{
-    Asset, OracleEvent, PythClient, TwapCalculator, TwapResult, TwapSample,
-    DEFAULT_TWAP_WINDOW_SECS, HERMES_URL,
+    forward_tagged, merkle_root, recompute, result_mismatches, AggregationRule, Asset,
+    AssetRegistry, AssetTwapSettings, CatalogFeed, ConfidenceAction, ConfidenceCounts,
+    ConfidencePolicy, CoveragePolicy, DeviationAlarm, EndpointHealth, EndpointHealthHandle,
+    ExpirySchedule, FeedHealth, FeedHealthChange, FeedHealthMonitor, FeedHealthThresholds,
+    FixedPrice, FsyncPolicy, GapInterval, GapPolicy, LazerClient, OracleEvent, PriceAggregator,
+    PriceFeedCatalog, PriceQuarantine, PriceSource, PriceSourceKind, ProofParams, ProofStore,
+    PythClient, QuarantineStatus, RejectionCounts, RoundingMode, RoundingRule, SampleJournal,
+    SampleRejected, SampleRejection, SettlementCalendar, SettlementFallback, SettlementProof,
+    SettlementRung, SettlementScheduler, SpikeFilter, SpikePolicy, StreamMode, TickerClient,
+    TwapCalculator, TwapMethod, TwapResult, TwapSample, TwapWindow, COINBASE_URL,
+    DEFAULT_DEGRADED_LAG_SECS, DEFAULT_HALTED_AFTER_SECS, DEFAULT_LAZER_CHANNEL,
+    DEFAULT_MAX_FUTURE_SKEW_SECS, DEFAULT_RETENTION_WINDOWS, DEFAULT_STALE_AFTER_SECS,
+    DEFAULT_TWAP_WINDOW_SECS, HERMES_URL, LAZER_URL, PROOF_VERSION,
 };
 pub use joyride_oracle_wire::{BroadcastFrame, PriceUpdate, TwapPreview, WirePayload};
 pub use server::run_server;
@@ -6,21 +6,258 @@
 //!
 //! ```bash
 //! cargo run
+//! cargo run -- verify proofs/BTC/1777019400.json
+//! cargo run -- recompute journal/ --symbol BTC --window-end 1777019400
 //! ```
 
+mod cli;
+
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{broadcast, mpsc, RwLock};
 use tracing::{info, warn};
 
 use joyride_oracle::{
-    run_server, Asset, OracleEvent, PythClient, TwapCalculator, TwapPreview, HERMES_URL,
+    forward_tagged, run_server, AggregationRule, AssetRegistry, EndpointHealthHandle, FeedHealth,
+    FeedHealthMonitor, FeedHealthThresholds, FsyncPolicy, LazerClient, OracleEvent,
+    PriceAggregator, PriceFeedCatalog, PriceSource, PriceSourceKind, ProofStore, PythClient,
+    SampleJournal, SettlementScheduler, SpikeFilter, StreamMode, TickerClient, TwapCalculator,
+    TwapPreview, TwapWindow, DEFAULT_MAX_FUTURE_SKEW_SECS, DEFAULT_RETENTION_WINDOWS,
+    DEFAULT_TWAP_WINDOW_SECS, HERMES_URL,
 };
 
-/// Assets tracked by the oracle.
-const ASSETS: &[Asset] = &[Asset::Sol, Asset::Btc, Asset::Eth];
+/// Asset registry used when `ORACLE_ASSETS_FILE` is unset.
+const DEFAULT_ASSETS: &str = include_str!("../assets.json");
+/// TWAP windows previewed for every asset. The first is the settlement
+/// window and the one `TwapCalculator::calculate` uses.
+const TWAP_WINDOWS: &[(&str, i64)] = &[
+    ("30m", DEFAULT_TWAP_WINDOW_SECS),
+    ("1m", 60),
+    ("5m", 5 * 60),
+    ("1h", 60 * 60),
+];
 const ORDERED_FANOUT_BUFFER: usize = 4096;
 const PREVIEW_FANOUT_BUFFER: usize = 2048;
+const SOURCE_EVENT_BUFFER: usize = 256;
+const ENDPOINT_HEALTH_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tracked assets, from the JSON file named by `ORACLE_ASSETS_FILE` or the
+/// bundled `assets.json`.
+fn asset_registry() -> anyhow::Result<AssetRegistry> {
+    match std::env::var("ORACLE_ASSETS_FILE") {
+        Ok(path) if !path.is_empty() => AssetRegistry::load(path),
+        _ => AssetRegistry::from_json(DEFAULT_ASSETS),
+    }
+}
+
+/// Price sources from the comma-separated `ORACLE_PRICE_SOURCE` (`hermes`,
+/// `lazer`, `coinbase`), or Hermes alone.
+fn price_source_kinds() -> anyhow::Result<Vec<PriceSourceKind>> {
+    let mut kinds = Vec::new();
+    for kind in std::env::var("ORACLE_PRICE_SOURCE")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+    {
+        let kind: PriceSourceKind = kind.parse()?;
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    if kinds.is_empty() {
+        kinds.push(PriceSourceKind::default());
+    }
+    Ok(kinds)
+}
+
+/// Aggregator combining several price sources, configured from
+/// `ORACLE_AGGREGATION` (`median` or `quorum:<n>`) and
+/// `ORACLE_DEVIATION_THRESHOLD` (relative, e.g. `0.005`).
+fn price_aggregator() -> anyhow::Result<PriceAggregator> {
+    let rule = match std::env::var("ORACLE_AGGREGATION") {
+        Ok(rule) if !rule.is_empty() => rule.parse()?,
+        _ => AggregationRule::default(),
+    };
+    let mut aggregator = PriceAggregator::new(rule);
+    if let Ok(threshold) = std::env::var("ORACLE_DEVIATION_THRESHOLD") {
+        if !threshold.is_empty() {
+            let threshold: f64 = threshold
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid ORACLE_DEVIATION_THRESHOLD: {e}"))?;
+            aggregator = aggregator.with_deviation_threshold(threshold);
+        }
+    }
+    Ok(aggregator)
+}
+
+/// Feed health monitor with thresholds from `ORACLE_DEGRADED_LAG_SECS`,
+/// `ORACLE_STALE_AFTER_SECS` and `ORACLE_HALTED_AFTER_SECS`, overridden by
+/// each asset's `health` settings.
+fn feed_health_monitor(assets: &AssetRegistry) -> anyhow::Result<FeedHealthMonitor> {
+    let mut thresholds = FeedHealthThresholds::default();
+    for (var, threshold) in [
+        (
+            "ORACLE_DEGRADED_LAG_SECS",
+            &mut thresholds.degraded_lag_secs,
+        ),
+        ("ORACLE_STALE_AFTER_SECS", &mut thresholds.stale_after_secs),
+        (
+            "ORACLE_HALTED_AFTER_SECS",
+            &mut thresholds.halted_after_secs,
+        ),
+    ] {
+        if let Ok(secs) = std::env::var(var) {
+            if !secs.is_empty() {
+                *threshold = secs
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("invalid {var}: {e}"))?;
+            }
+        }
+    }
+    thresholds.validate()?;
+    Ok(assets.configure_health(FeedHealthMonitor::new(thresholds)))
+}
+
+/// How far ahead of the local clock a publish time may be before the TWAP
+/// calculator rejects it, from `ORACLE_MAX_FUTURE_SKEW_SECS`.
+fn max_future_skew_secs() -> anyhow::Result<i64> {
+    match std::env::var("ORACLE_MAX_FUTURE_SKEW_SECS") {
+        Ok(secs) if !secs.is_empty() => {
+            let secs: i64 = secs
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid ORACLE_MAX_FUTURE_SKEW_SECS: {e}"))?;
+            anyhow::ensure!(
+                secs >= 0,
+                "ORACLE_MAX_FUTURE_SKEW_SECS must not be negative"
+            );
+            Ok(secs)
+        }
+        _ => Ok(DEFAULT_MAX_FUTURE_SKEW_SECS),
+    }
+}
+
+/// Whether price events sent to WebSocket clients keep their signed Pyth
+/// update data (`ORACLE_WIRE_UPDATE_DATA=true`). Off by default; the TWAP
+/// samples, journal and proof bundles keep it either way.
+fn wire_update_data() -> anyhow::Result<bool> {
+    match std::env::var("ORACLE_WIRE_UPDATE_DATA") {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid ORACLE_WIRE_UPDATE_DATA: {e}")),
+        _ => Ok(false),
+    }
+}
+
+/// Pyth Lazer client configured from `ORACLE_LAZER_TOKEN` (required),
+/// `ORACLE_LAZER_URL` and `ORACLE_LAZER_CHANNEL`.
+fn lazer_client(
+    event_tx: mpsc::Sender<OracleEvent>,
+    assets: AssetRegistry,
+) -> anyhow::Result<LazerClient> {
+    let token = std::env::var("ORACLE_LAZER_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("ORACLE_LAZER_TOKEN is required for the lazer source"))?;
+    let mut client = LazerClient::new(event_tx, assets, &token)?;
+    if let Some(url) = std::env::var("ORACLE_LAZER_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+    {
+        client = client.with_url(&url);
+    }
+    if let Some(channel) = std::env::var("ORACLE_LAZER_CHANNEL")
+        .ok()
+        .filter(|channel| !channel.is_empty())
+    {
+        client = client.with_channel(&channel);
+    }
+    Ok(client)
+}
+
+/// Exchange ticker client, pointed at `ORACLE_COINBASE_URL` if set.
+fn ticker_client(event_tx: mpsc::Sender<OracleEvent>, assets: AssetRegistry) -> TickerClient {
+    let client = TickerClient::new(event_tx, assets);
+    match std::env::var("ORACLE_COINBASE_URL") {
+        Ok(url) if !url.is_empty() => client.with_url(&url),
+        _ => client,
+    }
+}
+
+/// Hermes endpoints from the comma-separated `ORACLE_HERMES_URLS`, in order
+/// of preference, or the public endpoint.
+fn hermes_endpoints() -> Vec<String> {
+    let urls: Vec<String> = std::env::var("ORACLE_HERMES_URLS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim().trim_end_matches('/'))
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect();
+    if urls.is_empty() {
+        vec![HERMES_URL.to_string()]
+    } else {
+        urls
+    }
+}
+
+/// `failover` (default) or `redundant`, from `ORACLE_HERMES_MODE`.
+fn hermes_mode() -> anyhow::Result<StreamMode> {
+    match std::env::var("ORACLE_HERMES_MODE") {
+        Ok(mode) if !mode.is_empty() => mode.parse(),
+        _ => Ok(StreamMode::default()),
+    }
+}
+
+/// Log every Hermes endpoint's health once a minute, so a degraded provider
+/// shows up before the remaining one fails too.
+fn spawn_endpoint_health_log(endpoint_health: EndpointHealthHandle) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ENDPOINT_HEALTH_LOG_INTERVAL);
+        loop {
+            interval.tick().await;
+            for health in endpoint_health.snapshot() {
+                info!(
+                    endpoint = %health.url,
+                    connected = health.connected,
+                    updates = health.updates,
+                    forwarded = health.forwarded,
+                    failures = health.failures,
+                    last_update = health.last_update,
+                    last_error = health.last_error.as_deref(),
+                    missed_updates = ?health.missed_updates,
+                    "hermes_endpoint_health"
+                );
+            }
+        }
+    });
+}
+
+/// Directory for the on-disk TWAP sample journal. Unset disables it.
+fn journal_dir() -> Option<String> {
+    std::env::var("ORACLE_JOURNAL_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+}
+
+/// When the journal syncs appends to disk, from `ORACLE_JOURNAL_FSYNC`
+/// (`always`, `never` or `every:<n>`).
+fn journal_fsync() -> anyhow::Result<FsyncPolicy> {
+    match std::env::var("ORACLE_JOURNAL_FSYNC") {
+        Ok(policy) if !policy.is_empty() => policy
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid ORACLE_JOURNAL_FSYNC: {e}")),
+        _ => Ok(FsyncPolicy::default()),
+    }
+}
+
+/// Directory for settlement proof bundles. Unset disables writing and
+/// serving them.
+fn proof_dir() -> Option<String> {
+    std::env::var("ORACLE_PROOF_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+}
 
 /// WebSocket server address (0.0.0.0 for Docker/production).
 fn server_addr() -> String {
@@ -29,30 +266,97 @@ fn server_addr() -> String {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        // Warnings, such as unreadable journal lines skipped while loading
+        // a recording, go to stderr beside the command's own output.
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_max_level(tracing::Level::WARN)
+            .init();
+        std::process::exit(cli::run(&args));
+    }
+
     tracing_subscriber::fmt().init();
 
     info!("Starting Joyride Oracle Service");
+    let mut assets = asset_registry()?;
+    anyhow::ensure!(!assets.is_empty(), "asset registry is empty");
+    let price_sources = price_source_kinds()?;
+    let aggregator = price_aggregator()?;
+    let health_monitor = feed_health_monitor(&assets)?;
+    let hermes_endpoints = hermes_endpoints();
+    let hermes_mode = hermes_mode()?;
+    // Resolve and check feed IDs up front so a typo stops startup instead of
+    // silently matching no updates. Lazer feeds are checked by LazerClient.
+    if price_sources.contains(&PriceSourceKind::Hermes) {
+        match PriceFeedCatalog::fetch_any(&hermes_endpoints).await {
+            Ok(catalog) => {
+                assets.resolve_feeds(&catalog)?;
+                info!(
+                    catalog_feeds = catalog.len(),
+                    "Validated asset feeds against the Hermes catalog"
+                );
+            }
+            Err(e) if assets.is_resolved() => {
+                warn!("Could not fetch the Hermes catalog; feed IDs are unvalidated: {e:#}");
+            }
+            Err(e) => return Err(e.context("resolving asset feed IDs")),
+        }
+    }
     info!(
         "Tracking assets: {}",
-        ASSETS
-            .iter()
-            .map(|a| a.symbol())
-            .collect::<Vec<_>>()
-            .join(", ")
+        assets.symbols().collect::<Vec<_>>().join(", ")
     );
 
     // Split ordered oracle traffic from latest-state preview traffic so
     // preview fanout can never displace price delivery.
     let (ordered_tx, _) = broadcast::channel::<OracleEvent>(ORDERED_FANOUT_BUFFER);
     let ordered_tx_clone = ordered_tx.clone();
+    let wire_update_data = wire_update_data()?;
     let (preview_tx, _) = broadcast::channel::<TwapPreview>(PREVIEW_FANOUT_BUFFER);
     let preview_tx_clone = preview_tx.clone();
 
     // Create channel for Pyth client events
     let (event_tx, mut event_rx) = mpsc::channel::<OracleEvent>(256);
 
-    // Create TWAP calculator
-    let twap = Arc::new(RwLock::new(TwapCalculator::new()));
+    // Create TWAP calculator; all windows share one sample store per asset
+    let mut calculator = assets.configure(
+        TwapCalculator::with_windows(
+            TWAP_WINDOWS
+                .iter()
+                .map(|&(name, secs)| TwapWindow::new(name, secs))
+                .collect(),
+            DEFAULT_RETENTION_WINDOWS,
+        )
+        .with_max_future_skew(max_future_skew_secs()?),
+    );
+
+    // Rebuild the TWAP window from the journal before the Pyth stream resumes
+    let mut journal = match journal_dir() {
+        Some(dir) => {
+            let journal = SampleJournal::open(&dir, calculator.retention_secs())?
+                .with_fsync(journal_fsync()?);
+            let replayed = journal.replay(&mut calculator)?;
+            info!(journal_dir = %dir, replayed, "Restored TWAP samples from journal");
+            Some(journal)
+        }
+        None => {
+            warn!("ORACLE_JOURNAL_DIR not set; TWAP samples will not survive a restart");
+            None
+        }
+    };
+    let proofs = match proof_dir() {
+        Some(dir) => {
+            info!(proof_dir = %dir, "Writing settlement proofs");
+            Some(ProofStore::open(&dir)?)
+        }
+        None => {
+            warn!("ORACLE_PROOF_DIR not set; settlement proofs will not be kept");
+            None
+        }
+    };
+    let twap = Arc::new(RwLock::new(calculator));
     let twap_clone = twap.clone();
 
     // Start WebSocket server
@@ -60,22 +364,99 @@ async fn main() -> anyhow::Result<()> {
     let ordered_server_rx = ordered_tx.subscribe();
     let preview_server_rx = preview_tx.subscribe();
     let addr_clone = addr.clone();
+    let server_proofs = proofs.clone();
+    let server_twap = twap.clone();
     tokio::spawn(async move {
-        run_server(&addr_clone, ordered_server_rx, preview_server_rx).await;
+        run_server(
+            &addr_clone,
+            ordered_server_rx,
+            preview_server_rx,
+            server_proofs,
+            Some(server_twap),
+        )
+        .await;
     });
     info!("WebSocket server listening on {}", addr);
 
-    // Start Pyth client
-    info!(hermes_url = %HERMES_URL, "Using Hermes endpoint");
-    let mut pyth_client = PythClient::new(event_tx, ASSETS.to_vec());
+    // Start the price sources. Several feed the aggregator, which sends the
+    // combined prices on; a single one sends straight to the spike filter.
+    // The filter holds back suspect prints and hands the rest to the health
+    // monitor, which passes everything through to the event channel along
+    // with per-asset health transitions.
+    let (health_tx, health_rx) = mpsc::channel(SOURCE_EVENT_BUFFER);
+    tokio::spawn(health_monitor.run(health_rx, event_tx.clone()));
+    let spike_filter = assets.configure_spikes(SpikeFilter::new());
+    let pipeline_tx = if spike_filter.is_empty() {
+        health_tx
+    } else {
+        let (filter_tx, filter_rx) = mpsc::channel(SOURCE_EVENT_BUFFER);
+        tokio::spawn(spike_filter.run(filter_rx, health_tx));
+        filter_tx
+    };
+    let aggregator_input = (price_sources.len() > 1).then(|| {
+        info!(rule = ?aggregator.rule(), "Aggregating price sources");
+        let (input_tx, input_rx) = mpsc::channel(SOURCE_EVENT_BUFFER);
+        tokio::spawn(aggregator.run(input_rx, pipeline_tx.clone()));
+        input_tx
+    });
+    for &kind in &price_sources {
+        let source_tx = match &aggregator_input {
+            Some(input) => {
+                let (source_tx, source_rx) = mpsc::channel(SOURCE_EVENT_BUFFER);
+                forward_tagged(kind.name(), source_rx, input.clone());
+                source_tx
+            }
+            None => pipeline_tx.clone(),
+        };
+        let mut source: Box<dyn PriceSource> = match kind {
+            PriceSourceKind::Hermes => {
+                info!(
+                    hermes_endpoints = %hermes_endpoints.join(","),
+                    hermes_mode = ?hermes_mode,
+                    "Using Hermes endpoints"
+                );
+                // Behind the aggregator the other sources keep sampling
+                // through a Hermes outage, so there is no gap to backfill.
+                let client = PythClient::new(source_tx, assets.clone())
+                    .with_endpoints(hermes_endpoints.clone())
+                    .with_stream_mode(hermes_mode)
+                    .with_backfill(aggregator_input.is_none());
+                spawn_endpoint_health_log(client.endpoint_health());
+                Box::new(client)
+            }
+            PriceSourceKind::Lazer => {
+                let client = lazer_client(source_tx, assets.clone())?;
+                info!(lazer_url = %client.url(), channel = %client.channel(), "Using Pyth Lazer");
+                Box::new(client)
+            }
+            PriceSourceKind::Coinbase => {
+                let client = ticker_client(source_tx, assets.clone());
+                info!(ticker_url = %client.url(), "Using exchange ticker");
+                Box::new(client)
+            }
+        };
+        tokio::spawn(async move {
+            if let Err(e) = source.run().await {
+                tracing::error!("{} price source error: {}", source.name(), e);
+            }
+        });
+    }
+
+    // Start settlement scheduler; settlements flow through the ordered stream
+    let mut settlement_scheduler =
+        SettlementScheduler::new(assets.settlement_calendar(), twap.clone(), event_tx);
+    if let Some(proofs) = proofs {
+        settlement_scheduler = settlement_scheduler.with_proof_store(proofs);
+    }
     tokio::spawn(async move {
-        if let Err(e) = pyth_client.run().await {
-            tracing::error!("Pyth client error: {}", e);
+        if let Err(e) = settlement_scheduler.run().await {
+            tracing::error!("Settlement scheduler error: {}", e);
         }
     });
 
     // Start TWAP preview timer task (broadcasts rolling TWAP previews every second)
     let timer_twap = twap.clone();
+    let timer_assets = assets.clone();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(1));
 
@@ -84,10 +465,10 @@ async fn main() -> anyhow::Result<()> {
 
             let now = chrono::Utc::now().timestamp();
 
-            // Calculate and broadcast TWAP previews for each asset
+            // Calculate and broadcast TWAP previews for each asset and window
             let twap = timer_twap.read().await;
-            for asset in ASSETS {
-                if let Some(preview) = twap.calculate_preview(asset.symbol(), now) {
+            for symbol in timer_assets.symbols() {
+                for preview in twap.calculate_previews(symbol, now) {
                     let _ = preview_tx_clone.send(preview);
                 }
             }
@@ -101,20 +482,48 @@ async fn main() -> anyhow::Result<()> {
         // Broadcast ordered events to WebSocket clients. Previews fan out through
         // a dedicated latest-state channel from the timer task above.
         if !matches!(event, OracleEvent::TwapPreview(_)) {
-            let _ = ordered_tx_clone.send(event.clone());
+            let mut wire_event = event.clone();
+            if let OracleEvent::Price(update) = &mut wire_event {
+                if !wire_update_data {
+                    update.update_data = None;
+                }
+            }
+            let _ = ordered_tx_clone.send(wire_event);
         }
 
         match &event {
             OracleEvent::Connected => {
-                info!("Connected to Pyth Hermes");
+                info!("Connected to price source");
             }
             OracleEvent::Disconnected => {
-                warn!("Disconnected from Pyth Hermes");
+                warn!("Disconnected from price source");
             }
             OracleEvent::Price(update) => {
-                // Record for TWAP
+                // Record for TWAP, turning away publish times that went
+                // backwards, are resent or run ahead of our clock
                 let mut twap = twap_clone.write().await;
-                twap.record(update);
+                match twap.admit(update, chrono::Utc::now().timestamp()) {
+                    Ok(true) => {
+                        if let Some(journal) = journal.as_mut() {
+                            if let Err(e) = journal.append(update) {
+                                warn!("Failed to journal TWAP sample: {}", e);
+                            }
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(rejected) => {
+                        warn!(
+                            "{}: rejected ${:.4} published at {} ({:?}, newest sample {:?}, clock {})",
+                            rejected.symbol,
+                            rejected.price,
+                            rejected.publish_time,
+                            rejected.reason,
+                            rejected.latest_time,
+                            rejected.received_at
+                        );
+                        let _ = ordered_tx_clone.send(OracleEvent::SampleRejected(rejected));
+                    }
+                }
 
                 // Log price changes (avoid spamming on every update)
                 let should_log = match last_prices.get(&update.symbol) {
@@ -136,9 +545,59 @@ async fn main() -> anyhow::Result<()> {
                     last_prices.insert(update.symbol.clone(), update.price);
                 }
             }
+            OracleEvent::Settlement(result) => {
+                info!(
+                    "{} settled at {}: ${:.4} ({} samples, {:.1}% coverage)",
+                    result.symbol,
+                    result.window_end,
+                    result.twap,
+                    result.sample_count,
+                    result.coverage * 100.0
+                );
+            }
             OracleEvent::Error { message } => {
                 warn!("Oracle error: {}", message);
             }
+            OracleEvent::DeviationAlarm(alarm) => {
+                warn!(
+                    "{}: {} at ${:.4} is {:.2}% from the ${:.4} median",
+                    alarm.symbol,
+                    alarm.source,
+                    alarm.price,
+                    alarm.deviation * 100.0,
+                    alarm.median
+                );
+            }
+            OracleEvent::FeedHealth(change) => {
+                let age = change
+                    .age_secs
+                    .map_or_else(|| "no price".to_string(), |age| format!("{age}s old"));
+                if change.state == FeedHealth::Fresh {
+                    info!(
+                        "{} feed recovered from {:?} ({age})",
+                        change.symbol, change.previous
+                    );
+                } else {
+                    warn!(
+                        "{} feed is {:?}, was {:?} ({age})",
+                        change.symbol, change.state, change.previous
+                    );
+                }
+            }
+            OracleEvent::PriceQuarantine(quarantine) => {
+                warn!(
+                    "{}: ${:.4} at {} {:?} ({:.2}% from ${:.4}, {} held)",
+                    quarantine.symbol,
+                    quarantine.price,
+                    quarantine.publish_time,
+                    quarantine.status,
+                    quarantine.deviation * 100.0,
+                    quarantine.reference_price,
+                    quarantine.held
+                );
+            }
+            // SampleRejected is raised above, straight onto the ordered stream
+            OracleEvent::SampleRejected(_) => {}
             // TwapPreview is generated by the timer task, not received through event_rx
             OracleEvent::TwapPreview(_) => {}
         }
@@ -146,3 +605,17 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_asset_registry_is_valid() {
+        let assets = AssetRegistry::from_json(DEFAULT_ASSETS).unwrap();
+        assert_eq!(assets.symbols().collect::<Vec<_>>(), ["SOL", "BTC", "ETH"]);
+        assert!(assets
+            .iter()
+            .all(|asset| asset.twap.coverage_policy.is_some() && asset.spike_filter.is_some()));
+    }
+}
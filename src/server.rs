@@ -1,6 +1,9 @@
 //! WebSocket server for broadcasting oracle data to dashboard clients.
+//!
+//! The same port answers plain HTTP `GET /proofs/{symbol}/{window_end}` with
+//! the stored settlement proof bundle when a proof store is configured.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::net::SocketAddr;
 use std::sync::{
     atomic::{AtomicU64, AtomicUsize, Ordering},
@@ -12,6 +15,7 @@ use chrono::{SecondsFormat, Utc};
 use futures_util::{Sink, SinkExt, StreamExt};
 use serde::Serialize;
 use socket2::SockRef;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{broadcast, RwLock};
 use tokio::time::Instant;
@@ -21,8 +25,10 @@ use tokio_tungstenite::{
 };
 use tracing::{error, info, warn};
 
-use joyride_oracle_core::OracleEvent;
-use joyride_oracle_wire::{PriceUpdate, TwapPreview};
+use joyride_oracle_core::{
+    ConfidenceCounts, OracleEvent, ProofStore, RejectionCounts, TwapCalculator,
+};
+use joyride_oracle_wire::{FeedHealthChange, PriceUpdate, TwapPreview};
 
 /// Server-side serialization envelope for domain events. Borrows the event
 /// so callers can keep it around (e.g. for metrics) after the JSON is produced.
@@ -69,6 +75,10 @@ const FANOUT_HEALTH_INTERVAL: Duration = Duration::from_secs(30);
 const WS_SEND_TIMEOUT: Duration = Duration::from_secs(10);
 const ORDERED_CLIENT_BUFFER: usize = 4096;
 const PREVIEW_CLIENT_BUFFER: usize = 2048;
+const PROOFS_PATH: &str = "/proofs/";
+const PROOF_REQUEST_PREFIX: &[u8] = b"GET /proofs/";
+const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HTTP_REQUEST_BYTES: usize = 8 * 1024;
 
 fn tcp_keepalive() -> socket2::TcpKeepalive {
     socket2::TcpKeepalive::new()
@@ -93,9 +103,17 @@ impl Default for ClientOptions {
 
 #[derive(Clone, Default)]
 struct ServerState {
+    /// Latest price per symbol, EMA included, replayed to new clients.
     latest_prices: Arc<RwLock<HashMap<String, PriceUpdate>>>,
-    latest_previews: Arc<RwLock<HashMap<String, TwapPreview>>>,
+    /// Latest feed health transition per symbol, so new clients learn which
+    /// assets are not quoting.
+    latest_health: Arc<RwLock<HashMap<String, FeedHealthChange>>>,
+    /// Latest preview per `(symbol, window)`.
+    latest_previews: Arc<RwLock<HashMap<(String, String), TwapPreview>>>,
     metrics: Arc<DeliveryMetrics>,
+    proofs: Option<ProofStore>,
+    /// The service's calculator, read for confidence-policy counts.
+    twap: Option<Arc<RwLock<TwapCalculator>>>,
 }
 
 #[derive(Default)]
@@ -140,25 +158,86 @@ struct ClientStats {
     client_pings_received: u64,
     ordered_lagged: u64,
     preview_lagged: u64,
-    last_btc_publish_time: Option<i64>,
-    last_eth_publish_time: Option<i64>,
-    last_sol_publish_time: Option<i64>,
+    last_publish_times: PublishTimes,
 }
 
-#[derive(Default)]
-struct PublishTimeSnapshot {
-    btc: Option<i64>,
-    eth: Option<i64>,
-    sol: Option<i64>,
+/// Latest `publish_time` per symbol, logged as `SYMBOL=time` pairs in symbol
+/// order.
+#[derive(Debug, Default, PartialEq)]
+struct PublishTimes(BTreeMap<String, i64>);
+
+impl PublishTimes {
+    fn record(&mut self, update: &PriceUpdate) {
+        self.0.insert(update.symbol.clone(), update.publish_time);
+    }
+}
+
+impl std::fmt::Display for PublishTimes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (symbol, publish_time)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{symbol}={publish_time}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Per-symbol confidence-policy counts, formatted for the health log.
+struct ConfidenceTally(Vec<(String, ConfidenceCounts)>);
+
+impl std::fmt::Display for ConfidenceTally {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (symbol, counts)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(
+                f,
+                "{symbol}=rejected:{},flagged:{}",
+                counts.rejected, counts.flagged
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Per-symbol publish-time rejection counts, formatted for the health log.
+struct RejectionTally(Vec<(String, RejectionCounts)>);
+
+impl std::fmt::Display for RejectionTally {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (symbol, counts)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(
+                f,
+                "{symbol}=regressions:{},duplicates:{},future:{}",
+                counts.regressions, counts.duplicates, counts.future
+            )?;
+        }
+        Ok(())
+    }
 }
 
 impl ServerState {
     async fn cache_ordered_event(&self, event: &OracleEvent) {
-        if let OracleEvent::Price(update) = event {
-            self.latest_prices
-                .write()
-                .await
-                .insert(update.symbol.clone(), update.clone());
+        match event {
+            OracleEvent::Price(update) => {
+                self.latest_prices
+                    .write()
+                    .await
+                    .insert(update.symbol.clone(), update.clone());
+            }
+            OracleEvent::FeedHealth(change) => {
+                self.latest_health
+                    .write()
+                    .await
+                    .insert(change.symbol.clone(), change.clone());
+            }
+            _ => {}
         }
     }
 
@@ -166,7 +245,7 @@ impl ServerState {
         self.latest_previews
             .write()
             .await
-            .insert(preview.symbol.clone(), preview.clone());
+            .insert(preview_key(preview), preview.clone());
     }
 
     async fn snapshot_prices(&self) -> Vec<PriceUpdate> {
@@ -176,29 +255,51 @@ impl ServerState {
         snapshot
     }
 
+    async fn snapshot_health(&self) -> Vec<FeedHealthChange> {
+        let health = self.latest_health.read().await;
+        let mut snapshot: Vec<_> = health.values().cloned().collect();
+        snapshot.sort_by(|left, right| left.symbol.cmp(&right.symbol));
+        snapshot
+    }
+
     async fn snapshot_previews(&self) -> Vec<TwapPreview> {
         let previews = self.latest_previews.read().await;
         let mut snapshot: Vec<_> = previews.values().cloned().collect();
-        snapshot.sort_by(|left, right| left.symbol.cmp(&right.symbol));
+        snapshot.sort_by_key(preview_key);
         snapshot
     }
 
-    async fn publish_time_snapshot(&self) -> (PublishTimeSnapshot, usize, usize) {
+    async fn publish_time_snapshot(&self) -> (PublishTimes, usize, usize) {
         // Acquire and release each lock sequentially to avoid holding two
         // RwLock read guards simultaneously (prevents lock-ordering issues).
         let (snapshot, price_count) = {
             let prices = self.latest_prices.read().await;
-            let s = PublishTimeSnapshot {
-                btc: prices.get("BTC").map(|update| update.publish_time),
-                eth: prices.get("ETH").map(|update| update.publish_time),
-                sol: prices.get("SOL").map(|update| update.publish_time),
-            };
+            let s = PublishTimes(
+                prices
+                    .iter()
+                    .map(|(symbol, update)| (symbol.clone(), update.publish_time))
+                    .collect(),
+            );
             (s, prices.len())
         };
         let preview_count = self.latest_previews.read().await.len();
 
         (snapshot, price_count, preview_count)
     }
+
+    async fn confidence_tally(&self) -> ConfidenceTally {
+        match &self.twap {
+            Some(twap) => ConfidenceTally(twap.read().await.confidence_counts()),
+            None => ConfidenceTally(Vec::new()),
+        }
+    }
+
+    async fn rejection_tally(&self) -> RejectionTally {
+        match &self.twap {
+            Some(twap) => RejectionTally(twap.read().await.rejection_counts()),
+            None => RejectionTally(Vec::new()),
+        }
+    }
 }
 
 impl ClientStats {
@@ -234,20 +335,20 @@ impl ClientStats {
     }
 
     fn record_price_update(&mut self, update: &PriceUpdate) {
-        match update.symbol.as_str() {
-            "BTC" => self.last_btc_publish_time = Some(update.publish_time),
-            "ETH" => self.last_eth_publish_time = Some(update.publish_time),
-            "SOL" => self.last_sol_publish_time = Some(update.publish_time),
-            _ => {}
-        }
+        self.last_publish_times.record(update);
     }
 }
 
-/// Run the WebSocket server for broadcasting oracle events.
+/// Run the WebSocket server for broadcasting oracle events. With `proofs`,
+/// settlement proof bundles are also served over HTTP on the same port.
+/// With `twap`, the periodic health log includes its confidence-policy
+/// counts.
 pub async fn run_server(
     addr: &str,
     mut ordered_rx: broadcast::Receiver<OracleEvent>,
     mut preview_rx: broadcast::Receiver<TwapPreview>,
+    proofs: Option<ProofStore>,
+    twap: Option<Arc<RwLock<TwapCalculator>>>,
 ) {
     let listener = match TcpListener::bind(addr).await {
         Ok(l) => l,
@@ -259,7 +360,11 @@ pub async fn run_server(
 
     info!("Oracle WebSocket server listening on {}", addr);
 
-    let state = ServerState::default();
+    let state = ServerState {
+        proofs,
+        twap,
+        ..ServerState::default()
+    };
     let (ordered_client_tx, _) = broadcast::channel::<OracleEvent>(ORDERED_CLIENT_BUFFER);
     let ordered_client_tx_clone = ordered_client_tx.clone();
     let ordered_state = state.clone();
@@ -330,6 +435,12 @@ pub async fn run_server(
 
             let (publish_times, cached_prices, cached_previews) =
                 health_state.publish_time_snapshot().await;
+            let confidence = health_state.confidence_tally().await;
+            let confidence_rejected_total: u64 =
+                confidence.0.iter().map(|(_, counts)| counts.rejected).sum();
+            let rejections = health_state.rejection_tally().await;
+            let future_samples_total: u64 =
+                rejections.0.iter().map(|(_, counts)| counts.future).sum();
 
             info!(
                 active_clients = health_state.metrics.active_clients.load(Ordering::Relaxed),
@@ -363,9 +474,11 @@ pub async fn run_server(
                 send_timeouts_total = health_state.metrics.send_timeouts.load(Ordering::Relaxed),
                 cached_prices,
                 cached_previews,
-                last_btc_publish_time = publish_times.btc,
-                last_eth_publish_time = publish_times.eth,
-                last_sol_publish_time = publish_times.sol,
+                last_publish_times = %publish_times,
+                confidence_rejected_total,
+                confidence_counts = %confidence,
+                future_samples_total,
+                sample_rejections = %rejections,
                 "oracle_fanout_health"
             );
         }
@@ -392,6 +505,14 @@ pub async fn run_server(
         let preview_client_tx = preview_client_tx.clone();
 
         tokio::spawn(async move {
+            if let Some(proofs) = &state.proofs {
+                if is_proof_request(&stream).await {
+                    if let Err(e) = serve_proof(stream, proofs).await {
+                        warn!(client = %peer_addr, error = %e, "Proof request failed");
+                    }
+                    return;
+                }
+            }
             if let Err(e) = handle_client(
                 stream,
                 peer_addr,
@@ -407,6 +528,84 @@ pub async fn run_server(
     }
 }
 
+/// Whether the connection opens with `GET /proofs/` rather than a WebSocket
+/// upgrade. Only peeks, so the WebSocket handshake still sees every byte.
+async fn is_proof_request(stream: &TcpStream) -> bool {
+    let mut buf = [0u8; PROOF_REQUEST_PREFIX.len()];
+    let deadline = Instant::now() + HTTP_REQUEST_TIMEOUT;
+    loop {
+        let peeked = match tokio::time::timeout_at(deadline, stream.peek(&mut buf)).await {
+            Ok(Ok(n)) if n > 0 => n,
+            _ => return false,
+        };
+        if buf[..peeked] != PROOF_REQUEST_PREFIX[..peeked] {
+            return false;
+        }
+        if peeked == buf.len() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Answer one HTTP proof request and close the connection.
+async fn serve_proof(mut stream: TcpStream, proofs: &ProofStore) -> anyhow::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_HTTP_REQUEST_BYTES {
+        let n = tokio::time::timeout(HTTP_REQUEST_TIMEOUT, stream.read(&mut buf)).await??;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let (status, body) = proof_response(&request, proofs);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Status line and JSON body for a `GET /proofs/{symbol}/{window_end}`
+/// request head.
+fn proof_response(request: &[u8], proofs: &ProofStore) -> (&'static str, String) {
+    let error =
+        |status, message: &str| (status, serde_json::json!({ "error": message }).to_string());
+
+    let request_line = request.split(|&b| b == b'\n').next().unwrap_or_default();
+    let path = std::str::from_utf8(request_line)
+        .ok()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+    let Some((symbol, window_end)) = path
+        .strip_prefix(PROOFS_PATH)
+        .and_then(|rest| rest.split_once('/'))
+        .and_then(|(symbol, end)| Some((symbol, end.parse::<i64>().ok()?)))
+    else {
+        return error(
+            "400 Bad Request",
+            "expected GET /proofs/{symbol}/{window_end}",
+        );
+    };
+
+    match proofs.load_json(symbol, window_end) {
+        Ok(Some(json)) => ("200 OK", json),
+        Ok(None) => error("404 Not Found", "no proof for that settlement"),
+        Err(e) => {
+            warn!(symbol, window_end, error = %e, "Failed to read settlement proof");
+            error("500 Internal Server Error", "failed to read proof")
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)] // tungstenite's handshake callback signature
 async fn handle_client(
     stream: TcpStream,
     peer_addr: SocketAddr,
@@ -483,6 +682,34 @@ async fn handle_client(
         stats.record_snapshot_price(price);
     }
 
+    for change in state.snapshot_health().await {
+        let json = serialize_json(&OracleEvent::FeedHealth(change))?;
+        if let Err(reason) = send_text(
+            &mut ws_sender,
+            json,
+            &state,
+            connection_id,
+            peer_addr,
+            &client_name,
+            "initial_snapshot_health",
+        )
+        .await
+        {
+            log_disconnect(
+                &state,
+                connection_id,
+                peer_addr,
+                &client_name,
+                include_previews,
+                connected_at,
+                &stats,
+                reason,
+            );
+            return Ok(());
+        }
+        stats.record_message_sent();
+    }
+
     if include_previews {
         let snapshot_previews = state.snapshot_previews().await;
         for preview in &snapshot_previews {
@@ -773,6 +1000,7 @@ where
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn log_disconnect(
     state: &ServerState,
     connection_id: u64,
@@ -800,9 +1028,7 @@ fn log_disconnect(
         client_pings_received = stats.client_pings_received,
         ordered_lagged = stats.ordered_lagged,
         preview_lagged = stats.preview_lagged,
-        last_btc_publish_time = stats.last_btc_publish_time,
-        last_eth_publish_time = stats.last_eth_publish_time,
-        last_sol_publish_time = stats.last_sol_publish_time,
+        last_publish_times = %stats.last_publish_times,
         "Oracle WS client disconnected"
     );
 }
@@ -817,12 +1043,12 @@ fn drain_latest_previews(
     stats: &mut ClientStats,
 ) -> Vec<TwapPreview> {
     let mut latest = HashMap::new();
-    latest.insert(first_preview.symbol.clone(), first_preview);
+    latest.insert(preview_key(&first_preview), first_preview);
 
     loop {
         match preview_rx.try_recv() {
             Ok(preview) => {
-                latest.insert(preview.symbol.clone(), preview);
+                latest.insert(preview_key(&preview), preview);
             }
             Err(broadcast::error::TryRecvError::Empty) => break,
             Err(broadcast::error::TryRecvError::Closed) => break,
@@ -844,10 +1070,16 @@ fn drain_latest_previews(
     }
 
     let mut previews: Vec<_> = latest.into_values().collect();
-    previews.sort_by(|left, right| left.symbol.cmp(&right.symbol));
+    previews.sort_by_key(preview_key);
     previews
 }
 
+/// Previews are latest-state per asset *and* window: a 1m preview must not
+/// displace the 30m one for the same symbol.
+fn preview_key(preview: &TwapPreview) -> (String, String) {
+    (preview.symbol.clone(), preview.window.clone())
+}
+
 fn parse_client_options(query: Option<&str>) -> ClientOptions {
     let mut options = ClientOptions::default();
 
@@ -888,8 +1120,8 @@ fn parse_client_options(query: Option<&str>) -> ClientOptions {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use joyride_oracle_wire::{BroadcastFrame, WirePayload};
     use futures_util::{SinkExt, StreamExt};
+    use joyride_oracle_wire::{BroadcastFrame, FeedHealth, WirePayload};
     use tokio::time::timeout;
     use tokio_tungstenite::connect_async;
 
@@ -905,6 +1137,7 @@ mod tests {
             confidence: 0.12,
             publish_time: 1_776_947_696,
             feed_id: "0xef".to_string(),
+            ..Default::default()
         });
         let json = serialize_json(&event).unwrap();
         let frame: BroadcastFrame = serde_json::from_str(&json).unwrap();
@@ -913,11 +1146,77 @@ mod tests {
             other => panic!("expected Price, got {other:?}"),
         }
 
+        let event = OracleEvent::FeedHealth(FeedHealthChange {
+            symbol: "SOL".to_string(),
+            state: FeedHealth::Stale,
+            previous: FeedHealth::Degraded,
+            publish_time: Some(1_776_947_696),
+            age_secs: Some(31),
+        });
+        let frame: BroadcastFrame = serde_json::from_str(&serialize_json(&event).unwrap()).unwrap();
+        match frame.payload {
+            WirePayload::FeedHealth(change) => assert_eq!(change.state, FeedHealth::Stale),
+            other => panic!("expected FeedHealth, got {other:?}"),
+        }
+
         let hb = heartbeat_json();
         let frame: BroadcastFrame = serde_json::from_str(&hb).unwrap();
         assert!(matches!(frame.payload, WirePayload::Heartbeat));
     }
 
+    #[tokio::test]
+    async fn serves_proof_bundle_over_http() {
+        use joyride_oracle_core::{SettlementProof, TwapCalculator};
+
+        let dir =
+            std::env::temp_dir().join(format!("joyride-server-proofs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let proofs = ProofStore::open(&dir).unwrap();
+        let mut calc = TwapCalculator::with_window(10);
+        for i in 0..10 {
+            calc.record(&PriceUpdate {
+                symbol: "SOL".to_string(),
+                price: 100.0 + i as f64,
+                confidence: 0.01,
+                publish_time: 1000 + i,
+                feed_id: "0x123".to_string(),
+                ..Default::default()
+            });
+        }
+        let proof = SettlementProof::build(&calc, &calc.calculate("SOL", 1009).unwrap());
+        proofs.save(&proof).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_proofs = proofs.clone();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            assert!(is_proof_request(&stream).await);
+            serve_proof(stream, &server_proofs).await.unwrap();
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET /proofs/SOL/1009 HTTP/1.1\r\nHost: oracle\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        server.await.unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        let served: SettlementProof = serde_json::from_str(body).unwrap();
+        assert_eq!(served, proof);
+
+        let (status, _) = proof_response(b"GET /proofs/SOL/1010 HTTP/1.1\r\n\r\n", &proofs);
+        assert_eq!(status, "404 Not Found");
+        let (status, _) = proof_response(b"GET /proofs/SOL HTTP/1.1\r\n\r\n", &proofs);
+        assert_eq!(status, "400 Bad Request");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[tokio::test]
     async fn handle_client_sends_heartbeat_when_idle() {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -967,9 +1266,7 @@ mod tests {
 
         // Send a Ping with a payload
         let ping_payload = b"keepalive".to_vec();
-        ws.send(Message::Ping(ping_payload.clone().into()))
-            .await
-            .unwrap();
+        ws.send(Message::Ping(ping_payload.clone())).await.unwrap();
 
         // Expect a Pong back with the same payload (per RFC 6455 Section 5.5.3)
         let msg = timeout(Duration::from_secs(2), ws.next())
@@ -979,7 +1276,7 @@ mod tests {
             .unwrap();
 
         assert!(
-            matches!(&msg, Message::Pong(data) if &data[..] == &ping_payload[..]),
+            matches!(&msg, Message::Pong(data) if data[..] == ping_payload[..]),
             "expected Pong with matching payload, got {:?}",
             msg
         );
@@ -1002,6 +1299,7 @@ mod tests {
                 confidence: 0.1,
                 publish_time: 100,
                 feed_id: "sol-feed".to_string(),
+                ..Default::default()
             }))
             .await;
         state
@@ -1011,11 +1309,24 @@ mod tests {
                 confidence: 1.5,
                 publish_time: 101,
                 feed_id: "btc-feed".to_string(),
+                ema_price: Some(61_900.0),
+                ema_confidence: Some(1.2),
+                ..Default::default()
+            }))
+            .await;
+        state
+            .cache_ordered_event(&OracleEvent::FeedHealth(FeedHealthChange {
+                symbol: "SOL".to_string(),
+                state: FeedHealth::Stale,
+                previous: FeedHealth::Fresh,
+                publish_time: Some(100),
+                age_secs: Some(31),
             }))
             .await;
         state
             .cache_preview(&TwapPreview {
                 symbol: "ETH".to_string(),
+                window: "30m".to_string(),
                 twap: 3_000.0,
                 sample_count: 10,
                 coverage: 0.9,
@@ -1034,7 +1345,7 @@ mod tests {
             .unwrap();
 
         let mut messages = Vec::new();
-        for _ in 0..3 {
+        for _ in 0..4 {
             let message = timeout(Duration::from_secs(1), ws.next())
                 .await
                 .unwrap()
@@ -1047,8 +1358,8 @@ mod tests {
 
         assert!(matches!(
             &messages[0],
-            OracleEvent::Price(PriceUpdate { symbol, publish_time, .. })
-                if symbol == "BTC" && *publish_time == 101
+            OracleEvent::Price(PriceUpdate { symbol, publish_time, ema_price, .. })
+                if symbol == "BTC" && *publish_time == 101 && *ema_price == Some(61_900.0)
         ));
         assert!(matches!(
             &messages[1],
@@ -1057,6 +1368,11 @@ mod tests {
         ));
         assert!(matches!(
             &messages[2],
+            OracleEvent::FeedHealth(FeedHealthChange { symbol, state: FeedHealth::Stale, .. })
+                if symbol == "SOL"
+        ));
+        assert!(matches!(
+            &messages[3],
             OracleEvent::TwapPreview(TwapPreview { symbol, sample_count, .. })
                 if symbol == "ETH" && *sample_count == 10
         ));
@@ -1065,14 +1381,37 @@ mod tests {
         server.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn publish_times_track_any_configured_symbol() {
+        let state = ServerState::default();
+        let mut stats = ClientStats::default();
+        for (symbol, publish_time) in [("DOGE", 7), ("AVAX", 5), ("DOGE", 9)] {
+            let event = OracleEvent::Price(PriceUpdate {
+                symbol: symbol.to_string(),
+                price: 1.0,
+                confidence: 0.01,
+                publish_time,
+                ..Default::default()
+            });
+            state.cache_ordered_event(&event).await;
+            stats.record_ordered_event(&event);
+        }
+
+        assert_eq!(stats.last_publish_times.to_string(), "AVAX=5 DOGE=9");
+        let (snapshot, cached_prices, _) = state.publish_time_snapshot().await;
+        assert_eq!(snapshot, stats.last_publish_times);
+        assert_eq!(cached_prices, 2);
+    }
+
     #[test]
-    fn drain_latest_previews_keeps_only_latest_per_asset() {
+    fn drain_latest_previews_keeps_only_latest_per_asset_and_window() {
         let (preview_tx, _) = broadcast::channel::<TwapPreview>(16);
         let mut preview_rx = preview_tx.subscribe();
         let state = ServerState::default();
 
         let _ = preview_tx.send(TwapPreview {
             symbol: "BTC".to_string(),
+            window: "30m".to_string(),
             twap: 100.0,
             sample_count: 1,
             coverage: 1.0,
@@ -1081,12 +1420,21 @@ mod tests {
 
         let _ = preview_tx.send(TwapPreview {
             symbol: "ETH".to_string(),
+            window: "30m".to_string(),
             twap: 200.0,
             sample_count: 2,
             coverage: 0.9,
         });
         let _ = preview_tx.send(TwapPreview {
             symbol: "BTC".to_string(),
+            window: "1m".to_string(),
+            twap: 99.0,
+            sample_count: 60,
+            coverage: 1.0,
+        });
+        let _ = preview_tx.send(TwapPreview {
+            symbol: "BTC".to_string(),
+            window: "30m".to_string(),
             twap: 101.0,
             sample_count: 3,
             coverage: 1.0,
@@ -1102,11 +1450,15 @@ mod tests {
             &mut ClientStats::default(),
         );
 
-        assert_eq!(previews.len(), 2);
+        assert_eq!(previews.len(), 3);
         assert_eq!(previews[0].symbol, "BTC");
-        assert_eq!(previews[0].twap, 101.0);
-        assert_eq!(previews[1].symbol, "ETH");
-        assert_eq!(previews[1].twap, 200.0);
+        assert_eq!(previews[0].window, "1m");
+        assert_eq!(previews[0].twap, 99.0);
+        assert_eq!(previews[1].symbol, "BTC");
+        assert_eq!(previews[1].window, "30m");
+        assert_eq!(previews[1].twap, 101.0);
+        assert_eq!(previews[2].symbol, "ETH");
+        assert_eq!(previews[2].twap, 200.0);
     }
 
     #[test]
@@ -1130,6 +1482,7 @@ mod tests {
         state
             .cache_preview(&TwapPreview {
                 symbol: "BTC".to_string(),
+                window: "30m".to_string(),
                 twap: 100.0,
                 sample_count: 1,
                 coverage: 1.0,